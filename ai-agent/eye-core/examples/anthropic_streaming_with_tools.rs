@@ -1,8 +1,9 @@
 use anyhow::Result;
-use eye::streaming::stream_to_stdout;
-use eye::{completion::ToolDefinition, providers, streaming::StreamingPrompt, tool::Tool};
+use eye::{agent::AgentEvent, completion::ToolDefinition, providers, tool::Tool};
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::io::Write;
 
 #[derive(Deserialize)]
 struct OperationArgs {
@@ -105,7 +106,21 @@ async fn main() -> Result<(), anyhow::Error> {
         .build();
 
     println!("Calculate 2 - 5");
-    let mut stream = calculator_agent.stream_prompt("Calculate 2 - 5").await?;
-    stream_to_stdout(calculator_agent, &mut stream).await?;
+    let mut events = Box::pin(calculator_agent.stream_chat_events("Calculate 2 - 5", vec![]));
+    while let Some(event) = events.next().await {
+        match event? {
+            AgentEvent::TextDelta(text) => {
+                print!("{}", text);
+                std::io::stdout().flush()?;
+            }
+            AgentEvent::ToolCallStarted(call) => {
+                println!("\nCalling {} with {}", call.name, call.arguments)
+            }
+            AgentEvent::ToolResult(result) => println!("Result: {}", result.content),
+            AgentEvent::FinalAnswer(transcript) => {
+                println!("\n\nUsed {} tokens", transcript.usage.total_tokens())
+            }
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,23 @@
+use eye::{
+    providers::openai::{self, GPT_4O},
+    streaming::{stream_to_stdout, StreamingPrompt},
+};
+
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
+    // Create streaming agent with a single context prompt
+    let agent = openai::Client::from_env()
+        .agent(GPT_4O)
+        .preamble("Be precise and concise.")
+        .temperature(0.5)
+        .build();
+
+    // Stream the response and print chunks as they arrive
+    let mut stream = agent
+        .stream_prompt("When and where and what type is the next solar eclipse?")
+        .await?;
+
+    stream_to_stdout(agent, &mut stream).await?;
+
+    Ok(())
+}
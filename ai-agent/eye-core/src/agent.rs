@@ -12,9 +12,16 @@
 //! completions responses and prompts. The [Agent] struct also implements the [Chat] trait, which allows it to
 //! be used for generating chat completions.
 //!
+//! When prompted or chatted with, the agent runs a tool-calling loop: the tool calls made by the model are
+//! executed concurrently, their results are sent back to the model, and the model is queried again until it
+//! answers with a message or the agent's maximum depth is reached. Use [Agent::chat_transcript] to get every message
+//! exchanged along the way, or [Agent::stream_chat_events] to follow the text, tool calls and tool results as they
+//! come.
+//!
 //! The [AgentBuilder] implements the builder pattern for creating instances of [Agent].
-//! It allows configuring the model, preamble, context documents, tools, temperature, and additional parameters
-//! before building the agent.
+//! It allows configuring the model, preamble, context documents, tools, temperature and other sampling
+//! parameters, and additional parameters before building the agent. Providers return a
+//! [CompletionError::UnsupportedParameter] for sampling parameters they do not support.
 //!
 //! # Example
 //! ```rust
@@ -33,6 +40,7 @@
 //!     .tool(tool1)
 //!     .tool(tool2)
 //!     .temperature(0.8)
+//!     .max_depth(3)
 //!     .additional_params(json!({"foo": "bar"}))
 //!     .build();
 //!
@@ -108,18 +116,20 @@
 //! ```
 use std::collections::HashMap;
 
-use futures::{stream, StreamExt, TryStreamExt};
+use async_stream::try_stream;
+use futures::{stream, Stream, StreamExt, TryStreamExt};
 
 use crate::{
     completion::{
-        Chat, Completion, CompletionError, CompletionModel, CompletionRequestBuilder,
-        CompletionResponse, Document, Message, ModelChoice, Prompt, PromptError,
+        Chat, Completion, CompletionError, CompletionModel, CompletionRequest,
+        CompletionRequestBuilder, CompletionResponse, Document, Message, ModelChoice, Pricing,
+        Prompt, PromptError, ToolCall, ToolChoice, ToolResult, Usage,
     },
     streaming::{
-        StreamingChat, StreamingCompletion, StreamingCompletionModel, StreamingPrompt,
-        StreamingResult,
+        StreamingChat, StreamingChoice, StreamingCompletion, StreamingCompletionModel,
+        StreamingPrompt, StreamingResult,
     },
-    tool::{Tool, ToolSet},
+    tool::{Tool, ToolError, ToolSet, ToolSetError},
     vector_store::{VectorStoreError, VectorStoreIndexDyn},
 };
 
@@ -156,16 +166,69 @@ pub struct Agent<M: CompletionModel> {
     temperature: Option<f64>,
     /// Maximum number of tokens for the completion
     max_tokens: Option<u64>,
+    /// Sequences at which the model stops generating
+    stop: Vec<String>,
+    /// Top p (nucleus sampling) of the model
+    top_p: Option<f64>,
+    /// Seed of the model, for reproducible sampling
+    seed: Option<u64>,
+    /// Frequency penalty of the model
+    frequency_penalty: Option<f64>,
+    /// Presence penalty of the model
+    presence_penalty: Option<f64>,
+    /// Whether the model may, must or must not call the tools on the first turn of a chat
+    tool_choice: Option<ToolChoice>,
     /// Additional parameters to be passed to the model
     additional_params: Option<serde_json::Value>,
     /// List of vector store, with the sample number
     dynamic_context: Vec<(usize, Box<dyn VectorStoreIndexDyn>)>,
     /// Dynamic tools
     dynamic_tools: Vec<(usize, Box<dyn VectorStoreIndexDyn>)>,
+    /// Maximum number of tool-calling turns in a single chat
+    max_depth: usize,
+    /// Maximum number of tool calls from a single turn run at the same time
+    tool_concurrency: usize,
+    /// Whether a failing tool call fails the chat instead of being reported to the model
+    strict_tools: bool,
+    /// Prices of the model, used to compute the cost of a chat
+    pricing: Option<Pricing>,
     /// Actual tool implementations
     pub tools: ToolSet,
 }
 
+/// Default maximum number of tool-calling turns an [Agent] runs before giving up
+pub const DEFAULT_MAX_DEPTH: usize = 5;
+
+/// Default maximum number of tool calls an [Agent] runs at the same time
+pub const DEFAULT_TOOL_CONCURRENCY: usize = 4;
+
+/// The result of a chat with an [Agent]: its final answer and the messages that led to it.
+#[derive(Clone, Debug)]
+pub struct Transcript {
+    /// The model's final answer
+    pub output: String,
+    /// The chat history, followed by the prompt, every tool call and tool result, and the final
+    /// answer, in order. Can be passed as chat history for the next prompt.
+    pub messages: Vec<Message>,
+    /// The tokens used across all the turns of the chat
+    pub usage: Usage,
+    /// The cost of the chat in dollars, if the agent was given the model's [Pricing]
+    pub cost: Option<f64>,
+}
+
+/// An event of a streamed chat with an [Agent], see [Agent::stream_chat_events]
+#[derive(Clone, Debug)]
+pub enum AgentEvent {
+    /// A chunk of text from the model, either its answer or what it writes before calling tools
+    TextDelta(String),
+    /// The model called a tool. The calls of a turn are run once the model has made all of them.
+    ToolCallStarted(ToolCall),
+    /// A tool call finished. The results of a turn come in the order of its calls.
+    ToolResult(ToolResult),
+    /// The model answered, ending the chat
+    FinalAnswer(Transcript),
+}
+
 impl<M: CompletionModel> Completion<M> for Agent<M> {
     async fn completion(
         &self,
@@ -245,6 +308,12 @@ impl<M: CompletionModel> Completion<M> for Agent<M> {
             .tools([static_tools.clone(), dynamic_tools].concat())
             .temperature_opt(self.temperature)
             .max_tokens_opt(self.max_tokens)
+            .stop_sequences(self.stop.clone())
+            .top_p_opt(self.top_p)
+            .seed_opt(self.seed)
+            .frequency_penalty_opt(self.frequency_penalty)
+            .presence_penalty_opt(self.presence_penalty)
+            .tool_choice_opt(self.tool_choice.clone())
             .additional_params_opt(self.additional_params.clone()))
     }
 }
@@ -263,19 +332,191 @@ impl<M: CompletionModel> Prompt for &Agent<M> {
 
 impl<M: CompletionModel> Chat for Agent<M> {
     async fn chat(&self, prompt: &str, chat_history: Vec<Message>) -> Result<String, PromptError> {
-        match self.completion(prompt, chat_history).await?.send().await? {
-            CompletionResponse {
-                choice: ModelChoice::Message(msg),
-                ..
-            } => Ok(msg),
-            CompletionResponse {
-                choice: ModelChoice::ToolCall(toolname, _, args),
-                ..
-            } => Ok(self.tools.call(&toolname, args.to_string()).await?),
+        Ok(self.chat_transcript(prompt, chat_history).await?.output)
+    }
+}
+
+impl<M: CompletionModel> Agent<M> {
+    /// Chat with the agent, running tool calls until the model answers with a message.
+    ///
+    /// The tool calls of each turn are executed concurrently through the agent's [ToolSet], up to
+    /// the agent's tool concurrency, and the calls and their results are appended to the
+    /// conversation before the model is queried again. A call that fails has its error sent to
+    /// the model as its result, so that the model can retry or answer anyway, unless the agent
+    /// has [strict tools](AgentBuilder::strict_tools). A call to a tool the agent does not have
+    /// always fails the chat. Context documents and tools are looked up once, for the user's prompt. Returns a
+    /// [PromptError::MaxDepthError] if the model is still calling tools after `max_depth` turns.
+    pub async fn chat_transcript(
+        &self,
+        prompt: &str,
+        chat_history: Vec<Message>,
+    ) -> Result<Transcript, PromptError> {
+        let mut request = self.completion(prompt, chat_history.clone()).await?.build();
+        let mut messages = chat_history;
+        messages.push(Message::user(prompt));
+
+        let mut usage = Usage::default();
+        let mut depth = 0;
+        loop {
+            let response = self.model.completion(request.clone()).await?;
+            usage += response.usage;
+            let (text, calls) = match response.choice {
+                ModelChoice::Message(output) => {
+                    messages.push(Message::assistant(output.clone()));
+                    return Ok(Transcript {
+                        output,
+                        messages,
+                        usage,
+                        cost: self.pricing.map(|pricing| usage.cost(&pricing)),
+                    });
+                }
+                ModelChoice::ToolCalls { text, calls } => (text, calls),
+            };
+            if depth == self.max_depth {
+                return Err(PromptError::MaxDepthError(self.max_depth));
+            }
+
+            let results = self.run_tools(calls.clone()).try_collect().await?;
+
+            let call = Message::from(ModelChoice::ToolCalls { text, calls });
+            let result = Message::Tool { content: results };
+            messages.extend([call.clone(), result.clone()]);
+            continue_after_tools(&mut request, call, result);
+            depth += 1;
+        }
+    }
+
+    /// Runs tool calls through the agent's [ToolSet], up to the agent's tool concurrency, and
+    /// streams their results in the order of the calls. Errors of known tools become their
+    /// results unless the agent has strict tools.
+    fn run_tools(
+        &self,
+        calls: Vec<ToolCall>,
+    ) -> impl Stream<Item = Result<ToolResult, PromptError>> + Send + '_ {
+        let tools = &self.tools;
+        let strict = self.strict_tools;
+        stream::iter(calls)
+            .map(move |call| async move {
+                let content = match tools.call(&call.name, call.arguments.to_string()).await {
+                    Ok(output) => output,
+                    Err(error @ ToolSetError::ToolNotFoundError(_)) => Err(error)?,
+                    Err(error) if strict => Err(error)?,
+                    Err(error) => {
+                        tracing::warn!("Tool call {} to {} failed: {}", call.id, call.name, error);
+                        tool_error_content(&error)
+                    }
+                };
+                Ok(ToolResult {
+                    id: call.id,
+                    content,
+                })
+            })
+            .buffered(self.tool_concurrency.max(1))
+    }
+}
+
+impl<M: StreamingCompletionModel> Agent<M> {
+    /// Chat with the agent like [Agent::chat_transcript], streaming the chat as it goes: the
+    /// model's text as it is written, its tool calls and their results, and finally its answer
+    /// along with the transcript of the chat. The stream ends after the final answer or the first
+    /// error, and dropping it stops the chat.
+    pub fn stream_chat_events<'a>(
+        &'a self,
+        prompt: &'a str,
+        chat_history: Vec<Message>,
+    ) -> impl Stream<Item = Result<AgentEvent, PromptError>> + Send + 'a {
+        try_stream! {
+            let mut request = self.completion(prompt, chat_history.clone()).await?.build();
+            let mut messages = chat_history;
+            messages.push(Message::user(prompt));
+
+            let mut usage = Usage::default();
+            let mut depth = 0;
+            loop {
+                let mut chunks = self.model.stream(request.clone()).await?;
+                let mut response = None;
+                while let Some(chunk) = chunks.next().await {
+                    match chunk? {
+                        StreamingChoice::Message(text) => yield AgentEvent::TextDelta(text),
+                        StreamingChoice::ToolCall(name, id, arguments) => {
+                            yield AgentEvent::ToolCallStarted(ToolCall {
+                                id,
+                                name,
+                                arguments,
+                            })
+                        }
+                        StreamingChoice::Final(final_response) => response = Some(final_response),
+                    }
+                }
+                let response: CompletionResponse<_> = response
+                    .ok_or_else(|| {
+                        CompletionError::ResponseError(
+                            "Stream ended without a final response".into(),
+                        )
+                    })?
+                    .into();
+                usage += response.usage;
+
+                let (text, calls) = match response.choice {
+                    ModelChoice::Message(output) => {
+                        messages.push(Message::assistant(output.clone()));
+                        yield AgentEvent::FinalAnswer(Transcript {
+                            output,
+                            messages,
+                            usage,
+                            cost: self.pricing.map(|pricing| usage.cost(&pricing)),
+                        });
+                        break;
+                    }
+                    ModelChoice::ToolCalls { text, calls } => (text, calls),
+                };
+                if depth == self.max_depth {
+                    Err(PromptError::MaxDepthError(self.max_depth))?;
+                }
+
+                let mut results = vec![];
+                for await result in self.run_tools(calls.clone()) {
+                    let result = result?;
+                    results.push(result.clone());
+                    yield AgentEvent::ToolResult(result);
+                }
+
+                let call = Message::from(ModelChoice::ToolCalls { text, calls });
+                let result = Message::Tool { content: results };
+                messages.extend([call.clone(), result.clone()]);
+                continue_after_tools(&mut request, call, result);
+                depth += 1;
+            }
         }
     }
 }
 
+/// The result sent to the model for a failed tool call
+fn tool_error_content(error: &ToolSetError) -> String {
+    match error {
+        ToolSetError::ToolCallError(ToolError::ToolCallError(error)) => format!("Error: {error}"),
+        error => format!("Error: {error}"),
+    }
+}
+
+/// Continues a chat after a turn of tool calls: the prompt and the tool calls move to the chat
+/// history, and the tool results become the prompt
+fn continue_after_tools(request: &mut CompletionRequest, call: Message, result: Message) {
+    // The documents are now part of the user prompt in the history, so they are
+    // not attached again to the tool result.
+    request.chat_history.push(request.prompt_with_context());
+    request.chat_history.push(call);
+    request.prompt = result;
+    request.documents.clear();
+    // A forced tool call only applies to the first turn, so that the model can answer
+    if matches!(
+        request.tool_choice,
+        Some(ToolChoice::Required | ToolChoice::Specific(_))
+    ) {
+        request.tool_choice = Some(ToolChoice::Auto);
+    }
+}
+
 /// A builder for creating an agent
 ///
 /// # Example
@@ -310,12 +551,32 @@ pub struct AgentBuilder<M: CompletionModel> {
     additional_params: Option<serde_json::Value>,
     /// Maximum number of tokens for the completion
     max_tokens: Option<u64>,
+    /// Sequences at which the model stops generating
+    stop: Vec<String>,
+    /// Top p (nucleus sampling) of the model
+    top_p: Option<f64>,
+    /// Seed of the model, for reproducible sampling
+    seed: Option<u64>,
+    /// Frequency penalty of the model
+    frequency_penalty: Option<f64>,
+    /// Presence penalty of the model
+    presence_penalty: Option<f64>,
+    /// Whether the model may, must or must not call the tools on the first turn of a chat
+    tool_choice: Option<ToolChoice>,
     /// List of vector store, with the sample number
     dynamic_context: Vec<(usize, Box<dyn VectorStoreIndexDyn>)>,
     /// Dynamic tools
     dynamic_tools: Vec<(usize, Box<dyn VectorStoreIndexDyn>)>,
     /// Temperature of the model
     temperature: Option<f64>,
+    /// Maximum number of tool-calling turns in a single chat
+    max_depth: usize,
+    /// Maximum number of tool calls from a single turn run at the same time
+    tool_concurrency: usize,
+    /// Whether a failing tool call fails the chat instead of being reported to the model
+    strict_tools: bool,
+    /// Prices of the model, used to compute the cost of a chat
+    pricing: Option<Pricing>,
     /// Actual tool implementations
     tools: ToolSet,
 }
@@ -329,9 +590,19 @@ impl<M: CompletionModel> AgentBuilder<M> {
             static_tools: vec![],
             temperature: None,
             max_tokens: None,
+            stop: vec![],
+            top_p: None,
+            seed: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            tool_choice: None,
             additional_params: None,
             dynamic_context: vec![],
             dynamic_tools: vec![],
+            max_depth: DEFAULT_MAX_DEPTH,
+            tool_concurrency: DEFAULT_TOOL_CONCURRENCY,
+            strict_tools: false,
+            pricing: None,
             tools: ToolSet::default(),
         }
     }
@@ -407,6 +678,71 @@ impl<M: CompletionModel> AgentBuilder<M> {
         self
     }
 
+    /// Add a sequence at which the model stops generating
+    pub fn stop_sequence(mut self, stop: &str) -> Self {
+        self.stop.push(stop.into());
+        self
+    }
+
+    /// Set the top p (nucleus sampling) of the model
+    pub fn top_p(mut self, top_p: f64) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+
+    /// Set the seed of the model, for reproducible sampling where the provider supports it
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Set the frequency penalty of the model
+    pub fn frequency_penalty(mut self, frequency_penalty: f64) -> Self {
+        self.frequency_penalty = Some(frequency_penalty);
+        self
+    }
+
+    /// Set the presence penalty of the model
+    pub fn presence_penalty(mut self, presence_penalty: f64) -> Self {
+        self.presence_penalty = Some(presence_penalty);
+        self
+    }
+
+    /// Set the maximum number of tool-calling turns in a single chat (default: [DEFAULT_MAX_DEPTH]).
+    /// With a depth of 0, any tool call made by the model is an error.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Set the maximum number of tool calls from a single turn run at the same time
+    /// (default: [DEFAULT_TOOL_CONCURRENCY]). A limit of 1 runs the calls one after the other.
+    pub fn tool_concurrency(mut self, tool_concurrency: usize) -> Self {
+        self.tool_concurrency = tool_concurrency;
+        self
+    }
+
+    /// Set whether a failing tool call fails the chat. By default the error is sent to the model
+    /// as the call's result instead. Calls to unknown tools fail the chat either way.
+    pub fn strict_tools(mut self, strict_tools: bool) -> Self {
+        self.strict_tools = strict_tools;
+        self
+    }
+
+    /// Set whether the model may, must or must not call the agent's tools. A forced tool call
+    /// ([ToolChoice::Required] or [ToolChoice::Specific]) only applies to the first turn of a
+    /// chat: the model decides on the following turns.
+    pub fn tool_choice(mut self, tool_choice: ToolChoice) -> Self {
+        self.tool_choice = Some(tool_choice);
+        self
+    }
+
+    /// Set the prices of the model, so that the cost of each chat is reported in its [Transcript]
+    pub fn pricing(mut self, pricing: Pricing) -> Self {
+        self.pricing = Some(pricing);
+        self
+    }
+
     /// Set additional parameters to be passed to the model
     pub fn additional_params(mut self, params: serde_json::Value) -> Self {
         self.additional_params = Some(params);
@@ -422,9 +758,19 @@ impl<M: CompletionModel> AgentBuilder<M> {
             static_tools: self.static_tools,
             temperature: self.temperature,
             max_tokens: self.max_tokens,
+            stop: self.stop,
+            top_p: self.top_p,
+            seed: self.seed,
+            frequency_penalty: self.frequency_penalty,
+            presence_penalty: self.presence_penalty,
+            tool_choice: self.tool_choice,
             additional_params: self.additional_params,
             dynamic_context: self.dynamic_context,
             dynamic_tools: self.dynamic_tools,
+            max_depth: self.max_depth,
+            tool_concurrency: self.tool_concurrency,
+            strict_tools: self.strict_tools,
+            pricing: self.pricing,
             tools: self.tools,
         }
     }
@@ -460,3 +806,447 @@ impl<M: StreamingCompletionModel> StreamingChat for Agent<M> {
             .await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::completion::{tests::ScriptedModel, AssistantContent, ToolDefinition};
+
+    #[derive(serde::Deserialize)]
+    struct AddArgs {
+        x: i32,
+        y: i32,
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("Math error")]
+    struct MathError;
+
+    /// Adds numbers, keeping track of how many calls run at the same time. Fails on overflow.
+    #[derive(Clone, Default)]
+    struct Adder {
+        running: Arc<AtomicUsize>,
+        max_running: Arc<AtomicUsize>,
+    }
+
+    impl Tool for Adder {
+        const NAME: &'static str = "add";
+
+        type Error = MathError;
+        type Args = AddArgs;
+        type Output = i32;
+
+        async fn definition(&self, _prompt: String) -> ToolDefinition {
+            ToolDefinition {
+                name: "add".to_string(),
+                description: "Add x and y together".to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "x": { "type": "number" },
+                        "y": { "type": "number" }
+                    }
+                }),
+            }
+        }
+
+        async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_running.fetch_max(running, Ordering::SeqCst);
+            // Let the other calls start before this one finishes
+            tokio::task::yield_now().await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            args.x.checked_add(args.y).ok_or(MathError)
+        }
+    }
+
+    fn add(id: &str, x: i32, y: i32) -> ToolCall {
+        ToolCall {
+            id: id.into(),
+            name: "add".into(),
+            arguments: json!({ "x": x, "y": y }),
+        }
+    }
+
+    fn add_call(id: &str, x: i32, y: i32) -> ModelChoice {
+        ModelChoice::ToolCalls {
+            text: None,
+            calls: vec![add(id, x, y)],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chat_without_tool_call() {
+        let model = ScriptedModel::new([ModelChoice::Message("Hello!".into())]);
+        let agent = AgentBuilder::new(model.clone())
+            .tool(Adder::default())
+            .build();
+
+        let response = agent.chat("Hi", vec![]).await.unwrap();
+
+        assert_eq!(response, "Hello!");
+        assert_eq!(model.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_chat_sends_tool_results_back_to_model() {
+        let model = ScriptedModel::new([
+            add_call("call_1", 2, 3),
+            add_call("call_2", 5, 4),
+            ModelChoice::Message("The result is 9".into()),
+        ]);
+        let agent = AgentBuilder::new(model.clone())
+            .context("Numbers are integers")
+            .tool(Adder::default())
+            .build();
+
+        let history = vec![Message::user("Hi"), Message::assistant("Hello!")];
+        let transcript = agent
+            .chat_transcript("What is 2 + 3 + 4?", history.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(transcript.output, "The result is 9");
+        assert_eq!(
+            transcript.messages,
+            [
+                history.clone(),
+                vec![
+                    Message::user("What is 2 + 3 + 4?"),
+                    Message::tool_call("call_1", "add", json!({ "x": 2, "y": 3 })),
+                    Message::tool_result("call_1", "5"),
+                    Message::tool_call("call_2", "add", json!({ "x": 5, "y": 4 })),
+                    Message::tool_result("call_2", "9"),
+                    Message::assistant("The result is 9"),
+                ],
+            ]
+            .concat()
+        );
+
+        let requests = model.requests();
+        assert_eq!(requests.len(), 3);
+
+        // The first request carries the user prompt and the context documents
+        assert_eq!(requests[0].prompt, Message::user("What is 2 + 3 + 4?"));
+        assert_eq!(requests[0].chat_history, history);
+        assert_eq!(requests[0].documents.len(), 1);
+
+        // Later requests continue from the last tool result, with the documents kept in the
+        // user prompt and the tools still available
+        let last = &requests[2];
+        assert_eq!(last.prompt, Message::tool_result("call_2", "9"));
+        assert!(last.documents.is_empty());
+        assert_eq!(last.tools.len(), 1);
+        assert_eq!(last.chat_history.len(), 6);
+        assert!(last.chat_history[2]
+            .text()
+            .starts_with("<attachments>\n<file id: static_doc_0>"));
+        assert_eq!(last.chat_history[5], transcript.messages[5]);
+    }
+
+    #[tokio::test]
+    async fn test_chat_runs_parallel_tool_calls() {
+        let calls = vec![
+            add("call_1", 1, 2),
+            add("call_2", 3, 4),
+            add("call_3", 5, 6),
+        ];
+        let model = ScriptedModel::new([
+            ModelChoice::ToolCalls {
+                text: Some("Adding them up".into()),
+                calls: calls.clone(),
+            },
+            ModelChoice::Message("3, 7 and 11".into()),
+        ]);
+        let adder = Adder::default();
+        let agent = AgentBuilder::new(model.clone())
+            .tool(adder.clone())
+            .tool_concurrency(2)
+            .build();
+
+        let transcript = agent
+            .chat_transcript("Add the pairs", vec![])
+            .await
+            .unwrap();
+
+        // All the calls go in one assistant message, and all the results in one tool message
+        assert_eq!(
+            transcript.messages,
+            [
+                Message::user("Add the pairs"),
+                Message::Assistant {
+                    content: [
+                        vec![AssistantContent::Text {
+                            text: "Adding them up".into()
+                        }],
+                        calls.into_iter().map(AssistantContent::ToolCall).collect(),
+                    ]
+                    .concat()
+                },
+                Message::Tool {
+                    content: vec![
+                        ToolResult {
+                            id: "call_1".into(),
+                            content: "3".into()
+                        },
+                        ToolResult {
+                            id: "call_2".into(),
+                            content: "7".into()
+                        },
+                        ToolResult {
+                            id: "call_3".into(),
+                            content: "11".into()
+                        },
+                    ]
+                },
+                Message::assistant("3, 7 and 11"),
+            ]
+        );
+        assert_eq!(model.requests()[1].prompt, transcript.messages[2]);
+        assert_eq!(adder.max_running.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_chat_reports_usage_and_cost() {
+        let model =
+            ScriptedModel::new([add_call("call_1", 2, 3), ModelChoice::Message("5".into())]);
+        let agent = AgentBuilder::new(model)
+            .tool(Adder::default())
+            .pricing(Pricing::new(2.0, 10.0).cached_prompt(1.0))
+            .build();
+
+        let transcript = agent
+            .chat_transcript("What is 2 + 3?", vec![])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            transcript.usage,
+            Usage {
+                prompt_tokens: 200,
+                completion_tokens: 20,
+                cached_tokens: 100,
+                reasoning_tokens: 0,
+            }
+        );
+        // 100 uncached prompt tokens at $2, 100 cached at $1 and 20 completion tokens at $10
+        // per million tokens
+        assert_eq!(transcript.cost, Some(0.0005));
+    }
+
+    #[tokio::test]
+    async fn test_chat_forces_tool_call_on_first_turn_only() {
+        let model =
+            ScriptedModel::new([add_call("call_1", 2, 3), ModelChoice::Message("5".into())]);
+        let agent = AgentBuilder::new(model.clone())
+            .tool(Adder::default())
+            .tool_choice(ToolChoice::Specific("add".into()))
+            .build();
+
+        agent.chat("What is 2 + 3?", vec![]).await.unwrap();
+
+        let requests = model.requests();
+        assert_eq!(
+            requests[0].tool_choice,
+            Some(ToolChoice::Specific("add".into()))
+        );
+        assert_eq!(requests[1].tool_choice, Some(ToolChoice::Auto));
+    }
+
+    #[tokio::test]
+    async fn test_chat_stops_at_max_depth() {
+        let model = ScriptedModel::new([
+            add_call("call_1", 1, 1),
+            add_call("call_2", 2, 2),
+            add_call("call_3", 3, 3),
+        ]);
+        let agent = AgentBuilder::new(model.clone())
+            .tool(Adder::default())
+            .max_depth(1)
+            .build();
+
+        let error = agent.chat("Keep adding", vec![]).await.unwrap_err();
+
+        assert!(matches!(error, PromptError::MaxDepthError(1)));
+        assert_eq!(model.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_chat_sends_tool_errors_back_to_model() {
+        let model = ScriptedModel::new([
+            add_call("call_1", i32::MAX, 1),
+            ModelChoice::Message("That number is too big".into()),
+        ]);
+        let agent = AgentBuilder::new(model.clone())
+            .tool(Adder::default())
+            .build();
+
+        let transcript = agent
+            .chat_transcript("What is 2147483647 + 1?", vec![])
+            .await
+            .unwrap();
+
+        assert_eq!(transcript.output, "That number is too big");
+        assert_eq!(
+            model.requests()[1].prompt,
+            Message::tool_result("call_1", "Error: Math error")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_chat_strict_tools() {
+        let model = ScriptedModel::new([add_call("call_1", i32::MAX, 1)]);
+        let agent = AgentBuilder::new(model.clone())
+            .tool(Adder::default())
+            .strict_tools(true)
+            .build();
+
+        let error = agent.prompt("What is 2147483647 + 1?").await.unwrap_err();
+
+        assert!(matches!(
+            error,
+            PromptError::ToolError(ToolSetError::ToolCallError(_))
+        ));
+        assert_eq!(model.requests().len(), 1);
+    }
+
+    /// Unlike a failing tool, an unknown tool fails the chat even without strict tools
+    #[tokio::test]
+    async fn test_chat_unknown_tool() {
+        let model = ScriptedModel::new([ModelChoice::ToolCalls {
+            text: None,
+            calls: vec![ToolCall {
+                id: "call_1".into(),
+                name: "subtract".into(),
+                arguments: json!({}),
+            }],
+        }]);
+        let agent = AgentBuilder::new(model).tool(Adder::default()).build();
+
+        let error = agent.prompt("What is 3 - 1?").await.unwrap_err();
+
+        assert!(matches!(
+            error,
+            PromptError::ToolError(ToolSetError::ToolNotFoundError(name)) if name == "subtract"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_stream_chat_events() {
+        let model = ScriptedModel::new([
+            ModelChoice::ToolCalls {
+                text: Some("Adding them up".into()),
+                calls: vec![add("call_1", 2, 3), add("call_2", 5, 4)],
+            },
+            ModelChoice::Message("5 and 9".into()),
+        ]);
+        let agent = AgentBuilder::new(model.clone())
+            .tool(Adder::default())
+            .pricing(Pricing::new(2.0, 10.0).cached_prompt(1.0))
+            .build();
+
+        let events = agent
+            .stream_chat_events("Add the pairs", vec![])
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+
+        let (events, final_answer) = events.split_at(events.len() - 1);
+        assert_eq!(
+            events
+                .iter()
+                .map(|event| match event {
+                    AgentEvent::TextDelta(text) => format!("text {text}"),
+                    AgentEvent::ToolCallStarted(call) => format!("call {}", call.id),
+                    AgentEvent::ToolResult(result) => {
+                        format!("result {} {}", result.id, result.content)
+                    }
+                    AgentEvent::FinalAnswer(_) => "final".into(),
+                })
+                .collect::<Vec<_>>(),
+            [
+                "text Adding ",
+                "text them ",
+                "text up",
+                "call call_1",
+                "call call_2",
+                "result call_1 5",
+                "result call_2 9",
+                "text 5 ",
+                "text and ",
+                "text 9",
+            ]
+        );
+
+        let AgentEvent::FinalAnswer(transcript) = &final_answer[0] else {
+            panic!("Expected the final answer, got {:?}", final_answer[0]);
+        };
+        assert_eq!(transcript.output, "5 and 9");
+        assert_eq!(
+            transcript.messages,
+            [
+                Message::user("Add the pairs"),
+                Message::from(ModelChoice::ToolCalls {
+                    text: Some("Adding them up".into()),
+                    calls: vec![add("call_1", 2, 3), add("call_2", 5, 4)],
+                }),
+                Message::Tool {
+                    content: vec![
+                        ToolResult {
+                            id: "call_1".into(),
+                            content: "5".into()
+                        },
+                        ToolResult {
+                            id: "call_2".into(),
+                            content: "9".into()
+                        },
+                    ]
+                },
+                Message::assistant("5 and 9"),
+            ]
+        );
+        assert_eq!(transcript.usage.prompt_tokens, 200);
+        assert_eq!(transcript.cost, Some(0.0005));
+
+        // The model is sent the tool results
+        let requests = model.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].prompt, transcript.messages[2]);
+    }
+
+    #[tokio::test]
+    async fn test_stream_chat_events_unknown_tool() {
+        let model = ScriptedModel::new([ModelChoice::ToolCalls {
+            text: None,
+            calls: vec![ToolCall {
+                id: "call_1".into(),
+                name: "subtract".into(),
+                arguments: json!({}),
+            }],
+        }]);
+        let agent = AgentBuilder::new(model).tool(Adder::default()).build();
+
+        let events = agent
+            .stream_chat_events("What is 3 - 1?", vec![])
+            .collect::<Vec<_>>()
+            .await;
+
+        // The call is reported before it fails, and the stream ends with the error
+        assert_eq!(events.len(), 2);
+        assert!(matches!(
+            &events[0],
+            Ok(AgentEvent::ToolCallStarted(call)) if call.name == "subtract"
+        ));
+        assert!(matches!(
+            &events[1],
+            Err(PromptError::ToolError(ToolSetError::ToolNotFoundError(name))) if name == "subtract"
+        ));
+    }
+}
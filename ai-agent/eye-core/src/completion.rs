@@ -53,9 +53,11 @@
 //!         // Handle the completion response as a message
 //!         println!("Received message: {}", message);
 //!     }
-//!     ModelChoice::ToolCall(tool_name, tool_params) => {
-//!         // Handle the completion response as a tool call
-//!         println!("Received tool call: {} {:?}", tool_name, tool_params);
+//!     ModelChoice::ToolCalls { calls, .. } => {
+//!         // Handle the completion response as tool calls
+//!         for call in calls {
+//!             println!("Received tool call: {} {:?}", call.name, call.arguments);
+//!         }
 //!     }
 //! }
 //! ```
@@ -92,6 +94,10 @@ pub enum CompletionError {
     /// Error returned by the completion model provider
     #[error("ProviderError: {0}")]
     ProviderError(String),
+
+    /// A sampling parameter set on the request is not supported by the provider
+    #[error("UnsupportedParameterError: {0} does not support the `{1}` parameter")]
+    UnsupportedParameter(String, SamplingParam),
 }
 
 #[derive(Debug, Error)]
@@ -101,18 +107,225 @@ pub enum PromptError {
 
     #[error("ToolCallError: {0}")]
     ToolError(#[from] ToolSetError),
+
+    /// The model was still calling tools after the maximum number of turns
+    #[error("MaxDepthError: no final answer after {0} tool-calling turns")]
+    MaxDepthError(usize),
 }
 
 // ================================================================
 // Request models
 // ================================================================
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct Message {
-    /// "system", "user", or "assistant"
-    pub role: String,
+/// A message in a chat with a model.
+///
+/// User and assistant messages are made of one or more content parts: users can send text
+/// and images, while the model answers with text and tool calls. The results of those tool
+/// calls are sent back to the model in a [Message::Tool] message.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "role", rename_all = "lowercase")]
+pub enum Message {
+    /// Instructions for the model
+    System { content: String },
+    /// A message from the user
+    User { content: Vec<UserContent> },
+    /// A message from the model
+    Assistant { content: Vec<AssistantContent> },
+    /// The results of tool calls made by the model
+    Tool { content: Vec<ToolResult> },
+}
+
+/// A content part of a [Message::User] message
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum UserContent {
+    Text { text: String },
+    Image(Image),
+}
+
+/// A content part of a [Message::Assistant] message
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AssistantContent {
+    Text { text: String },
+    ToolCall(ToolCall),
+}
+
+/// An image sent to the model
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum Image {
+    /// An image the provider downloads itself
+    Url { url: String },
+    /// Base64-encoded image data, with its MIME type (e.g.: `image/png`)
+    Base64 { media_type: String, data: String },
+}
+
+/// A tool call made by the model
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct ToolCall {
+    /// The id of the call, used to match it with its result. Providers that do not assign ids
+    /// use the tool name and the position of the call in the response, e.g. `add_0`.
+    pub id: String,
+    pub name: String,
+    pub arguments: serde_json::Value,
+}
+
+/// The output of a tool call, sent back to the model
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct ToolResult {
+    /// The id of the [ToolCall] this is the result of
+    pub id: String,
     pub content: String,
 }
 
+impl Message {
+    pub fn system(content: impl Into<String>) -> Self {
+        Message::System {
+            content: content.into(),
+        }
+    }
+
+    /// A user message made of a single text part
+    pub fn user(text: impl Into<String>) -> Self {
+        Message::User {
+            content: vec![UserContent::text(text)],
+        }
+    }
+
+    /// An assistant message made of a single text part
+    pub fn assistant(text: impl Into<String>) -> Self {
+        Message::Assistant {
+            content: vec![AssistantContent::Text { text: text.into() }],
+        }
+    }
+
+    /// An assistant message recording a single tool call made by the model
+    pub fn tool_call(
+        id: impl Into<String>,
+        name: impl Into<String>,
+        arguments: serde_json::Value,
+    ) -> Self {
+        Message::Assistant {
+            content: vec![AssistantContent::ToolCall(ToolCall {
+                id: id.into(),
+                name: name.into(),
+                arguments,
+            })],
+        }
+    }
+
+    /// A tool message returning the output of the tool call `id` to the model
+    pub fn tool_result(id: impl Into<String>, content: impl Into<String>) -> Self {
+        Message::Tool {
+            content: vec![ToolResult {
+                id: id.into(),
+                content: content.into(),
+            }],
+        }
+    }
+
+    /// The text of the message: its text parts (or tool results) joined by newlines
+    pub fn text(&self) -> String {
+        match self {
+            Message::System { content } => content.clone(),
+            Message::User { content } => content
+                .iter()
+                .filter_map(|part| match part {
+                    UserContent::Text { text } => Some(text.as_str()),
+                    UserContent::Image(_) => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Message::Assistant { content } => content
+                .iter()
+                .filter_map(|part| match part {
+                    AssistantContent::Text { text } => Some(text.as_str()),
+                    AssistantContent::ToolCall(_) => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Message::Tool { content } => content
+                .iter()
+                .map(|result| result.content.as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+
+    /// The tool calls made by the model in this message
+    pub fn tool_calls(&self) -> Vec<&ToolCall> {
+        match self {
+            Message::Assistant { content } => content
+                .iter()
+                .filter_map(|part| match part {
+                    AssistantContent::ToolCall(call) => Some(call),
+                    AssistantContent::Text { .. } => None,
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// The images in this message
+    pub fn images(&self) -> Vec<&Image> {
+        match self {
+            Message::User { content } => content
+                .iter()
+                .filter_map(|part| match part {
+                    UserContent::Image(image) => Some(image),
+                    UserContent::Text { .. } => None,
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+impl UserContent {
+    pub fn text(text: impl Into<String>) -> Self {
+        UserContent::Text { text: text.into() }
+    }
+
+    pub fn image_url(url: impl Into<String>) -> Self {
+        UserContent::Image(Image::Url { url: url.into() })
+    }
+
+    pub fn image_base64(media_type: impl Into<String>, data: impl Into<String>) -> Self {
+        UserContent::Image(Image::Base64 {
+            media_type: media_type.into(),
+            data: data.into(),
+        })
+    }
+}
+
+/// The assistant message recording a completion choice: its text, then its tool calls
+impl From<ModelChoice> for Message {
+    fn from(choice: ModelChoice) -> Self {
+        match choice {
+            ModelChoice::Message(text) => Message::assistant(text),
+            ModelChoice::ToolCalls { text, calls } => Message::Assistant {
+                content: text
+                    .map(|text| AssistantContent::Text { text })
+                    .into_iter()
+                    .chain(calls.into_iter().map(AssistantContent::ToolCall))
+                    .collect(),
+            },
+        }
+    }
+}
+
+impl From<&str> for Message {
+    fn from(prompt: &str) -> Self {
+        Message::user(prompt)
+    }
+}
+
+impl From<String> for Message {
+    fn from(prompt: String) -> Self {
+        Message::user(prompt)
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Document {
     pub id: String,
@@ -150,6 +363,33 @@ pub struct ToolDefinition {
     pub parameters: serde_json::Value,
 }
 
+/// Whether the model may, must or must not call the tools of a request
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolChoice {
+    /// The model decides whether to call tools
+    #[default]
+    Auto,
+    /// The model does not call any tool
+    None,
+    /// The model calls at least one tool
+    Required,
+    /// The model calls the tool with the given name
+    Specific(String),
+}
+
+/// The format the model must answer in, for providers with native structured output
+#[derive(Clone, Debug, PartialEq)]
+pub enum ResponseFormat {
+    /// A JSON object of any shape
+    Json,
+    /// A JSON value matching the schema, identified by its name
+    JsonSchema {
+        name: String,
+        schema: serde_json::Value,
+    },
+}
+
 // ================================================================
 // Implementations
 // ================================================================
@@ -159,8 +399,8 @@ pub trait Prompt: Send + Sync {
     ///
     /// If the completion model's response is a message, then it is returned as a string.
     ///
-    /// If the completion model's response is a tool call, then the tool is called and
-    /// the result is returned as a string.
+    /// If the completion model's response is a tool call, then the tool is called and its result
+    /// is sent back to the model, until the model answers with a message.
     ///
     /// If the tool does not exist, or the tool call fails, then an error is returned.
     fn prompt(
@@ -175,8 +415,8 @@ pub trait Chat: Send + Sync {
     ///
     /// If the completion model's response is a message, then it is returned as a string.
     ///
-    /// If the completion model's response is a tool call, then the tool is called and the result
-    /// is returned as a string.
+    /// If the completion model's response is a tool call, then the tool is called and its result
+    /// is sent back to the model, until the model answers with a message.
     ///
     /// If the tool does not exist, or the tool call fails, then an error is returned.
     fn chat(
@@ -212,6 +452,8 @@ pub trait Completion<M: CompletionModel> {
 pub struct CompletionResponse<T> {
     /// The completion choice returned by the completion model provider
     pub choice: ModelChoice,
+    /// The tokens used by the request and the completion
+    pub usage: Usage,
     /// The raw response returned by the completion model provider
     pub raw_response: T,
 }
@@ -221,9 +463,100 @@ pub struct CompletionResponse<T> {
 pub enum ModelChoice {
     /// Represents a completion response as a message
     Message(String),
-    /// Represents a completion response as a tool call of the form
-    /// `ToolCall(function_name, id, function_params)`.
-    ToolCall(String, String, serde_json::Value),
+    /// Represents a completion response as one or more tool calls, along with any text the
+    /// model wrote before making them. The calls are independent and can be run concurrently.
+    ToolCalls {
+        text: Option<String>,
+        calls: Vec<ToolCall>,
+    },
+}
+
+/// Token usage of a completion, in a provider-agnostic form. Counts that a provider does not
+/// report are left at 0.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    /// Tokens in the request, including the cached ones
+    pub prompt_tokens: u64,
+    /// Tokens generated by the model, including the reasoning ones
+    pub completion_tokens: u64,
+    /// Prompt tokens read from the provider's prompt cache
+    pub cached_tokens: u64,
+    /// Completion tokens the model spent reasoning before answering
+    pub reasoning_tokens: u64,
+}
+
+impl Usage {
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+
+    /// The cost of the usage in dollars, at the given prices
+    pub fn cost(&self, pricing: &Pricing) -> f64 {
+        let cached = self.cached_tokens.min(self.prompt_tokens);
+        let uncached = self.prompt_tokens - cached;
+        (uncached as f64 * pricing.prompt
+            + cached as f64 * pricing.cached_prompt.unwrap_or(pricing.prompt)
+            + self.completion_tokens as f64 * pricing.completion)
+            / 1_000_000.0
+    }
+}
+
+impl std::ops::Add for Usage {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            prompt_tokens: self.prompt_tokens + other.prompt_tokens,
+            completion_tokens: self.completion_tokens + other.completion_tokens,
+            cached_tokens: self.cached_tokens + other.cached_tokens,
+            reasoning_tokens: self.reasoning_tokens + other.reasoning_tokens,
+        }
+    }
+}
+
+impl std::ops::AddAssign for Usage {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl std::iter::Sum for Usage {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Usage::default(), std::ops::Add::add)
+    }
+}
+
+/// The prices of a model, in dollars per million tokens
+///
+/// # Example
+/// ```
+/// use eye::completion::Pricing;
+///
+/// // $2.50 per million prompt tokens, $1.25 when cached, $10 per million completion tokens
+/// let gpt_4o = Pricing::new(2.5, 10.0).cached_prompt(1.25);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Pricing {
+    pub prompt: f64,
+    /// The price of cached prompt tokens, if the provider discounts them
+    pub cached_prompt: Option<f64>,
+    pub completion: f64,
+}
+
+impl Pricing {
+    pub fn new(prompt: f64, completion: f64) -> Self {
+        Self {
+            prompt,
+            cached_prompt: None,
+            completion,
+        }
+    }
+
+    /// Set the price of cached prompt tokens
+    pub fn cached_prompt(mut self, cached_prompt: f64) -> Self {
+        self.cached_prompt = Some(cached_prompt);
+        self
+    }
 }
 
 /// Trait defining a completion model that can be used to generate completion responses.
@@ -241,15 +574,17 @@ pub trait CompletionModel: Clone + Send + Sync {
            + Send;
 
     /// Generates a completion request builder for the given `prompt`.
-    fn completion_request(&self, prompt: &str) -> CompletionRequestBuilder<Self> {
-        CompletionRequestBuilder::new(self.clone(), prompt.to_string())
+    fn completion_request(&self, prompt: impl Into<Message>) -> CompletionRequestBuilder<Self> {
+        CompletionRequestBuilder::new(self.clone(), prompt)
     }
 }
 
 /// Struct representing a general completion request that can be sent to a completion model provider.
+#[derive(Clone, Debug)]
 pub struct CompletionRequest {
-    /// The prompt to be sent to the completion model provider
-    pub prompt: String,
+    /// The prompt to be sent to the completion model provider: usually a user message, or
+    /// a tool result when an agent is continuing after a tool call
+    pub prompt: Message,
     /// The preamble to be sent to the completion model provider
     pub preamble: Option<String>,
     /// The chat history to be sent to the completion model provider
@@ -258,28 +593,108 @@ pub struct CompletionRequest {
     pub documents: Vec<Document>,
     /// The tools to be sent to the completion model provider
     pub tools: Vec<ToolDefinition>,
+    /// Whether the model may, must or must not call the tools. Providers use their own
+    /// default (usually [ToolChoice::Auto]) if not set.
+    pub tool_choice: Option<ToolChoice>,
     /// The temperature to be sent to the completion model provider
     pub temperature: Option<f64>,
     /// The max tokens to be sent to the completion model provider
     pub max_tokens: Option<u64>,
+    /// Sequences at which the model stops generating (no stop sequence if empty)
+    pub stop: Vec<String>,
+    /// The nucleus sampling probability mass to be sent to the completion model provider
+    pub top_p: Option<f64>,
+    /// The seed to be sent to the completion model provider, for reproducible sampling
+    pub seed: Option<u64>,
+    /// The frequency penalty to be sent to the completion model provider
+    pub frequency_penalty: Option<f64>,
+    /// The presence penalty to be sent to the completion model provider
+    pub presence_penalty: Option<f64>,
+    /// The format the model must answer in. Providers without native structured output
+    /// return a [CompletionError::UnsupportedParameter] if it is set.
+    pub response_format: Option<ResponseFormat>,
     /// Additional provider-specific parameters to be sent to the completion model provider
     pub additional_params: Option<serde_json::Value>,
 }
 
+/// A sampling or output parameter of a [CompletionRequest] that not every provider supports
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SamplingParam {
+    MaxTokens,
+    Stop,
+    TopP,
+    Seed,
+    FrequencyPenalty,
+    PresencePenalty,
+    ResponseFormat,
+}
+
+impl std::fmt::Display for SamplingParam {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SamplingParam::MaxTokens => "max_tokens",
+            SamplingParam::Stop => "stop",
+            SamplingParam::TopP => "top_p",
+            SamplingParam::Seed => "seed",
+            SamplingParam::FrequencyPenalty => "frequency_penalty",
+            SamplingParam::PresencePenalty => "presence_penalty",
+            SamplingParam::ResponseFormat => "response_format",
+        })
+    }
+}
+
 impl CompletionRequest {
-    pub fn prompt_with_context(&self) -> String {
-        if !self.documents.is_empty() {
-            format!(
-                "<attachments>\n{}</attachments>\n\n{}",
-                self.documents
-                    .iter()
-                    .map(|doc| doc.to_string())
-                    .collect::<Vec<_>>()
-                    .join(""),
-                self.prompt
-            )
-        } else {
-            self.prompt.clone()
+    /// Whether the sampling parameter is set on the request
+    pub fn is_set(&self, param: SamplingParam) -> bool {
+        match param {
+            SamplingParam::MaxTokens => self.max_tokens.is_some(),
+            SamplingParam::Stop => !self.stop.is_empty(),
+            SamplingParam::TopP => self.top_p.is_some(),
+            SamplingParam::Seed => self.seed.is_some(),
+            SamplingParam::FrequencyPenalty => self.frequency_penalty.is_some(),
+            SamplingParam::PresencePenalty => self.presence_penalty.is_some(),
+            SamplingParam::ResponseFormat => self.response_format.is_some(),
+        }
+    }
+
+    /// Returns a [CompletionError::UnsupportedParameter] if any of the `unsupported` sampling
+    /// parameters is set on the request. Providers call this before building their request, so
+    /// that a parameter is never silently dropped.
+    pub fn check_unsupported(
+        &self,
+        provider: &str,
+        unsupported: &[SamplingParam],
+    ) -> Result<(), CompletionError> {
+        match unsupported.iter().find(|param| self.is_set(**param)) {
+            Some(param) => Err(CompletionError::UnsupportedParameter(
+                provider.to_string(),
+                *param,
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the prompt with the request's documents attached in front of its text.
+    /// Documents are only attached to user prompts.
+    pub fn prompt_with_context(&self) -> Message {
+        match &self.prompt {
+            Message::User { content } if !self.documents.is_empty() => {
+                let attachments = format!(
+                    "<attachments>\n{}</attachments>\n\n",
+                    self.documents
+                        .iter()
+                        .map(|doc| doc.to_string())
+                        .collect::<Vec<_>>()
+                        .join("")
+                );
+                let mut content = content.clone();
+                match content.first_mut() {
+                    Some(UserContent::Text { text }) => text.insert_str(0, &attachments),
+                    _ => content.insert(0, UserContent::text(attachments)),
+                }
+                Message::User { content }
+            }
+            prompt => prompt.clone(),
         }
     }
 }
@@ -330,27 +745,41 @@ impl CompletionRequest {
 /// Instead, use the [CompletionModel::completion_request] method.
 pub struct CompletionRequestBuilder<M: CompletionModel> {
     model: M,
-    prompt: String,
+    prompt: Message,
     preamble: Option<String>,
     chat_history: Vec<Message>,
     documents: Vec<Document>,
     tools: Vec<ToolDefinition>,
+    tool_choice: Option<ToolChoice>,
     temperature: Option<f64>,
     max_tokens: Option<u64>,
+    stop: Vec<String>,
+    top_p: Option<f64>,
+    seed: Option<u64>,
+    frequency_penalty: Option<f64>,
+    presence_penalty: Option<f64>,
+    response_format: Option<ResponseFormat>,
     additional_params: Option<serde_json::Value>,
 }
 
 impl<M: CompletionModel> CompletionRequestBuilder<M> {
-    pub fn new(model: M, prompt: String) -> Self {
+    pub fn new(model: M, prompt: impl Into<Message>) -> Self {
         Self {
             model,
-            prompt,
+            prompt: prompt.into(),
             preamble: None,
             chat_history: Vec::new(),
             documents: Vec::new(),
             tools: Vec::new(),
+            tool_choice: None,
             temperature: None,
             max_tokens: None,
+            stop: Vec::new(),
+            top_p: None,
+            seed: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            response_format: None,
             additional_params: None,
         }
     }
@@ -400,6 +829,18 @@ impl<M: CompletionModel> CompletionRequestBuilder<M> {
             .fold(self, |builder, tool| builder.tool(tool))
     }
 
+    /// Sets whether the model may, must or must not call the tools of the completion request.
+    pub fn tool_choice(mut self, tool_choice: ToolChoice) -> Self {
+        self.tool_choice = Some(tool_choice);
+        self
+    }
+
+    /// Sets whether the model may, must or must not call the tools of the completion request.
+    pub fn tool_choice_opt(mut self, tool_choice: Option<ToolChoice>) -> Self {
+        self.tool_choice = tool_choice;
+        self
+    }
+
     /// Adds additional parameters to the completion request.
     /// This can be used to set additional provider-specific parameters. For example,
     /// Cohere's completion models accept a `connectors` parameter that can be used to
@@ -453,6 +894,78 @@ impl<M: CompletionModel> CompletionRequestBuilder<M> {
         self
     }
 
+    /// Adds a stop sequence to the completion request.
+    pub fn stop_sequence(mut self, stop: impl Into<String>) -> Self {
+        self.stop.push(stop.into());
+        self
+    }
+
+    /// Adds a list of stop sequences to the completion request.
+    pub fn stop_sequences(self, stop: Vec<String>) -> Self {
+        stop.into_iter()
+            .fold(self, |builder, stop| builder.stop_sequence(stop))
+    }
+
+    /// Sets the top p (nucleus sampling) for the completion request.
+    pub fn top_p(mut self, top_p: f64) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+
+    /// Sets the top p (nucleus sampling) for the completion request.
+    pub fn top_p_opt(mut self, top_p: Option<f64>) -> Self {
+        self.top_p = top_p;
+        self
+    }
+
+    /// Sets the seed for the completion request.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Sets the seed for the completion request.
+    pub fn seed_opt(mut self, seed: Option<u64>) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Sets the frequency penalty for the completion request.
+    pub fn frequency_penalty(mut self, frequency_penalty: f64) -> Self {
+        self.frequency_penalty = Some(frequency_penalty);
+        self
+    }
+
+    /// Sets the frequency penalty for the completion request.
+    pub fn frequency_penalty_opt(mut self, frequency_penalty: Option<f64>) -> Self {
+        self.frequency_penalty = frequency_penalty;
+        self
+    }
+
+    /// Sets the presence penalty for the completion request.
+    pub fn presence_penalty(mut self, presence_penalty: f64) -> Self {
+        self.presence_penalty = Some(presence_penalty);
+        self
+    }
+
+    /// Sets the presence penalty for the completion request.
+    pub fn presence_penalty_opt(mut self, presence_penalty: Option<f64>) -> Self {
+        self.presence_penalty = presence_penalty;
+        self
+    }
+
+    /// Sets the format the model must answer in, for providers with native structured output.
+    pub fn response_format(mut self, response_format: ResponseFormat) -> Self {
+        self.response_format = Some(response_format);
+        self
+    }
+
+    /// Sets the format the model must answer in, for providers with native structured output.
+    pub fn response_format_opt(mut self, response_format: Option<ResponseFormat>) -> Self {
+        self.response_format = response_format;
+        self
+    }
+
     /// Builds the completion request.
     pub fn build(self) -> CompletionRequest {
         CompletionRequest {
@@ -461,8 +974,15 @@ impl<M: CompletionModel> CompletionRequestBuilder<M> {
             chat_history: self.chat_history,
             documents: self.documents,
             tools: self.tools,
+            tool_choice: self.tool_choice,
             temperature: self.temperature,
             max_tokens: self.max_tokens,
+            stop: self.stop,
+            top_p: self.top_p,
+            seed: self.seed,
+            frequency_penalty: self.frequency_penalty,
+            presence_penalty: self.presence_penalty,
+            response_format: self.response_format,
             additional_params: self.additional_params,
         }
     }
@@ -483,9 +1003,126 @@ impl<M: StreamingCompletionModel> CompletionRequestBuilder<M> {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
+    use std::{
+        collections::VecDeque,
+        sync::{Arc, Mutex},
+    };
+
     use super::*;
 
+    /// A completion model that replays scripted choices and records the requests it receives
+    #[derive(Clone, Default)]
+    pub(crate) struct ScriptedModel {
+        without_response_format: bool,
+        script: Arc<Mutex<VecDeque<ModelChoice>>>,
+        requests: Arc<Mutex<Vec<CompletionRequest>>>,
+    }
+
+    impl ScriptedModel {
+        pub(crate) fn new(script: impl IntoIterator<Item = ModelChoice>) -> Self {
+            Self {
+                script: Arc::new(Mutex::new(script.into_iter().collect())),
+                ..Default::default()
+            }
+        }
+
+        /// Rejects requests with a response format, like a model without native structured output
+        pub(crate) fn without_response_format(mut self) -> Self {
+            self.without_response_format = true;
+            self
+        }
+
+        pub(crate) fn requests(&self) -> Vec<CompletionRequest> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    impl CompletionModel for ScriptedModel {
+        type Response = ();
+
+        async fn completion(
+            &self,
+            request: CompletionRequest,
+        ) -> Result<CompletionResponse<()>, CompletionError> {
+            if self.without_response_format {
+                request.check_unsupported("Scripted", &[SamplingParam::ResponseFormat])?;
+            }
+            self.requests.lock().unwrap().push(request);
+            let choice = self
+                .script
+                .lock()
+                .unwrap()
+                .pop_front()
+                .ok_or_else(|| CompletionError::ResponseError("Script exhausted".into()))?;
+            Ok(CompletionResponse {
+                choice,
+                usage: Usage {
+                    prompt_tokens: 100,
+                    completion_tokens: 10,
+                    cached_tokens: 50,
+                    reasoning_tokens: 0,
+                },
+                raw_response: (),
+            })
+        }
+    }
+
+    /// A chat with an image, and a turn of two tool calls answered in one tool message
+    pub(crate) fn tool_chat() -> Vec<Message> {
+        vec![
+            Message::system("Be brief."),
+            Message::User {
+                content: vec![
+                    UserContent::text("What is on these?"),
+                    UserContent::image_url("https://example.com/a.png"),
+                    UserContent::image_base64("image/png", "iVBORw0KGgo="),
+                ],
+            },
+            Message::Assistant {
+                content: vec![
+                    AssistantContent::Text {
+                        text: "Looking them up".into(),
+                    },
+                    AssistantContent::ToolCall(ToolCall {
+                        id: "call_1".into(),
+                        name: "lookup".into(),
+                        arguments: serde_json::json!({ "image": 1 }),
+                    }),
+                    AssistantContent::ToolCall(ToolCall {
+                        id: "call_2".into(),
+                        name: "lookup".into(),
+                        arguments: serde_json::json!({ "image": 2 }),
+                    }),
+                ],
+            },
+            Message::Tool {
+                content: vec![
+                    ToolResult {
+                        id: "call_1".into(),
+                        content: "A cat".into(),
+                    },
+                    ToolResult {
+                        id: "call_2".into(),
+                        content: "{\"animal\":\"dog\"}".into(),
+                    },
+                ],
+            },
+        ]
+    }
+
+    /// The definition of the tool called in [tool_chat]
+    pub(crate) fn lookup_tool() -> ToolDefinition {
+        ToolDefinition {
+            name: "lookup".into(),
+            description: "Describe an image".into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": { "image": { "type": "integer" } },
+            }),
+        }
+    }
+
     #[test]
     fn test_document_display_without_metadata() {
         let doc = Document {
@@ -534,13 +1171,20 @@ mod tests {
         };
 
         let request = CompletionRequest {
-            prompt: "What is the capital of France?".to_string(),
+            prompt: Message::user("What is the capital of France?"),
             preamble: None,
             chat_history: Vec::new(),
             documents: vec![doc1, doc2],
             tools: Vec::new(),
+            tool_choice: None,
             temperature: None,
             max_tokens: None,
+            stop: Vec::new(),
+            top_p: None,
+            seed: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            response_format: None,
             additional_params: None,
         };
 
@@ -553,6 +1197,124 @@ mod tests {
         )
         .to_string();
 
-        assert_eq!(request.prompt_with_context(), expected);
+        assert_eq!(request.prompt_with_context(), Message::user(expected));
+    }
+
+    #[test]
+    fn test_prompt_with_context_with_image() {
+        let request = CompletionRequest {
+            prompt: Message::User {
+                content: vec![
+                    UserContent::image_url("https://example.com/chart.png"),
+                    UserContent::text("What does this chart show?"),
+                ],
+            },
+            preamble: None,
+            chat_history: Vec::new(),
+            documents: vec![Document {
+                id: "doc1".to_string(),
+                text: "Document 1 text.".to_string(),
+                additional_props: HashMap::new(),
+            }],
+            tools: Vec::new(),
+            tool_choice: None,
+            temperature: None,
+            max_tokens: None,
+            stop: Vec::new(),
+            top_p: None,
+            seed: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            response_format: None,
+            additional_params: None,
+        };
+
+        assert_eq!(
+            request.prompt_with_context(),
+            Message::User {
+                content: vec![
+                    UserContent::text(
+                        "<attachments>\n<file id: doc1>\nDocument 1 text.\n</file>\n</attachments>\n\n"
+                    ),
+                    UserContent::image_url("https://example.com/chart.png"),
+                    UserContent::text("What does this chart show?"),
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_check_unsupported() {
+        let request = CompletionRequest {
+            prompt: Message::user("Hello"),
+            preamble: None,
+            chat_history: Vec::new(),
+            documents: Vec::new(),
+            tools: Vec::new(),
+            tool_choice: None,
+            temperature: Some(0.5),
+            max_tokens: None,
+            stop: vec!["\n\n".to_string()],
+            top_p: None,
+            seed: Some(42),
+            frequency_penalty: None,
+            presence_penalty: None,
+            response_format: None,
+            additional_params: None,
+        };
+
+        assert!(request
+            .check_unsupported("Provider", &[SamplingParam::TopP])
+            .is_ok());
+
+        let error = request
+            .check_unsupported(
+                "Provider",
+                &[SamplingParam::FrequencyPenalty, SamplingParam::Seed],
+            )
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            CompletionError::UnsupportedParameter(_, SamplingParam::Seed)
+        ));
+        assert_eq!(
+            error.to_string(),
+            "UnsupportedParameterError: Provider does not support the `seed` parameter"
+        );
+    }
+
+    #[test]
+    fn test_message_serde() {
+        let messages = vec![
+            Message::system("Be brief."),
+            Message::User {
+                content: vec![
+                    UserContent::text("Hi"),
+                    UserContent::image_base64("image/png", "iVBORw0KGgo="),
+                ],
+            },
+            Message::tool_call("call_1", "add", serde_json::json!({"x": 1, "y": 2})),
+            Message::tool_result("call_1", "3"),
+        ];
+
+        let json = serde_json::to_value(&messages).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {"role": "system", "content": "Be brief."},
+                {"role": "user", "content": [
+                    {"type": "text", "text": "Hi"},
+                    {"type": "image", "source": "base64", "media_type": "image/png", "data": "iVBORw0KGgo="}
+                ]},
+                {"role": "assistant", "content": [
+                    {"type": "tool_call", "id": "call_1", "name": "add", "arguments": {"x": 1, "y": 2}}
+                ]},
+                {"role": "tool", "content": [{"id": "call_1", "content": "3"}]}
+            ])
+        );
+        assert_eq!(
+            serde_json::from_value::<Vec<Message>>(json).unwrap(),
+            messages
+        );
     }
 }
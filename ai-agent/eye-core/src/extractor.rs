@@ -3,6 +3,12 @@
 //! Note: The target structure must implement the `serde::Deserialize`, `serde::Serialize`,
 //! and `schemars::JsonSchema` traits. Those can be easily derived using the `derive` macro.
 //!
+//! The extractor asks for the data in the provider's native structured output format when there
+//! is one (OpenAI's JSON schema response format, Gemini's response schema, DeepSeek's JSON mode),
+//! and otherwise makes the model call a `submit` tool whose parameters are the target structure.
+//! The extracted data is validated against the structure's schema, and the model is asked again,
+//! with the validation error, until the data is valid or the extractor runs out of attempts.
+//!
 //! # Example
 //! ```
 //! use eye::providers::openai;
@@ -20,6 +26,7 @@
 //!
 //! // Create the extractor
 //! let extractor = openai.extractor::<Person>(openai::GPT_4O)
+//!     .max_attempts(2)
 //!     .build();
 //!
 //! // Extract structured data from text
@@ -30,16 +37,24 @@
 
 use std::marker::PhantomData;
 
-use schemars::{schema_for, JsonSchema};
+use schemars::{
+    schema::{InstanceType, RootSchema, Schema, SingleOrVec},
+    schema_for, JsonSchema,
+};
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use serde_json::{json, Value};
 
 use crate::{
     agent::{Agent, AgentBuilder},
-    completion::{CompletionModel, Prompt, PromptError, ToolDefinition},
-    tool::Tool,
+    completion::{
+        Completion, CompletionError, CompletionModel, Message, ModelChoice, PromptError,
+        ResponseFormat, SamplingParam, ToolChoice, ToolDefinition, ToolResult,
+    },
 };
 
+/// Default number of times an [Extractor] asks the model for the data before giving up
+pub const DEFAULT_MAX_ATTEMPTS: usize = 3;
+
 #[derive(Debug, thiserror::Error)]
 pub enum ExtractionError {
     #[error("No data extracted")]
@@ -48,13 +63,21 @@ pub enum ExtractionError {
     #[error("Failed to deserialize the extracted data: {0}")]
     DeserializationError(#[from] serde_json::Error),
 
+    #[error("The extracted data does not match the schema: {0}")]
+    ValidationError(String),
+
     #[error("PromptError: {0}")]
     PromptError(#[from] PromptError),
+
+    #[error("CompletionError: {0}")]
+    CompletionError(#[from] CompletionError),
 }
 
 /// Extractor for structured data from text
 pub struct Extractor<M: CompletionModel, T: JsonSchema + for<'a> Deserialize<'a> + Send + Sync> {
     agent: Agent<M>,
+    model: M,
+    max_attempts: usize,
     _t: PhantomData<T>,
 }
 
@@ -62,14 +85,59 @@ impl<T: JsonSchema + for<'a> Deserialize<'a> + Send + Sync, M: CompletionModel>
 where
     M: Sync,
 {
+    /// Extract structured data from the text.
+    ///
+    /// The data is requested in the provider's native structured output format, or, if the
+    /// provider has none, as the arguments of a forced call to the `submit` tool (which is never
+    /// run). Data that does not match the schema is sent back to the model with the error, up to
+    /// the extractor's maximum number of attempts.
     pub async fn extract(&self, text: &str) -> Result<T, ExtractionError> {
-        let summary = self.agent.prompt(text).await?;
+        let schema = schema_for!(T);
+        let mut request = self.agent.completion(text, vec![]).await?.build();
+        request.response_format = Some(ResponseFormat::JsonSchema {
+            name: schema_name::<T>(),
+            schema: json!(schema),
+        });
 
-        if summary.is_empty() {
-            return Err(ExtractionError::NoData);
-        }
+        let mut attempt = 1;
+        loop {
+            let choice = match self.model.completion(request.clone()).await {
+                Err(CompletionError::UnsupportedParameter(_, SamplingParam::ResponseFormat)) => {
+                    request.response_format = None;
+                    request.tools = vec![submit_tool(&schema)];
+                    request.tool_choice = Some(ToolChoice::Specific(SUBMIT.to_string()));
+                    continue;
+                }
+                response => response?.choice,
+            };
+
+            let error = match parse(&choice, &schema) {
+                Ok(data) => return Ok(data),
+                Err(error) if attempt >= self.max_attempts => return Err(error),
+                Err(error) => error,
+            };
+            tracing::debug!("Extraction attempt {attempt} failed: {error}");
 
-        Ok(serde_json::from_str(&summary)?)
+            // The model is asked again, with its answer and the error appended to the
+            // conversation. The documents are now part of the user prompt in the history.
+            let feedback = format!("{error}. Answer again with data that matches the schema.");
+            request.chat_history.push(request.prompt_with_context());
+            request.documents.clear();
+            request.prompt = match &choice {
+                ModelChoice::ToolCalls { calls, .. } => Message::Tool {
+                    content: calls
+                        .iter()
+                        .map(|call| ToolResult {
+                            id: call.id.clone(),
+                            content: feedback.clone(),
+                        })
+                        .collect(),
+                },
+                ModelChoice::Message(_) => Message::user(feedback),
+            };
+            request.chat_history.push(Message::from(choice));
+            attempt += 1;
+        }
     }
 }
 
@@ -79,6 +147,8 @@ pub struct ExtractorBuilder<
     M: CompletionModel,
 > {
     agent_builder: AgentBuilder<M>,
+    model: M,
+    max_attempts: usize,
     _t: PhantomData<T>,
 }
 
@@ -87,14 +157,14 @@ impl<T: JsonSchema + for<'a> Deserialize<'a> + Serialize + Send + Sync, M: Compl
 {
     pub fn new(model: M) -> Self {
         Self {
-            agent_builder: AgentBuilder::new(model)
+            agent_builder: AgentBuilder::new(model.clone())
                 .preamble("\
                     You are an AI assistant whose purpose is to extract structured data from the provided text.\n\
-                    You will have access to a `submit` function that defines the structure of the data to extract from the provided text.\n\
-                    Use the `submit` function to submit the structured data.\n\
-                    Be sure to fill out every field and ALWAYS CALL THE `submit` function, event with default values!!!.
-                ")
-                .tool(SubmitTool::<T> {_t: PhantomData}),
+                    Answer with the extracted data only, in the structure you are given: a JSON schema, or the parameters of the `submit` function.\n\
+                    Fill out every field, using default values for the data the text does not provide.
+                "),
+            model,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
             _t: PhantomData,
         }
     }
@@ -113,40 +183,341 @@ impl<T: JsonSchema + for<'a> Deserialize<'a> + Serialize + Send + Sync, M: Compl
         self
     }
 
+    /// Set the number of times the model is asked for the data before the extraction fails
+    /// (default: [DEFAULT_MAX_ATTEMPTS]). Each new attempt tells the model what was wrong with
+    /// its previous answer.
+    pub fn max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
     /// Build the Extractor
     pub fn build(self) -> Extractor<M, T> {
         Extractor {
             agent: self.agent_builder.build(),
+            model: self.model,
+            max_attempts: self.max_attempts,
             _t: PhantomData,
         }
     }
 }
 
-#[derive(Deserialize, Serialize)]
-struct SubmitTool<T: JsonSchema + for<'a> Deserialize<'a> + Send + Sync> {
-    _t: PhantomData<T>,
+/// Name of the tool the model submits the extracted data with, when the provider has no
+/// native structured output
+const SUBMIT: &str = "submit";
+
+fn submit_tool(schema: &RootSchema) -> ToolDefinition {
+    ToolDefinition {
+        name: SUBMIT.to_string(),
+        description: "Submit the structured data you extracted from the provided text.".to_string(),
+        parameters: json!(schema),
+    }
 }
 
-#[derive(Debug, thiserror::Error)]
-#[error("SubmitError")]
-struct SubmitError;
-
-impl<T: JsonSchema + for<'a> Deserialize<'a> + Serialize + Send + Sync> Tool for SubmitTool<T> {
-    const NAME: &'static str = "submit";
-    type Error = SubmitError;
-    type Args = T;
-    type Output = T;
-
-    async fn definition(&self, _prompt: String) -> ToolDefinition {
-        ToolDefinition {
-            name: Self::NAME.to_string(),
-            description: "Submit the structured data you extracted from the provided text."
-                .to_string(),
-            parameters: json!(schema_for!(T)),
+/// The name of the schema of `T`, restricted to the characters OpenAI accepts
+fn schema_name<T: JsonSchema>() -> String {
+    T::schema_name()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+        .take(64)
+        .collect()
+}
+
+/// Reads the data from the model's answer: the arguments of its `submit` call, or its message
+/// parsed as JSON. The data must match the schema of `T`.
+fn parse<T: for<'a> Deserialize<'a>>(
+    choice: &ModelChoice,
+    schema: &RootSchema,
+) -> Result<T, ExtractionError> {
+    let data = match choice {
+        ModelChoice::ToolCalls { calls, .. } => calls
+            .iter()
+            .find(|call| call.name == SUBMIT)
+            .map(|call| call.arguments.clone())
+            .ok_or(ExtractionError::NoData)?,
+        ModelChoice::Message(text) => {
+            // Models often wrap JSON in a Markdown code block
+            let text = text.trim();
+            let text = text
+                .strip_prefix("```json")
+                .or_else(|| text.strip_prefix("```"))
+                .and_then(|text| text.strip_suffix("```"))
+                .unwrap_or(text);
+            if text.trim().is_empty() {
+                return Err(ExtractionError::NoData);
+            }
+            serde_json::from_str(text)?
+        }
+    };
+
+    validate(
+        &Schema::Object(schema.schema.clone()),
+        schema,
+        &data,
+        "data",
+    )
+    .map_err(ExtractionError::ValidationError)?;
+    Ok(serde_json::from_value(data)?)
+}
+
+/// Checks a value against the keywords `schemars` generates (references, types, enumerations,
+/// `anyOf`/`oneOf`/`allOf`, required and nested properties, array items), and describes the
+/// first mismatch with the path of the offending value.
+fn validate(schema: &Schema, root: &RootSchema, value: &Value, path: &str) -> Result<(), String> {
+    let schema = match schema {
+        Schema::Bool(true) => return Ok(()),
+        Schema::Bool(false) => return Err(format!("{path} is not allowed")),
+        Schema::Object(schema) => schema,
+    };
+
+    if let Some(reference) = &schema.reference {
+        let name = reference.trim_start_matches("#/definitions/");
+        return match root.definitions.get(name) {
+            Some(definition) => validate(definition, root, value, path),
+            None => Ok(()),
+        };
+    }
+
+    if let Some(instance_type) = &schema.instance_type {
+        let types = match instance_type {
+            SingleOrVec::Single(instance_type) => vec![**instance_type],
+            SingleOrVec::Vec(types) => types.clone(),
+        };
+        if !types
+            .iter()
+            .any(|instance_type| has_type(value, *instance_type))
+        {
+            let expected = types
+                .iter()
+                .map(|instance_type| {
+                    json!(instance_type)
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string()
+                })
+                .collect::<Vec<_>>()
+                .join(" or ");
+            return Err(format!(
+                "{path} should be of type {expected}, but is {}",
+                type_name(value)
+            ));
+        }
+    }
+
+    if let Some(values) = &schema.enum_values {
+        if !values.contains(value) {
+            let expected = values
+                .iter()
+                .map(Value::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Err(format!(
+                "{path} should be one of {expected}, but is {value}"
+            ));
+        }
+    }
+
+    if let Some(subschemas) = &schema.subschemas {
+        for schema in subschemas.all_of.iter().flatten() {
+            validate(schema, root, value, path)?;
+        }
+        for alternatives in [&subschemas.any_of, &subschemas.one_of]
+            .into_iter()
+            .flatten()
+        {
+            let results = alternatives
+                .iter()
+                .map(|schema| validate(schema, root, value, path))
+                .collect::<Vec<_>>();
+            // The first alternative's error is the most telling for `Option<T>`, which
+            // `schemars` describes as `T` or `null`
+            if !results.iter().any(Result::is_ok) {
+                if let Some(error) = results.into_iter().next() {
+                    return error;
+                }
+            }
         }
     }
 
-    async fn call(&self, data: Self::Args) -> Result<Self::Output, Self::Error> {
-        Ok(data)
+    if let (Some(object), Value::Object(fields)) = (&schema.object, value) {
+        if let Some(field) = object
+            .required
+            .iter()
+            .find(|field| !fields.contains_key(*field))
+        {
+            return Err(format!("{path}.{field} is missing"));
+        }
+        for (field, schema) in &object.properties {
+            if let Some(value) = fields.get(field) {
+                validate(schema, root, value, &format!("{path}.{field}"))?;
+            }
+        }
+    }
+
+    if let (Some(array), Value::Array(items)) = (&schema.array, value) {
+        if let Some(SingleOrVec::Single(schema)) = &array.items {
+            for (i, item) in items.iter().enumerate() {
+                validate(schema, root, item, &format!("{path}[{i}]"))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn has_type(value: &Value, instance_type: InstanceType) -> bool {
+    match instance_type {
+        InstanceType::Null => value.is_null(),
+        InstanceType::Boolean => value.is_boolean(),
+        InstanceType::Object => value.is_object(),
+        InstanceType::Array => value.is_array(),
+        InstanceType::Number => value.is_number(),
+        InstanceType::String => value.is_string(),
+        InstanceType::Integer => value.is_i64() || value.is_u64(),
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::completion::{tests::ScriptedModel, ToolCall};
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+    struct Person {
+        name: String,
+        age: Option<u8>,
+        address: Option<Address>,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+    struct Address {
+        city: String,
+    }
+
+    fn john() -> Person {
+        Person {
+            name: "John".into(),
+            age: Some(30),
+            address: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_extract_with_native_structured_output() {
+        let model = ScriptedModel::new([ModelChoice::Message(
+            r#"{ "name": "John", "age": 30 }"#.into(),
+        )]);
+        let extractor = ExtractorBuilder::<Person, _>::new(model.clone()).build();
+
+        let person = extractor.extract("John is 30.").await.unwrap();
+
+        assert_eq!(person, john());
+        let requests = model.requests();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].tools.is_empty());
+        assert!(matches!(
+            &requests[0].response_format,
+            Some(ResponseFormat::JsonSchema { name, schema })
+                if name == "Person" && schema["required"] == json!(["name"])
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_extract_falls_back_to_submit_tool() {
+        let model = ScriptedModel::new([ModelChoice::ToolCalls {
+            text: None,
+            calls: vec![ToolCall {
+                id: "call_1".into(),
+                name: SUBMIT.into(),
+                arguments: json!({ "name": "John", "age": 30 }),
+            }],
+        }])
+        .without_response_format();
+        let extractor = ExtractorBuilder::<Person, _>::new(model.clone()).build();
+
+        let person = extractor.extract("John is 30.").await.unwrap();
+
+        assert_eq!(person, john());
+        let requests = model.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].response_format, None);
+        assert_eq!(requests[0].tools[0].name, SUBMIT);
+        assert_eq!(
+            requests[0].tool_choice,
+            Some(ToolChoice::Specific(SUBMIT.into()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_extract_retries_with_validation_error() {
+        let model = ScriptedModel::new([
+            ModelChoice::Message(r#"{ "name": "John", "age": "thirty" }"#.into()),
+            ModelChoice::Message("```json\n{ \"name\": \"John\", \"age\": 30 }\n```".into()),
+        ]);
+        let extractor = ExtractorBuilder::<Person, _>::new(model.clone()).build();
+
+        let person = extractor.extract("John is thirty.").await.unwrap();
+
+        assert_eq!(person, john());
+        let requests = model.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(
+            requests[1].chat_history,
+            [
+                Message::user("John is thirty."),
+                Message::assistant(r#"{ "name": "John", "age": "thirty" }"#),
+            ]
+        );
+        assert_eq!(
+            requests[1].prompt,
+            Message::user(
+                "The extracted data does not match the schema: data.age should be of type \
+                 integer or null, but is a string. Answer again with data that matches the schema."
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_extract_gives_up_after_max_attempts() {
+        let model = ScriptedModel::new([ModelChoice::Message(
+            r#"{ "name": "John", "address": { "city": 75 } }"#.into(),
+        )]);
+        let extractor = ExtractorBuilder::<Person, _>::new(model.clone())
+            .max_attempts(1)
+            .build();
+
+        let error = extractor.extract("John lives in Paris.").await.unwrap_err();
+
+        assert!(matches!(
+            error,
+            ExtractionError::ValidationError(message)
+                if message == "data.address.city should be of type string, but is a number"
+        ));
+        assert_eq!(model.requests().len(), 1);
+    }
+
+    #[test]
+    fn test_validate_missing_field() {
+        let schema = schema_for!(Person);
+        let error = validate(
+            &Schema::Object(schema.schema.clone()),
+            &schema,
+            &json!({ "age": 30 }),
+            "data",
+        )
+        .unwrap_err();
+
+        assert_eq!(error, "data.name is missing");
     }
 }
@@ -17,3 +17,4 @@ pub fn merge_inplace(a: &mut serde_json::Value, b: serde_json::Value) {
         });
     }
 }
+
@@ -3,7 +3,7 @@
 use std::iter;
 
 use crate::{
-    completion::{self, CompletionError},
+    completion::{self, CompletionError, SamplingParam},
     json_utils,
 };
 
@@ -59,10 +59,28 @@ pub enum Content {
         name: String,
         input: serde_json::Value,
     },
+    ToolResult {
+        r#type: String,
+        tool_use_id: String,
+        content: String,
+    },
+    Image {
+        r#type: String,
+        source: ImageSource,
+    },
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ImageSource {
+    Base64 { media_type: String, data: String },
+    Url { url: String },
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Usage {
+    /// Not sent with the output tokens at the end of a stream
+    #[serde(default)]
     pub input_tokens: u64,
     pub cache_read_input_tokens: Option<u64>,
     pub cache_creation_input_tokens: Option<u64>,
@@ -88,6 +106,21 @@ impl std::fmt::Display for Usage {
     }
 }
 
+impl From<&Usage> for completion::Usage {
+    fn from(usage: &Usage) -> Self {
+        let cache_read = usage.cache_read_input_tokens.unwrap_or_default();
+        completion::Usage {
+            // Anthropic does not count cached tokens as input tokens
+            prompt_tokens: usage.input_tokens
+                + cache_read
+                + usage.cache_creation_input_tokens.unwrap_or_default(),
+            completion_tokens: usage.output_tokens,
+            cached_tokens: cache_read,
+            reasoning_tokens: 0,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ToolDefinition {
     pub name: String,
@@ -101,52 +134,156 @@ pub enum CacheControl {
     Ephemeral,
 }
 
+/// The sampling parameters set on the request, as Anthropic Messages API fields, except for
+/// `max_tokens` which Anthropic requires
+fn sampling_params(
+    request: &completion::CompletionRequest,
+) -> Result<serde_json::Value, CompletionError> {
+    request.check_unsupported(
+        "Anthropic",
+        &[
+            SamplingParam::Seed,
+            SamplingParam::FrequencyPenalty,
+            SamplingParam::PresencePenalty,
+            SamplingParam::ResponseFormat,
+        ],
+    )?;
+
+    let mut params = json!({});
+    if let Some(temperature) = request.temperature {
+        json_utils::merge_inplace(&mut params, json!({ "temperature": temperature }));
+    }
+    if let Some(top_p) = request.top_p {
+        json_utils::merge_inplace(&mut params, json!({ "top_p": top_p }));
+    }
+    if !request.stop.is_empty() {
+        json_utils::merge_inplace(&mut params, json!({ "stop_sequences": request.stop }));
+    }
+    Ok(params)
+}
+
 impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionResponse> {
     type Error = CompletionError;
 
     fn try_from(response: CompletionResponse) -> Result<Self, Self::Error> {
-        if let Some(tool_use) = response.content.iter().find_map(|content| match content {
-            Content::ToolUse {
-                name, input, id, ..
-            } => Some((name.clone(), id.clone(), input.clone())),
-            _ => None,
-        }) {
-            return Ok(completion::CompletionResponse {
-                choice: completion::ModelChoice::ToolCall(tool_use.0, tool_use.1, tool_use.2),
-                raw_response: response,
-            });
-        }
-
-        if let Some(text_content) = response.content.iter().find_map(|content| match content {
-            Content::String(text) => Some(text.clone()),
-            Content::Text { text, .. } => Some(text.clone()),
-            _ => None,
-        }) {
-            return Ok(completion::CompletionResponse {
-                choice: completion::ModelChoice::Message(text_content),
-                raw_response: response,
-            });
-        }
+        let text = response
+            .content
+            .iter()
+            .filter_map(|content| match content {
+                Content::String(text) | Content::Text { text, .. } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let text = (!text.is_empty()).then(|| text.join("\n"));
+
+        let calls = response
+            .content
+            .iter()
+            .filter_map(|content| match content {
+                Content::ToolUse {
+                    name, input, id, ..
+                } => Some(completion::ToolCall {
+                    id: id.clone(),
+                    name: name.clone(),
+                    arguments: input.clone(),
+                }),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let choice = match (text, calls.is_empty()) {
+            (text, false) => completion::ModelChoice::ToolCalls { text, calls },
+            (Some(text), true) => completion::ModelChoice::Message(text),
+            (None, true) => {
+                return Err(CompletionError::ResponseError(
+                    "Response did not contain a message or tool call".into(),
+                ))
+            }
+        };
 
-        Err(CompletionError::ResponseError(
-            "Response did not contain a message or tool call".into(),
-        ))
+        Ok(completion::CompletionResponse {
+            choice,
+            usage: completion::Usage::from(&response.usage),
+            raw_response: response,
+        })
     }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Message {
     pub role: String,
-    pub content: String,
+    pub content: Vec<Content>,
 }
 
-impl From<completion::Message> for Message {
-    fn from(message: completion::Message) -> Self {
-        Self {
-            role: message.role,
-            content: message.content,
-        }
-    }
+/// Converts the preamble and chat messages to Anthropic's `system` prompt and messages.
+/// Anthropic takes system instructions apart from the messages, so system messages are
+/// appended to the preamble, and tool results are sent back in a user message.
+fn chat(
+    preamble: Option<String>,
+    messages: impl IntoIterator<Item = completion::Message>,
+) -> (String, Vec<Message>) {
+    let mut system = preamble.into_iter().collect::<Vec<_>>();
+    let messages = messages
+        .into_iter()
+        .filter_map(|message| match message {
+            completion::Message::System { content } => {
+                system.push(content);
+                None
+            }
+            completion::Message::User { content } => Some(Message {
+                role: "user".to_owned(),
+                content: content
+                    .into_iter()
+                    .map(|part| match part {
+                        completion::UserContent::Text { text } => Content::Text {
+                            r#type: "text".to_owned(),
+                            text,
+                        },
+                        completion::UserContent::Image(image) => Content::Image {
+                            r#type: "image".to_owned(),
+                            source: match image {
+                                completion::Image::Base64 { media_type, data } => {
+                                    ImageSource::Base64 { media_type, data }
+                                }
+                                completion::Image::Url { url } => ImageSource::Url { url },
+                            },
+                        },
+                    })
+                    .collect(),
+            }),
+            completion::Message::Assistant { content } => Some(Message {
+                role: "assistant".to_owned(),
+                content: content
+                    .into_iter()
+                    .map(|part| match part {
+                        completion::AssistantContent::Text { text } => Content::Text {
+                            r#type: "text".to_owned(),
+                            text,
+                        },
+                        completion::AssistantContent::ToolCall(call) => Content::ToolUse {
+                            r#type: "tool_use".to_owned(),
+                            id: call.id,
+                            name: call.name,
+                            input: call.arguments,
+                        },
+                    })
+                    .collect(),
+            }),
+            completion::Message::Tool { content } => Some(Message {
+                role: "user".to_owned(),
+                content: content
+                    .into_iter()
+                    .map(|result| Content::ToolResult {
+                        r#type: "tool_result".to_owned(),
+                        tool_use_id: result.id,
+                        content: result.content,
+                    })
+                    .collect(),
+            }),
+        })
+        .collect();
+
+    (system.join("\n\n"), messages)
 }
 
 #[derive(Clone)]
@@ -164,52 +301,18 @@ impl CompletionModel {
             default_max_tokens: calculate_max_tokens(model),
         }
     }
-}
 
-/// Anthropic requires a `max_tokens` parameter to be set, which is dependent on the model. If not
-/// set or if set too high, the request will fail. The following values are based on the models
-/// available at the time of writing.
-///
-/// Dev Note: This is really bad design, I'm not sure why they did it like this..
-fn calculate_max_tokens(model: &str) -> Option<u64> {
-    if model.starts_with("claude-3-5-sonnet") || model.starts_with("claude-3-5-haiku") {
-        Some(8192)
-    } else if model.starts_with("claude-3-opus")
-        || model.starts_with("claude-3-sonnet")
-        || model.starts_with("claude-3-haiku")
-    {
-        Some(4096)
-    } else {
-        None
-    }
-}
-
-#[derive(Debug, Deserialize, Serialize)]
-struct Metadata {
-    user_id: Option<String>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(tag = "type", rename_all = "snake_case")]
-pub enum ToolChoice {
-    Auto,
-    Any,
-    Tool { name: String },
-}
-
-impl completion::CompletionModel for CompletionModel {
-    type Response = CompletionResponse;
-
-    #[cfg_attr(feature = "worker", worker::send)]
-    async fn completion(
+    /// Builds the Messages API request body, which streaming requests extend
+    pub(super) fn create_request(
         &self,
         completion_request: completion::CompletionRequest,
-    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+    ) -> Result<serde_json::Value, CompletionError> {
         // Note: Ideally we'd introduce provider-specific Request models to handle the
         // specific requirements of each provider. For now, we just manually check while
         // building the request as a raw JSON document.
 
         let prompt_with_context = completion_request.prompt_with_context();
+        let sampling_params = sampling_params(&completion_request)?;
 
         // Check if max_tokens is set, required for Anthropic
         let max_tokens = if let Some(tokens) = completion_request.max_tokens {
@@ -222,24 +325,22 @@ impl completion::CompletionModel for CompletionModel {
             ));
         };
 
-        let mut request = json!({
-            "model": self.model,
-            "messages": completion_request
+        let (system, messages) = chat(
+            completion_request.preamble,
+            completion_request
                 .chat_history
                 .into_iter()
-                .map(Message::from)
-                .chain(iter::once(Message {
-                    role: "user".to_owned(),
-                    content: prompt_with_context,
-                }))
-                .collect::<Vec<_>>(),
+                .chain(iter::once(prompt_with_context)),
+        );
+
+        let mut request = json!({
+            "model": self.model,
+            "messages": messages,
             "max_tokens": max_tokens,
-            "system": completion_request.preamble.unwrap_or("".to_string()),
+            "system": system,
         });
 
-        if let Some(temperature) = completion_request.temperature {
-            json_utils::merge_inplace(&mut request, json!({ "temperature": temperature }));
-        }
+        json_utils::merge_inplace(&mut request, sampling_params);
 
         if !completion_request.tools.is_empty() {
             json_utils::merge_inplace(
@@ -254,7 +355,7 @@ impl completion::CompletionModel for CompletionModel {
                             input_schema: tool.parameters,
                         })
                         .collect::<Vec<_>>(),
-                    "tool_choice": ToolChoice::Auto,
+                    "tool_choice": ToolChoice::from(completion_request.tool_choice.unwrap_or_default()),
                 }),
             );
         }
@@ -263,6 +364,63 @@ impl completion::CompletionModel for CompletionModel {
             json_utils::merge_inplace(&mut request, params.clone())
         }
 
+        Ok(request)
+    }
+}
+
+/// Anthropic requires a `max_tokens` parameter to be set, which is dependent on the model. If not
+/// set or if set too high, the request will fail. The following values are based on the models
+/// available at the time of writing.
+///
+/// Dev Note: This is really bad design, I'm not sure why they did it like this..
+fn calculate_max_tokens(model: &str) -> Option<u64> {
+    if model.starts_with("claude-3-5-sonnet") || model.starts_with("claude-3-5-haiku") {
+        Some(8192)
+    } else if model.starts_with("claude-3-opus")
+        || model.starts_with("claude-3-sonnet")
+        || model.starts_with("claude-3-haiku")
+    {
+        Some(4096)
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Metadata {
+    user_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ToolChoice {
+    Auto,
+    Any,
+    Tool { name: String },
+    None,
+}
+
+impl From<completion::ToolChoice> for ToolChoice {
+    fn from(tool_choice: completion::ToolChoice) -> Self {
+        match tool_choice {
+            completion::ToolChoice::Auto => ToolChoice::Auto,
+            completion::ToolChoice::None => ToolChoice::None,
+            completion::ToolChoice::Required => ToolChoice::Any,
+            completion::ToolChoice::Specific(name) => ToolChoice::Tool { name },
+        }
+    }
+}
+
+impl completion::CompletionModel for CompletionModel {
+    type Response = CompletionResponse;
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn completion(
+        &self,
+        completion_request: completion::CompletionRequest,
+    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+        let request = self.create_request(completion_request)?;
+
         let response = self
             .client
             .post("/v1/messages")
@@ -288,8 +446,8 @@ impl completion::CompletionModel for CompletionModel {
 }
 
 #[derive(Debug, Deserialize)]
-struct ApiErrorResponse {
-    message: String,
+pub struct ApiErrorResponse {
+    pub message: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -298,3 +456,145 @@ enum ApiResponse<T> {
     Message(T),
     Error(ApiErrorResponse),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::completion::{
+        tests::{lookup_tool, tool_chat},
+        CompletionModel as _,
+    };
+
+    #[test]
+    fn test_chat() {
+        let (system, messages) = chat(Some("You describe images.".into()), tool_chat());
+
+        // System messages join the preamble
+        assert_eq!(system, "You describe images.\n\nBe brief.");
+        assert_eq!(
+            serde_json::to_value(messages).unwrap(),
+            json!([
+                {
+                    "role": "user",
+                    "content": [
+                        { "type": "text", "text": "What is on these?" },
+                        { "type": "image", "source": { "type": "url", "url": "https://example.com/a.png" } },
+                        {
+                            "type": "image",
+                            "source": { "type": "base64", "media_type": "image/png", "data": "iVBORw0KGgo=" }
+                        },
+                    ],
+                },
+                {
+                    "role": "assistant",
+                    "content": [
+                        { "type": "text", "text": "Looking them up" },
+                        { "type": "tool_use", "id": "call_1", "name": "lookup", "input": { "image": 1 } },
+                        { "type": "tool_use", "id": "call_2", "name": "lookup", "input": { "image": 2 } },
+                    ],
+                },
+                // The results of a turn go back together, in a user message
+                {
+                    "role": "user",
+                    "content": [
+                        { "type": "tool_result", "tool_use_id": "call_1", "content": "A cat" },
+                        { "type": "tool_result", "tool_use_id": "call_2", "content": "{\"animal\":\"dog\"}" },
+                    ],
+                },
+            ])
+        );
+    }
+
+    fn model() -> CompletionModel {
+        CompletionModel::new(
+            super::super::ClientBuilder::new("key").build(),
+            CLAUDE_3_5_SONNET,
+        )
+    }
+
+    #[test]
+    fn test_request_sampling_params() {
+        let model = model();
+
+        // Anthropic requires max_tokens, which defaults to the model's limit
+        let request = model
+            .create_request(model.completion_request("Hi").build())
+            .unwrap();
+        assert_eq!(request["max_tokens"], 8192);
+        for param in ["temperature", "top_p", "stop_sequences"] {
+            assert!(request.get(param).is_none(), "{param} should not be sent");
+        }
+
+        let request = model
+            .create_request(
+                model
+                    .completion_request("Hi")
+                    .temperature(0.5)
+                    .max_tokens(100)
+                    .top_p(0.9)
+                    .stop_sequence("END")
+                    .stop_sequence("STOP")
+                    .build(),
+            )
+            .unwrap();
+        assert_eq!(request["temperature"], 0.5);
+        assert_eq!(request["max_tokens"], 100);
+        assert_eq!(request["top_p"], 0.9);
+        assert_eq!(request["stop_sequences"], json!(["END", "STOP"]));
+
+        let error = model
+            .create_request(model.completion_request("Hi").seed(42).build())
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            CompletionError::UnsupportedParameter(_, SamplingParam::Seed)
+        ));
+    }
+
+    #[test]
+    fn test_request_tool_choice() {
+        let model = model();
+        let tool_choice = |choice: Option<completion::ToolChoice>| {
+            let request = model
+                .create_request(
+                    model
+                        .completion_request("Hi")
+                        .tool(lookup_tool())
+                        .tool_choice_opt(choice)
+                        .build(),
+                )
+                .unwrap();
+            request["tool_choice"].clone()
+        };
+
+        assert_eq!(tool_choice(None), json!({ "type": "auto" }));
+        assert_eq!(
+            tool_choice(Some(completion::ToolChoice::Auto)),
+            json!({ "type": "auto" })
+        );
+        assert_eq!(
+            tool_choice(Some(completion::ToolChoice::None)),
+            json!({ "type": "none" })
+        );
+        assert_eq!(
+            tool_choice(Some(completion::ToolChoice::Required)),
+            json!({ "type": "any" })
+        );
+        assert_eq!(
+            tool_choice(Some(completion::ToolChoice::Specific("lookup".into()))),
+            json!({ "type": "tool", "name": "lookup" })
+        );
+
+        // Without tools there is nothing to choose from
+        let request = model
+            .create_request(
+                model
+                    .completion_request("Hi")
+                    .tool_choice(completion::ToolChoice::Required)
+                    .build(),
+            )
+            .unwrap();
+        assert!(request.get("tools").is_none());
+        assert!(request.get("tool_choice").is_none());
+    }
+}
@@ -1,13 +1,15 @@
 use async_stream::stream;
-use futures::StreamExt;
+use bytes::Bytes;
+use futures::Stream;
 use serde::Deserialize;
 use serde_json::json;
-use std::iter;
 
-use super::completion::{CompletionModel, Content, Message, ToolChoice, ToolDefinition, Usage};
-use crate::completion::{CompletionError, CompletionRequest};
+use super::completion::{ApiErrorResponse, CompletionModel, Usage};
+use crate::completion::{self, CompletionError, CompletionRequest};
 use crate::json_utils::merge_inplace;
-use crate::streaming::{StreamingChoice, StreamingCompletionModel, StreamingResult};
+use crate::streaming::{
+    sse_events, FinalResponse, StreamingChoice, StreamingCompletionModel, StreamingResult,
+};
 
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
@@ -17,7 +19,7 @@ pub enum StreamingEvent {
     },
     ContentBlockStart {
         index: usize,
-        content_block: Content,
+        content_block: ContentBlock,
     },
     ContentBlockDelta {
         index: usize,
@@ -32,24 +34,54 @@ pub enum StreamingEvent {
     },
     MessageStop,
     Ping,
+    Error {
+        error: ApiErrorResponse,
+    },
+    /// Event types added to the API later, which the stream can skip
+    #[serde(other)]
+    Other,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct MessageStart {
     pub id: String,
     pub role: String,
-    pub content: Vec<Content>,
+    pub content: Vec<ContentBlock>,
     pub model: String,
     pub stop_reason: Option<String>,
     pub stop_sequence: Option<String>,
     pub usage: Usage,
 }
 
+/// The opening of a content block. Text arrives in deltas, so only tool calls are read here.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentBlock {
+    Text {
+        text: String,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+    },
+    /// Blocks the stream does not surface, such as `thinking`
+    #[serde(other)]
+    Other,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ContentDelta {
-    TextDelta { text: String },
-    InputJsonDelta { partial_json: String },
+    TextDelta {
+        text: String,
+    },
+    InputJsonDelta {
+        partial_json: String,
+    },
+    /// Deltas of blocks the stream does not surface, such as `thinking_delta`
+    #[serde(other)]
+    Other,
 }
 
 #[derive(Debug, Deserialize)]
@@ -70,59 +102,8 @@ impl StreamingCompletionModel for CompletionModel {
         &self,
         completion_request: CompletionRequest,
     ) -> Result<StreamingResult, CompletionError> {
-        let prompt_with_context = completion_request.prompt_with_context();
-
-        let max_tokens = if let Some(tokens) = completion_request.max_tokens {
-            tokens
-        } else if let Some(tokens) = self.default_max_tokens {
-            tokens
-        } else {
-            return Err(CompletionError::RequestError(
-                "`max_tokens` must be set for Anthropic".into(),
-            ));
-        };
-
-        let mut request = json!({
-            "model": self.model,
-            "messages": completion_request
-                .chat_history
-                .into_iter()
-                .map(Message::from)
-                .chain(iter::once(Message {
-                    role: "user".to_owned(),
-                    content: prompt_with_context,
-                }))
-                .collect::<Vec<_>>(),
-            "max_tokens": max_tokens,
-            "system": completion_request.preamble.unwrap_or("".to_string()),
-            "stream": true,
-        });
-
-        if let Some(temperature) = completion_request.temperature {
-            merge_inplace(&mut request, json!({ "temperature": temperature }));
-        }
-
-        if !completion_request.tools.is_empty() {
-            merge_inplace(
-                &mut request,
-                json!({
-                    "tools": completion_request
-                        .tools
-                        .into_iter()
-                        .map(|tool| ToolDefinition {
-                            name: tool.name,
-                            description: Some(tool.description),
-                            input_schema: tool.parameters,
-                        })
-                        .collect::<Vec<_>>(),
-                    "tool_choice": ToolChoice::Auto,
-                }),
-            );
-        }
-
-        if let Some(ref params) = completion_request.additional_params {
-            merge_inplace(&mut request, params.clone())
-        }
+        let mut request = self.create_request(completion_request)?;
+        merge_inplace(&mut request, json!({ "stream": true }));
 
         let response = self
             .client
@@ -135,82 +116,238 @@ impl StreamingCompletionModel for CompletionModel {
             return Err(CompletionError::ProviderError(response.text().await?));
         }
 
-        Ok(Box::pin(stream! {
-            let mut current_tool_call: Option<ToolCallState> = None;
-            let mut stream = response.bytes_stream();
+        Ok(Box::pin(streaming_choices(response.bytes_stream())))
+    }
+}
 
-            while let Some(chunk_result) = stream.next().await {
-                let chunk = match chunk_result {
-                    Ok(c) => c,
-                    Err(e) => {
-                        yield Err(CompletionError::from(e));
-                        break;
-                    }
-                };
+/// Reads Anthropic's stream of message events. Text is yielded as it arrives, and each tool call
+/// once its input is complete. The final response is yielded when the message stops.
+fn streaming_choices<E>(
+    bytes: impl Stream<Item = Result<Bytes, E>>,
+) -> impl Stream<Item = Result<StreamingChoice, CompletionError>>
+where
+    CompletionError: From<E>,
+{
+    stream! {
+        let mut current_tool_call: Option<ToolCallState> = None;
+        let mut final_response = FinalResponse::default();
+        for await event in sse_events(bytes) {
+            let event = match event {
+                Ok(event) => event,
+                Err(error) => {
+                    yield Err(error);
+                    return;
+                }
+            };
+            let event = match serde_json::from_str::<StreamingEvent>(&event.data) {
+                Ok(event) => event,
+                Err(error) => {
+                    yield Err(CompletionError::JsonError(error));
+                    return;
+                }
+            };
 
-                let text = match String::from_utf8(chunk.to_vec()) {
-                    Ok(t) => t,
-                    Err(e) => {
-                        yield Err(CompletionError::ResponseError(e.to_string()));
-                        break;
+            match event {
+                StreamingEvent::MessageStart { message } => {
+                    final_response.usage = completion::Usage::from(&message.usage);
+                }
+                StreamingEvent::MessageDelta { delta, usage } => {
+                    // The output token count is cumulative
+                    final_response.usage.completion_tokens = usage.output_tokens;
+                    if delta.stop_reason.is_some() {
+                        final_response.finish_reason = delta.stop_reason;
+                    }
+                }
+                StreamingEvent::MessageStop => {
+                    yield Ok(StreamingChoice::Final(std::mem::take(&mut final_response)));
+                }
+                StreamingEvent::ContentBlockDelta { delta, .. } => match delta {
+                    ContentDelta::TextDelta { text } => {
+                        if current_tool_call.is_none() {
+                            yield Ok(final_response.add(StreamingChoice::Message(text)));
+                        }
                     }
-                };
-
-                for line in text.lines() {
-                    if let Some(data) = line.strip_prefix("data: ") {
-                        if let Ok(event) = serde_json::from_str::<StreamingEvent>(data) {
-                            match event {
-                                StreamingEvent::ContentBlockDelta { delta, .. } => {
-                                    match delta {
-                                        ContentDelta::TextDelta { text } => {
-                                            if current_tool_call.is_none() {
-                                                yield Ok(StreamingChoice::Message(text));
-                                            }
-                                        }
-                                        ContentDelta::InputJsonDelta { partial_json } => {
-                                            if let Some(ref mut tool_call) = current_tool_call {
-                                                tool_call.input_json.push_str(&partial_json);
-                                            }
-                                        }
-                                    }
-                                }
-                                StreamingEvent::ContentBlockStart {
-                                    content_block: Content::ToolUse { id, name, .. },
-                                    ..
-                                } => {
-                                    current_tool_call = Some(ToolCallState {
-                                        name,
-                                        id,
-                                        input_json: String::new(),
-                                    });
-                                }
-                                StreamingEvent::ContentBlockStop { .. } => {
-                                    if let Some(tool_call) = current_tool_call.take() {
-                                        let json_str = if tool_call.input_json.is_empty() {
-                                            "{}"
-                                        } else {
-                                            &tool_call.input_json
-                                        };
-                                        match serde_json::from_str(json_str) {
-                                            Ok(json_value) => {
-                                                yield Ok(StreamingChoice::ToolCall(
-                                                    tool_call.name,
-                                                    tool_call.id,
-                                                    json_value,
-                                                ));
-                                            }
-                                            Err(e) => {
-                                                yield Err(CompletionError::from(e));
-                                            }
-                                        }
-                                    }
-                                },
-                                _ => {}
+                    ContentDelta::InputJsonDelta { partial_json } => {
+                        if let Some(ref mut tool_call) = current_tool_call {
+                            tool_call.input_json.push_str(&partial_json);
+                        }
+                    }
+                    ContentDelta::Other => {}
+                },
+                StreamingEvent::ContentBlockStart {
+                    content_block: ContentBlock::ToolUse { id, name, .. },
+                    ..
+                } => {
+                    current_tool_call = Some(ToolCallState {
+                        name,
+                        id,
+                        input_json: String::new(),
+                    });
+                }
+                StreamingEvent::ContentBlockStop { .. } => {
+                    if let Some(tool_call) = current_tool_call.take() {
+                        let json_str = if tool_call.input_json.is_empty() {
+                            "{}"
+                        } else {
+                            &tool_call.input_json
+                        };
+                        match serde_json::from_str(json_str) {
+                            Ok(json_value) => {
+                                yield Ok(final_response.add(StreamingChoice::ToolCall(
+                                    tool_call.name,
+                                    tool_call.id,
+                                    json_value,
+                                )));
+                            }
+                            Err(e) => {
+                                yield Err(CompletionError::JsonError(e));
                             }
                         }
                     }
                 }
+                StreamingEvent::Error { error } => {
+                    yield Err(CompletionError::ProviderError(error.message));
+                    return;
+                }
+                _ => {}
             }
-        }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::streaming::tests::chunked;
+    use futures::StreamExt;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_streaming_choices() {
+        let body = include_str!("../../../tests/data/anthropic_streaming.sse");
+
+        for size in [1, 9, body.len()] {
+            let choices = streaming_choices(chunked(body, size))
+                .map(Result::unwrap)
+                .collect::<Vec<_>>()
+                .await;
+
+            assert_eq!(
+                choices,
+                vec![
+                    StreamingChoice::Message("Let me check the weather in Z".into()),
+                    StreamingChoice::Message("ürich.".into()),
+                    StreamingChoice::ToolCall(
+                        "get_weather".into(),
+                        "toolu_01".into(),
+                        json!({ "city": "Zürich" }),
+                    ),
+                    StreamingChoice::Final(FinalResponse {
+                        message: "Let me check the weather in Zürich.".into(),
+                        tool_calls: vec![completion::ToolCall {
+                            id: "toolu_01".into(),
+                            name: "get_weather".into(),
+                            arguments: json!({ "city": "Zürich" }),
+                        }],
+                        usage: completion::Usage {
+                            prompt_tokens: 50,
+                            completion_tokens: 24,
+                            cached_tokens: 10,
+                            reasoning_tokens: 0,
+                        },
+                        finish_reason: Some("tool_use".into()),
+                    }),
+                ],
+                "chunks of {size} bytes"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_streaming_error_event() {
+        let body = "event: error\n\
+            data: {\"type\":\"error\",\"error\":{\"type\":\"overloaded_error\",\"message\":\"Overloaded\"}}\n\n";
+
+        let choices = streaming_choices(chunked(body, 7))
+            .collect::<Vec<_>>()
+            .await;
+
+        assert!(matches!(
+            choices.as_slice(),
+            [Err(CompletionError::ProviderError(message))] if message == "Overloaded"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_streaming_skips_unknown_events() {
+        let body = concat!(
+            "event: message_start\n",
+            "data: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_02\",\"type\":\"message\",",
+            "\"role\":\"assistant\",\"content\":[],\"model\":\"claude-3-7-sonnet-20250219\",",
+            "\"stop_reason\":null,\"stop_sequence\":null,\"usage\":{\"input_tokens\":12,\"output_tokens\":1}}}\n\n",
+            "event: content_block_start\n",
+            "data: {\"type\":\"content_block_start\",\"index\":0,",
+            "\"content_block\":{\"type\":\"thinking\",\"thinking\":\"\"}}\n\n",
+            "event: content_block_delta\n",
+            "data: {\"type\":\"content_block_delta\",\"index\":0,",
+            "\"delta\":{\"type\":\"thinking_delta\",\"thinking\":\"The user says hi.\"}}\n\n",
+            "event: content_block_delta\n",
+            "data: {\"type\":\"content_block_delta\",\"index\":0,",
+            "\"delta\":{\"type\":\"signature_delta\",\"signature\":\"EqQB\"}}\n\n",
+            "event: content_block_stop\n",
+            "data: {\"type\":\"content_block_stop\",\"index\":0}\n\n",
+            "event: message_heartbeat\n",
+            "data: {\"type\":\"message_heartbeat\"}\n\n",
+            "event: content_block_start\n",
+            "data: {\"type\":\"content_block_start\",\"index\":1,",
+            "\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\n",
+            "event: content_block_delta\n",
+            "data: {\"type\":\"content_block_delta\",\"index\":1,",
+            "\"delta\":{\"type\":\"text_delta\",\"text\":\"Hello!\"}}\n\n",
+            "event: content_block_stop\n",
+            "data: {\"type\":\"content_block_stop\",\"index\":1}\n\n",
+            "event: message_delta\n",
+            "data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\",",
+            "\"stop_sequence\":null},\"usage\":{\"output_tokens\":9}}\n\n",
+            "event: message_stop\n",
+            "data: {\"type\":\"message_stop\"}\n\n",
+        );
+
+        let choices = streaming_choices(chunked(body, 11))
+            .map(Result::unwrap)
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(
+            choices,
+            vec![
+                StreamingChoice::Message("Hello!".into()),
+                StreamingChoice::Final(FinalResponse {
+                    message: "Hello!".into(),
+                    tool_calls: vec![],
+                    usage: completion::Usage {
+                        prompt_tokens: 12,
+                        completion_tokens: 9,
+                        cached_tokens: 0,
+                        reasoning_tokens: 0,
+                    },
+                    finish_reason: Some("end_turn".into()),
+                }),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_streaming_invalid_event() {
+        let body = "event: message_start\ndata: {\"type\":\"message_start\"}\n\n";
+
+        let choices = streaming_choices(chunked(body, 7))
+            .collect::<Vec<_>>()
+            .await;
+
+        assert!(matches!(
+            choices.as_slice(),
+            [Err(CompletionError::JsonError(_))]
+        ));
     }
 }
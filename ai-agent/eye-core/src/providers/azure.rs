@@ -13,7 +13,10 @@ use crate::{
     completion::{self, CompletionError, CompletionRequest},
     embeddings::{self, EmbeddingError, EmbeddingsBuilder},
     extractor::ExtractorBuilder,
-    json_utils, Embed,
+    json_utils,
+    providers::openai,
+    streaming::{StreamingCompletionModel, StreamingResult},
+    Embed,
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -235,6 +238,10 @@ pub struct EmbeddingData {
 pub struct Usage {
     pub prompt_tokens: usize,
     pub total_tokens: usize,
+    #[serde(default)]
+    pub prompt_tokens_details: Option<openai::PromptTokensDetails>,
+    #[serde(default)]
+    pub completion_tokens_details: Option<openai::CompletionTokensDetails>,
 }
 
 impl std::fmt::Display for Usage {
@@ -247,6 +254,23 @@ impl std::fmt::Display for Usage {
     }
 }
 
+impl From<&Usage> for completion::Usage {
+    fn from(usage: &Usage) -> Self {
+        completion::Usage {
+            prompt_tokens: usage.prompt_tokens as u64,
+            completion_tokens: usage.total_tokens.saturating_sub(usage.prompt_tokens) as u64,
+            cached_tokens: usage
+                .prompt_tokens_details
+                .as_ref()
+                .map_or(0, |details| details.cached_tokens as u64),
+            reasoning_tokens: usage
+                .completion_tokens_details
+                .as_ref()
+                .map_or(0, |details| details.reasoning_tokens as u64),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct EmbeddingModel {
     client: Client,
@@ -370,10 +394,16 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
     type Error = CompletionError;
 
     fn try_from(value: CompletionResponse) -> std::prelude::v1::Result<Self, Self::Error> {
+        let usage = value
+            .usage
+            .as_ref()
+            .map(completion::Usage::from)
+            .unwrap_or_default();
         match value.choices.as_slice() {
             [Choice {
                 message:
                     Message {
+                        content,
                         tool_calls: Some(calls),
                         ..
                     },
@@ -381,14 +411,23 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
             }, ..]
                 if !calls.is_empty() =>
             {
-                let call = calls.first().unwrap();
+                let calls = calls
+                    .iter()
+                    .map(|call| {
+                        Ok(completion::ToolCall {
+                            id: call.id.clone(),
+                            name: call.function.name.clone(),
+                            arguments: serde_json::from_str(&call.function.arguments)?,
+                        })
+                    })
+                    .collect::<Result<_, CompletionError>>()?;
 
                 Ok(completion::CompletionResponse {
-                    choice: completion::ModelChoice::ToolCall(
-                        call.function.name.clone(),
-                        "".to_owned(),
-                        serde_json::from_str(&call.function.arguments)?,
-                    ),
+                    choice: completion::ModelChoice::ToolCalls {
+                        text: content.clone(),
+                        calls,
+                    },
+                    usage,
                     raw_response: value,
                 })
             }
@@ -401,6 +440,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
                 ..
             }, ..] => Ok(completion::CompletionResponse {
                 choice: completion::ModelChoice::Message(content.to_string()),
+                usage,
                 raw_response: value,
             }),
             _ => Err(CompletionError::ResponseError(
@@ -469,21 +509,18 @@ impl CompletionModel {
     }
 }
 
-impl completion::CompletionModel for CompletionModel {
-    type Response = CompletionResponse;
-
-    #[cfg_attr(feature = "worker", worker::send)]
-    async fn completion(
+impl CompletionModel {
+    /// Builds the chat completion request body, which streaming requests extend
+    fn create_completion_request(
         &self,
         mut completion_request: CompletionRequest,
-    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+    ) -> serde_json::Value {
+        let sampling_params = openai::sampling_params(&completion_request);
+
         // Add preamble to chat history (if available)
         // NOTE: Azure o1-preview models does not support system messages
         let mut full_history = if let Some(preamble) = &completion_request.preamble {
-            vec![completion::Message {
-                role: "system".into(),
-                content: preamble.clone(),
-            }]
+            vec![completion::Message::system(preamble.clone())]
         } else {
             vec![]
         };
@@ -492,38 +529,50 @@ impl completion::CompletionModel for CompletionModel {
         full_history.append(&mut completion_request.chat_history);
 
         // Add context documents to chat history
-        let prompt_with_context = completion_request.prompt_with_context();
-
-        // Add context documents to chat history
-        full_history.push(completion::Message {
-            role: "user".into(),
-            content: prompt_with_context,
-        });
+        full_history.push(completion_request.prompt_with_context());
 
         let request = if completion_request.tools.is_empty() {
             json!({
-                "messages": full_history,
-                "temperature": completion_request.temperature,
+                "messages": openai::chat_messages(full_history),
             })
         } else {
             json!({
-                "messages": full_history,
-                "temperature": completion_request.temperature,
+                "messages": openai::chat_messages(full_history),
                 "tools": completion_request.tools.into_iter().map(ToolDefinition::from).collect::<Vec<_>>(),
-                "tool_choice": "auto",
+                "tool_choice": openai::tool_choice(&completion_request.tool_choice.unwrap_or_default()),
             })
         };
 
+        let mut request = json_utils::merge(request, sampling_params);
+        if let Some(format) = &completion_request.response_format {
+            json_utils::merge_inplace(
+                &mut request,
+                json!({ "response_format": openai::response_format(format) }),
+            );
+        }
+
+        if let Some(params) = completion_request.additional_params {
+            json_utils::merge(request, params)
+        } else {
+            request
+        }
+    }
+}
+
+impl completion::CompletionModel for CompletionModel {
+    type Response = CompletionResponse;
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn completion(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+        let request = self.create_completion_request(completion_request);
+
         let response = self
             .client
             .post_chat_completion(&self.model)
-            .json(
-                &if let Some(params) = completion_request.additional_params {
-                    json_utils::merge(request, params)
-                } else {
-                    request
-                },
-            )
+            .json(&request)
             .send()
             .await?;
 
@@ -544,6 +593,21 @@ impl completion::CompletionModel for CompletionModel {
     }
 }
 
+impl StreamingCompletionModel for CompletionModel {
+    async fn stream(&self, request: CompletionRequest) -> Result<StreamingResult, CompletionError> {
+        let mut request = self.create_completion_request(request);
+        json_utils::merge_inplace(
+            &mut request,
+            json!({ "stream": true, "stream_options": { "include_usage": true } }),
+        );
+
+        openai::send_streaming_request::<Usage>(
+            self.client.post_chat_completion(&self.model).json(&request),
+        )
+        .await
+    }
+}
+
 #[cfg(test)]
 mod azure_tests {
     use super::*;
@@ -577,11 +641,18 @@ mod azure_tests {
             .completion(CompletionRequest {
                 preamble: Some("You are a helpful assistant.".to_string()),
                 chat_history: vec![],
-                prompt: "Hello, world!".to_string(),
+                prompt: "Hello, world!".into(),
                 documents: vec![],
                 max_tokens: Some(100),
                 temperature: Some(0.0),
+                stop: vec![],
+                top_p: None,
+                seed: None,
+                frequency_penalty: None,
+                presence_penalty: None,
+                response_format: None,
                 tools: vec![],
+                tool_choice: None,
                 additional_params: None,
             })
             .await
@@ -12,12 +12,17 @@ use std::collections::HashMap;
 
 use crate::{
     agent::AgentBuilder,
-    completion::{self, CompletionError},
+    completion::{self, CompletionError, SamplingParam},
     embeddings::{self, EmbeddingError, EmbeddingsBuilder},
     extractor::ExtractorBuilder,
-    json_utils, Embed,
+    json_utils,
+    streaming::{lines, FinalResponse, StreamingChoice, StreamingCompletionModel, StreamingResult},
+    Embed,
 };
 
+use async_stream::stream;
+use bytes::Bytes;
+use futures::Stream;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
@@ -157,7 +162,7 @@ pub struct EmbeddingResponse {
     pub meta: Option<Meta>,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Deserialize)]
 pub struct Meta {
     pub api_version: ApiVersion,
     pub billed_units: BilledUnits,
@@ -165,7 +170,7 @@ pub struct Meta {
     pub warnings: Vec<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Deserialize)]
 pub struct ApiVersion {
     pub version: String,
     #[serde(default)]
@@ -196,6 +201,16 @@ impl std::fmt::Display for BilledUnits {
     }
 }
 
+impl From<&BilledUnits> for completion::Usage {
+    fn from(billed_units: &BilledUnits) -> Self {
+        completion::Usage {
+            prompt_tokens: billed_units.input_tokens as u64,
+            completion_tokens: billed_units.output_tokens as u64,
+            ..Default::default()
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct EmbeddingModel {
     client: Client,
@@ -317,6 +332,8 @@ pub struct CompletionResponse {
     pub tool_calls: Vec<ToolCall>,
     #[serde(default)]
     pub chat_history: Vec<ChatHistory>,
+    #[serde(default)]
+    pub meta: Option<Meta>,
 }
 
 impl From<CompletionResponse> for completion::CompletionResponse<CompletionResponse> {
@@ -325,18 +342,33 @@ impl From<CompletionResponse> for completion::CompletionResponse<CompletionRespo
             text, tool_calls, ..
         } = &response;
 
+        // Cohere does not assign ids to tool calls
         let model_response = if !tool_calls.is_empty() {
-            completion::ModelChoice::ToolCall(
-                tool_calls.first().unwrap().name.clone(),
-                "".to_owned(),
-                tool_calls.first().unwrap().parameters.clone(),
-            )
+            completion::ModelChoice::ToolCalls {
+                text: (!text.is_empty()).then(|| text.clone()),
+                calls: tool_calls
+                    .iter()
+                    .enumerate()
+                    .map(|(i, call)| completion::ToolCall {
+                        id: format!("{}_{}", call.name, i),
+                        name: call.name.clone(),
+                        arguments: call.parameters.clone(),
+                    })
+                    .collect(),
+            }
         } else {
             completion::ModelChoice::Message(text.clone())
         };
 
+        let usage = response
+            .meta
+            .as_ref()
+            .map(|meta| completion::Usage::from(&meta.billed_units))
+            .unwrap_or_default();
+
         completion::CompletionResponse {
             choice: model_response,
+            usage,
             raw_response: response,
         }
     }
@@ -379,12 +411,18 @@ pub struct Connector {
     pub id: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct ToolCall {
     pub name: String,
     pub parameters: serde_json::Value,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ToolResult {
+    pub call: ToolCall,
+    pub outputs: Vec<serde_json::Value>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ChatHistory {
     pub role: String,
@@ -479,21 +517,116 @@ impl From<completion::ToolDefinition> for ToolDefinition {
 #[derive(Deserialize, Serialize)]
 pub struct Message {
     pub role: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub message: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_results: Vec<ToolResult>,
 }
 
-impl From<completion::Message> for Message {
-    fn from(message: completion::Message) -> Self {
-        Self {
-            role: match message.role.as_str() {
-                "system" => "SYSTEM".to_owned(),
-                "user" => "USER".to_owned(),
-                "assistant" => "CHATBOT".to_owned(),
-                _ => "USER".to_owned(),
-            },
-            message: message.content,
-        }
-    }
+/// The sampling parameters set on the request, as Cohere chat fields. Parameters that are not
+/// set are left out.
+fn sampling_params(request: &completion::CompletionRequest) -> serde_json::Value {
+    let params = [
+        ("temperature", request.temperature.map(|value| json!(value))),
+        ("max_tokens", request.max_tokens.map(|value| json!(value))),
+        (
+            "stop_sequences",
+            (!request.stop.is_empty()).then(|| json!(request.stop)),
+        ),
+        ("p", request.top_p.map(|value| json!(value))),
+        ("seed", request.seed.map(|value| json!(value))),
+        (
+            "frequency_penalty",
+            request.frequency_penalty.map(|value| json!(value)),
+        ),
+        (
+            "presence_penalty",
+            request.presence_penalty.map(|value| json!(value)),
+        ),
+    ];
+
+    serde_json::Value::Object(
+        params
+            .into_iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value?)))
+            .collect(),
+    )
+}
+
+/// Converts chat messages to Cohere's chat history. Cohere identifies a tool call by its name
+/// and parameters, so each tool result is sent along with the call it answers.
+fn chat_history(messages: Vec<completion::Message>) -> Result<Vec<Message>, CompletionError> {
+    let mut calls = HashMap::new();
+
+    messages
+        .into_iter()
+        .map(|message| {
+            if !message.images().is_empty() {
+                return Err(CompletionError::RequestError(
+                    "Cohere chat does not accept images".into(),
+                ));
+            }
+            let text = message.text();
+            Ok(match message {
+                completion::Message::System { content } => Message {
+                    role: "SYSTEM".to_owned(),
+                    message: content,
+                    tool_calls: vec![],
+                    tool_results: vec![],
+                },
+                completion::Message::User { .. } => Message {
+                    role: "USER".to_owned(),
+                    message: text,
+                    tool_calls: vec![],
+                    tool_results: vec![],
+                },
+                completion::Message::Assistant { .. } => Message {
+                    role: "CHATBOT".to_owned(),
+                    message: text,
+                    tool_calls: message
+                        .tool_calls()
+                        .into_iter()
+                        .map(|call| {
+                            let tool_call = ToolCall {
+                                name: call.name.clone(),
+                                parameters: call.arguments.clone(),
+                            };
+                            calls.insert(call.id.clone(), tool_call.clone());
+                            tool_call
+                        })
+                        .collect(),
+                    tool_results: vec![],
+                },
+                completion::Message::Tool { content } => Message {
+                    role: "TOOL".to_owned(),
+                    message: String::new(),
+                    tool_calls: vec![],
+                    tool_results: content
+                        .into_iter()
+                        .map(|result| {
+                            let call = calls.get(&result.id).cloned().ok_or_else(|| {
+                                CompletionError::RequestError(
+                                    format!("No tool call with id {} in chat history", result.id)
+                                        .into(),
+                                )
+                            })?;
+                            // Outputs must be objects: other tool outputs are wrapped in one
+                            let output = match serde_json::from_str(&result.content) {
+                                Ok(serde_json::Value::Object(output)) => output.into(),
+                                _ => json!({ "output": result.content }),
+                            };
+                            Ok(ToolResult {
+                                call,
+                                outputs: vec![output],
+                            })
+                        })
+                        .collect::<Result<_, CompletionError>>()?,
+                },
+            })
+        })
+        .collect()
 }
 
 #[derive(Clone)]
@@ -509,6 +642,58 @@ impl CompletionModel {
             model: model.to_string(),
         }
     }
+
+    fn create_completion_request(
+        &self,
+        completion_request: completion::CompletionRequest,
+    ) -> Result<serde_json::Value, CompletionError> {
+        completion_request.check_unsupported("Cohere", &[SamplingParam::ResponseFormat])?;
+
+        let sampling_params = sampling_params(&completion_request);
+
+        // The prompt is sent apart from the chat history: a user prompt as the message, or
+        // tool results when an agent is continuing after a tool call
+        let mut chat_history = chat_history(
+            completion_request
+                .chat_history
+                .into_iter()
+                .chain(std::iter::once(completion_request.prompt))
+                .collect(),
+        )?;
+        let prompt = chat_history
+            .pop()
+            .expect("Chat history should contain the prompt");
+
+        // Cohere's chat API cannot force a tool call: the closest it gets is offering the
+        // model only the tool it should call, or no tool at all
+        let tools = match completion_request.tool_choice.unwrap_or_default() {
+            completion::ToolChoice::None => vec![],
+            completion::ToolChoice::Specific(name) => completion_request
+                .tools
+                .into_iter()
+                .filter(|tool| tool.name == name)
+                .collect(),
+            completion::ToolChoice::Auto | completion::ToolChoice::Required => {
+                completion_request.tools
+            }
+        };
+
+        let request = json!({
+            "model": self.model,
+            "preamble": completion_request.preamble,
+            "message": prompt.message,
+            "tool_results": prompt.tool_results,
+            "documents": completion_request.documents,
+            "chat_history": chat_history,
+            "tools": tools.into_iter().map(ToolDefinition::from).collect::<Vec<_>>(),
+        });
+        let request = json_utils::merge(request, sampling_params);
+
+        Ok(match completion_request.additional_params {
+            Some(params) => json_utils::merge(request, params),
+            None => request,
+        })
+    }
 }
 
 impl completion::CompletionModel for CompletionModel {
@@ -519,28 +704,9 @@ impl completion::CompletionModel for CompletionModel {
         &self,
         completion_request: completion::CompletionRequest,
     ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
-        let request = json!({
-            "model": self.model,
-            "preamble": completion_request.preamble,
-            "message": completion_request.prompt,
-            "documents": completion_request.documents,
-            "chat_history": completion_request.chat_history.into_iter().map(Message::from).collect::<Vec<_>>(),
-            "temperature": completion_request.temperature,
-            "tools": completion_request.tools.into_iter().map(ToolDefinition::from).collect::<Vec<_>>(),
-        });
+        let request = self.create_completion_request(completion_request)?;
 
-        let response = self
-            .client
-            .post("/v1/chat")
-            .json(
-                &if let Some(ref params) = completion_request.additional_params {
-                    json_utils::merge(request.clone(), params.clone())
-                } else {
-                    request.clone()
-                },
-            )
-            .send()
-            .await?;
+        let response = self.client.post("/v1/chat").json(&request).send().await?;
 
         if response.status().is_success() {
             match response.json::<ApiResponse<CompletionResponse>>().await? {
@@ -552,3 +718,240 @@ impl completion::CompletionModel for CompletionModel {
         }
     }
 }
+
+// ================================================================
+// Cohere Streaming API
+// ================================================================
+/// An event of a streaming chat, sent by Cohere as one JSON object per line
+#[derive(Debug, Deserialize)]
+#[serde(tag = "event_type", rename_all = "kebab-case")]
+pub enum StreamEvent {
+    TextGeneration {
+        text: String,
+    },
+    /// A fragment of the tool calls being generated, or of the text the model writes before them
+    ToolCallsChunk {
+        #[serde(default)]
+        text: Option<String>,
+    },
+    /// The complete tool calls, sent once the model has generated all of them
+    ToolCallsGeneration {
+        #[serde(default)]
+        tool_calls: Vec<ToolCall>,
+    },
+    StreamEnd {
+        finish_reason: String,
+        #[serde(default)]
+        response: Option<StreamEndResponse>,
+    },
+    /// Events that carry nothing the stream needs, such as `stream-start`
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StreamEndResponse {
+    #[serde(default)]
+    pub meta: Option<Meta>,
+}
+
+/// Reads the lines of a streaming chat into streaming choices. The fragments of tool calls are
+/// skipped: the calls are yielded whole once generated, in the order Cohere sent them. The final
+/// response, with the billed units, is yielded at the end of the stream.
+fn streaming_choices<E>(
+    bytes: impl Stream<Item = Result<Bytes, E>>,
+) -> impl Stream<Item = Result<StreamingChoice, CompletionError>>
+where
+    CompletionError: From<E>,
+{
+    stream! {
+        let mut response = FinalResponse::default();
+        // Cohere does not assign ids to tool calls
+        let mut calls = 0;
+        for await line in lines(bytes) {
+            let line = match line {
+                Ok(line) => line,
+                Err(error) => {
+                    yield Err(error);
+                    return;
+                }
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event = match serde_json::from_str::<StreamEvent>(&line) {
+                Ok(event) => event,
+                Err(error) => {
+                    yield Err(CompletionError::JsonError(error));
+                    return;
+                }
+            };
+
+            match event {
+                StreamEvent::TextGeneration { text }
+                | StreamEvent::ToolCallsChunk { text: Some(text) } => {
+                    if !text.is_empty() {
+                        yield Ok(response.add(StreamingChoice::Message(text)));
+                    }
+                }
+                StreamEvent::ToolCallsGeneration { tool_calls } => {
+                    for call in tool_calls {
+                        let id = format!("{}_{}", call.name, calls);
+                        calls += 1;
+                        yield Ok(response.add(StreamingChoice::ToolCall(
+                            call.name,
+                            id,
+                            call.parameters,
+                        )));
+                    }
+                }
+                StreamEvent::StreamEnd { finish_reason, response: end } => {
+                    if finish_reason == "ERROR" {
+                        yield Err(CompletionError::ProviderError(
+                            "Cohere stream ended with an error".into(),
+                        ));
+                        return;
+                    }
+                    if let Some(meta) = end.and_then(|end| end.meta) {
+                        response.usage = completion::Usage::from(&meta.billed_units);
+                    }
+                    response.finish_reason = Some(finish_reason);
+                    yield Ok(StreamingChoice::Final(response));
+                    return;
+                }
+                StreamEvent::ToolCallsChunk { text: None } | StreamEvent::Other => {}
+            }
+        }
+    }
+}
+
+impl StreamingCompletionModel for CompletionModel {
+    async fn stream(
+        &self,
+        completion_request: completion::CompletionRequest,
+    ) -> Result<StreamingResult, CompletionError> {
+        let mut request = self.create_completion_request(completion_request)?;
+        json_utils::merge_inplace(&mut request, json!({ "stream": true }));
+
+        let response = self.client.post("/v1/chat").json(&request).send().await?;
+
+        if !response.status().is_success() {
+            return Err(CompletionError::ProviderError(response.text().await?));
+        }
+
+        Ok(Box::pin(streaming_choices(response.bytes_stream())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::completion::tests::tool_chat;
+    use crate::streaming::tests::chunked;
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn test_streaming_choices() {
+        let body = include_str!("../../tests/data/cohere_streaming.jsonl");
+
+        for size in [1, 11, body.len()] {
+            let choices = streaming_choices(chunked(body, size))
+                .map(Result::unwrap)
+                .collect::<Vec<_>>()
+                .await;
+
+            assert_eq!(
+                choices,
+                vec![
+                    StreamingChoice::Message("I will look up the weather.".into()),
+                    StreamingChoice::ToolCall(
+                        "get_weather".into(),
+                        "get_weather_0".into(),
+                        json!({ "city": "Zürich" }),
+                    ),
+                    StreamingChoice::Final(FinalResponse {
+                        message: "I will look up the weather.".into(),
+                        tool_calls: vec![completion::ToolCall {
+                            id: "get_weather_0".into(),
+                            name: "get_weather".into(),
+                            arguments: json!({ "city": "Zürich" }),
+                        }],
+                        usage: completion::Usage {
+                            prompt_tokens: 55,
+                            completion_tokens: 19,
+                            ..Default::default()
+                        },
+                        finish_reason: Some("COMPLETE".into()),
+                    }),
+                ],
+                "chunks of {size} bytes"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_streaming_error() {
+        let body = "{\"event_type\":\"text-generation\",\"text\":\"Hi\"}\n\
+            {\"event_type\":\"stream-end\",\"finish_reason\":\"ERROR\"}\n";
+
+        let choices = streaming_choices(chunked(body, 8))
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(choices.len(), 2);
+        assert!(matches!(choices[1], Err(CompletionError::ProviderError(_))));
+    }
+
+    #[test]
+    fn test_chat_history() {
+        let mut messages = tool_chat();
+        // Cohere does not accept images
+        assert!(matches!(
+            chat_history(messages.clone()),
+            Err(CompletionError::RequestError(_))
+        ));
+        messages[1] = completion::Message::user("What is on these?");
+
+        let history = serde_json::to_value(chat_history(messages).unwrap()).unwrap();
+
+        assert_eq!(
+            history,
+            json!([
+                { "role": "SYSTEM", "message": "Be brief." },
+                { "role": "USER", "message": "What is on these?" },
+                {
+                    "role": "CHATBOT",
+                    "message": "Looking them up",
+                    "tool_calls": [
+                        { "name": "lookup", "parameters": { "image": 1 } },
+                        { "name": "lookup", "parameters": { "image": 2 } },
+                    ],
+                },
+                // Each result carries the call it answers, and outputs that are not objects
+                // are wrapped in one
+                {
+                    "role": "TOOL",
+                    "tool_results": [
+                        {
+                            "call": { "name": "lookup", "parameters": { "image": 1 } },
+                            "outputs": [{ "output": "A cat" }],
+                        },
+                        {
+                            "call": { "name": "lookup", "parameters": { "image": 2 } },
+                            "outputs": [{ "animal": "dog" }],
+                        },
+                    ],
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_chat_history_result_without_call() {
+        let error = chat_history(vec![completion::Message::tool_result("call_9", "A cat")])
+            .err()
+            .unwrap();
+
+        assert!(error.to_string().contains("No tool call with id call_9"));
+    }
+}
@@ -9,9 +9,14 @@
 //! let deepseek_chat = client.completion_model(deepseek::DEEPSEEK_CHAT);
 //! ```
 use crate::{
-    completion::{CompletionModel, CompletionRequest, CompletionResponse},
+    completion::{
+        CompletionModel, CompletionRequest, CompletionResponse, Message, ResponseFormat,
+        SamplingParam,
+    },
     extractor::ExtractorBuilder,
     json_utils,
+    providers::openai,
+    streaming::{StreamingCompletionModel, StreamingResult},
 };
 use reqwest::Client as HttpClient;
 use schemars::JsonSchema;
@@ -83,17 +88,54 @@ impl Client {
 pub struct DeepSeekResponse {
     // We'll match the JSON:
     pub choices: Vec<Choice>,
-    // you may want usage or other fields
+    pub usage: Option<DeepSeekUsage>,
+}
+
+/// Token usage, split between prompt tokens read from DeepSeek's context cache and the others
+#[derive(Debug, Deserialize)]
+pub struct DeepSeekUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    #[serde(default)]
+    pub prompt_cache_hit_tokens: u64,
+    #[serde(default)]
+    pub completion_tokens_details: Option<DeepSeekCompletionTokensDetails>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeepSeekCompletionTokensDetails {
+    #[serde(default)]
+    pub reasoning_tokens: u64,
+}
+
+impl From<&DeepSeekUsage> for crate::completion::Usage {
+    fn from(usage: &DeepSeekUsage) -> Self {
+        crate::completion::Usage {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            cached_tokens: usage.prompt_cache_hit_tokens,
+            reasoning_tokens: usage
+                .completion_tokens_details
+                .as_ref()
+                .map_or(0, |details| details.reasoning_tokens),
+        }
+    }
 }
 
 impl TryFrom<DeepSeekResponse> for CompletionResponse<DeepSeekResponse> {
     type Error = crate::completion::CompletionError;
 
     fn try_from(value: DeepSeekResponse) -> Result<Self, Self::Error> {
+        let usage = value
+            .usage
+            .as_ref()
+            .map(crate::completion::Usage::from)
+            .unwrap_or_default();
         match value.choices.as_slice() {
             [Choice {
                 message:
                     Some(DeepSeekMessage {
+                        content,
                         tool_calls: Some(calls),
                         ..
                     }),
@@ -101,14 +143,23 @@ impl TryFrom<DeepSeekResponse> for CompletionResponse<DeepSeekResponse> {
             }, ..]
                 if !calls.is_empty() =>
             {
-                let call = calls.first().unwrap();
+                let calls = calls
+                    .iter()
+                    .map(|call| {
+                        Ok(crate::completion::ToolCall {
+                            id: call.id.clone(),
+                            name: call.function.name.clone(),
+                            arguments: serde_json::from_str(&call.function.arguments)?,
+                        })
+                    })
+                    .collect::<Result<_, crate::completion::CompletionError>>()?;
 
                 Ok(crate::completion::CompletionResponse {
-                    choice: crate::completion::ModelChoice::ToolCall(
-                        call.function.name.clone(),
-                        "".to_owned(),
-                        serde_json::from_str(&call.function.arguments)?,
-                    ),
+                    choice: crate::completion::ModelChoice::ToolCalls {
+                        text: content.clone(),
+                        calls,
+                    },
+                    usage,
                     raw_response: value,
                 })
             }
@@ -121,6 +172,7 @@ impl TryFrom<DeepSeekResponse> for CompletionResponse<DeepSeekResponse> {
                 ..
             }, ..] => Ok(crate::completion::CompletionResponse {
                 choice: crate::completion::ModelChoice::Message(content.to_string()),
+                usage,
                 raw_response: value,
             }),
             _ => Err(crate::completion::CompletionError::ResponseError(
@@ -177,60 +229,54 @@ pub struct DeepSeekCompletionModel {
     pub model: String,
 }
 
-impl CompletionModel for DeepSeekCompletionModel {
-    type Response = DeepSeekResponse;
-
-    #[cfg_attr(feature = "worker", worker::send)]
-    async fn completion(
+impl DeepSeekCompletionModel {
+    /// Builds the chat completion request body, which streaming requests extend
+    fn create_completion_request(
         &self,
         request: CompletionRequest,
-    ) -> Result<CompletionResponse<DeepSeekResponse>, crate::completion::CompletionError> {
-        // 1. Build the array of messages from request.chat_history + user prompt
-        // if request.preamble is set, it becomes "system" or the first message.
-        // So let's gather them in the style "system" + "user" + chat_history => JSON messages.
-
-        let mut messages_json = vec![];
-
-        // If preamble is present, push a system message
-        if let Some(preamble) = &request.preamble {
-            messages_json.push(json!({
-                "role": "system",
-                "content": preamble,
-            }));
+    ) -> Result<serde_json::Value, crate::completion::CompletionError> {
+        // 1. Build the array of messages: the preamble as a "system" message, then
+        // request.chat_history and the prompt (the user's message, or a tool result when an
+        // agent is continuing after a tool call). DeepSeek accepts OpenAI's chat format.
+        let mut messages = vec![];
+
+        // DeepSeek's JSON mode does not take a schema, so it is given in the system prompt
+        let schema = match &request.response_format {
+            Some(ResponseFormat::JsonSchema { schema, .. }) => Some(format!(
+                "Answer with a JSON object that matches this JSON schema:\n{schema}"
+            )),
+            _ => None,
+        };
+        let preamble = [request.preamble.clone(), schema]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        if !preamble.is_empty() {
+            messages.push(Message::system(preamble.join("\n\n")));
         }
+        messages.extend(request.chat_history.iter().cloned());
+        messages.push(request.prompt_with_context());
 
-        // If chat_history is present, we can push them.
-        // Typically, a "user" role is "USER" and an "assistant" role is "system" or "assistant"
-        for msg in &request.chat_history {
-            let role = match msg.role.as_str() {
-                "system" => "system",
-                "assistant" => "assistant",
-                _ => "user",
-            };
-            messages_json.push(json!({
-                "role": role,
-                "content": msg.content,
-            }));
+        if messages.iter().any(|message| !message.images().is_empty()) {
+            return Err(crate::completion::CompletionError::RequestError(
+                "DeepSeek models do not accept images".into(),
+            ));
         }
 
-        // Add user’s prompt as well
-        messages_json.push(json!({
-            "role": "user",
-            "content": request.prompt_with_context(),
-        }));
-
-        // 2. Prepare the body as DeepSeek expects
-        let body = json!({
-            "model": self.model,
-            "messages": messages_json,
-            "frequency_penalty": 0,
-            "max_tokens": request.max_tokens.unwrap_or(2048),
-            "presence_penalty": 0,
-            "temperature": request.temperature.unwrap_or(1.0),
-            "top_p": 1,
-            "logprobs": false,
-            "stream": false,
-        });
+        let messages_json = openai::chat_messages(messages);
+
+        // 2. Prepare the body as DeepSeek expects, leaving the sampling parameters that are
+        // not set to DeepSeek's defaults
+        request.check_unsupported("DeepSeek", &[SamplingParam::Seed])?;
+        let body = json_utils::merge(
+            json!({
+                "model": self.model,
+                "messages": messages_json,
+                "logprobs": false,
+                "stream": false,
+            }),
+            openai::sampling_params(&request),
+        );
 
         // prepare tools
         let tools = if request.tools.is_empty() {
@@ -240,29 +286,48 @@ impl CompletionModel for DeepSeekCompletionModel {
         } else {
             json!({
                 "tools": request.tools.into_iter().map(DeepSeekToolDefinition::from).collect::<Vec<_>>(),
-                "tool_choice": "auto",
+                "tool_choice": openai::tool_choice(&request.tool_choice.unwrap_or_default()),
             })
         };
 
-        let body = json_utils::merge(body, tools);
+        let mut body = json_utils::merge(body, tools);
+        if request.response_format.is_some() {
+            json_utils::merge_inplace(
+                &mut body,
+                json!({ "response_format": { "type": "json_object" } }),
+            );
+        }
 
         // if user set additional_params, merge them:
-        let final_body = if let Some(params) = request.additional_params {
+        Ok(if let Some(params) = request.additional_params {
             json_utils::merge(body, params)
         } else {
             body
-        };
+        })
+    }
 
-        // 3. Execute the HTTP call
+    fn post_chat_completion(&self, body: &serde_json::Value) -> reqwest::RequestBuilder {
         let url = format!("{}/chat/completions", self.client.base_url);
-        let resp = self
-            .client
+        self.client
             .http_client
             .post(url)
             .bearer_auth(&self.client.api_key)
-            .json(&final_body)
-            .send()
-            .await?;
+            .json(body)
+    }
+}
+
+impl CompletionModel for DeepSeekCompletionModel {
+    type Response = DeepSeekResponse;
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<CompletionResponse<DeepSeekResponse>, crate::completion::CompletionError> {
+        let body = self.create_completion_request(request)?;
+
+        // 3. Execute the HTTP call
+        let resp = self.post_chat_completion(&body).send().await?;
 
         if !resp.status().is_success() {
             let status = resp.status();
@@ -279,6 +344,21 @@ impl CompletionModel for DeepSeekCompletionModel {
     }
 }
 
+impl StreamingCompletionModel for DeepSeekCompletionModel {
+    async fn stream(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<StreamingResult, crate::completion::CompletionError> {
+        let mut body = self.create_completion_request(request)?;
+        json_utils::merge_inplace(
+            &mut body,
+            json!({ "stream": true, "stream_options": { "include_usage": true } }),
+        );
+
+        openai::send_streaming_request::<DeepSeekUsage>(self.post_chat_completion(&body)).await
+    }
+}
+
 // ================================================================
 // DeepSeek Completion API
 // ================================================================
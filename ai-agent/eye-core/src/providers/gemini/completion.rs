@@ -13,11 +13,12 @@ pub const GEMINI_1_5_PRO_8B: &str = "gemini-1.5-pro-8b";
 pub const GEMINI_1_0_PRO: &str = "gemini-1.0-pro";
 
 use gemini_api_types::{
-    Content, ContentCandidate, FunctionDeclaration, GenerateContentRequest,
-    GenerateContentResponse, GenerationConfig, Part, Role, Tool,
+    Blob, Content, ContentCandidate, FileData, FunctionCall, FunctionDeclaration, FunctionResponse,
+    GenerateContentRequest, GenerateContentResponse, GenerationConfig, Part, Role, Schema, Tool,
+    ToolConfig,
 };
 use serde_json::{Map, Value};
-use std::convert::TryFrom;
+use std::{collections::HashMap, convert::TryFrom};
 
 use crate::completion::{self, CompletionError, CompletionRequest};
 
@@ -29,7 +30,7 @@ use super::Client;
 
 #[derive(Clone)]
 pub struct CompletionModel {
-    client: Client,
+    pub(super) client: Client,
     pub model: String,
 }
 
@@ -42,23 +43,16 @@ impl CompletionModel {
     }
 }
 
-impl completion::CompletionModel for CompletionModel {
-    type Response = GenerateContentResponse;
-
-    #[cfg_attr(feature = "worker", worker::send)]
-    async fn completion(
+impl CompletionModel {
+    /// Builds the request, which is the same for blocking and streaming completions
+    pub(super) fn create_request(
         &self,
         mut completion_request: CompletionRequest,
-    ) -> Result<completion::CompletionResponse<GenerateContentResponse>, CompletionError> {
+    ) -> Result<GenerateContentRequest, CompletionError> {
         let mut full_history = Vec::new();
         full_history.append(&mut completion_request.chat_history);
 
-        let prompt_with_context = completion_request.prompt_with_context();
-
-        full_history.push(completion::Message {
-            role: "user".into(),
-            content: prompt_with_context,
-        });
+        full_history.push(completion_request.prompt_with_context());
 
         // Handle Gemini specific parameters
         let additional_params = completion_request
@@ -76,22 +70,33 @@ impl completion::CompletionModel for CompletionModel {
             generation_config.max_output_tokens = Some(max_tokens);
         }
 
-        let request = GenerateContentRequest {
-            contents: full_history
-                .into_iter()
-                .map(|msg| Content {
-                    parts: vec![Part {
-                        text: Some(msg.content),
-                        ..Default::default()
-                    }],
-                    role: match msg.role.as_str() {
-                        "system" => Some(Role::Model),
-                        "user" => Some(Role::User),
-                        "assistant" => Some(Role::Model),
-                        _ => None,
-                    },
-                })
-                .collect(),
+        if !completion_request.stop.is_empty() {
+            generation_config.stop_sequences = Some(completion_request.stop);
+        }
+        if let Some(top_p) = completion_request.top_p {
+            generation_config.top_p = Some(top_p);
+        }
+        if let Some(seed) = completion_request.seed {
+            generation_config.seed = Some(seed);
+        }
+        if let Some(frequency_penalty) = completion_request.frequency_penalty {
+            generation_config.frequency_penalty = Some(frequency_penalty);
+        }
+        if let Some(presence_penalty) = completion_request.presence_penalty {
+            generation_config.presence_penalty = Some(presence_penalty);
+        }
+        if let Some(response_format) = completion_request.response_format {
+            generation_config.response_mime_type = Some("application/json".to_string());
+            if let completion::ResponseFormat::JsonSchema { schema, .. } = response_format {
+                let schema = openapi_schema(&schema, &schema["definitions"]);
+                generation_config.response_schema = Some(Schema::try_from(schema)?);
+            }
+        }
+
+        let (system_instruction, contents) = chat(completion_request.preamble, full_history);
+
+        Ok(GenerateContentRequest {
+            contents,
             generation_config: Some(generation_config),
             safety_settings: None,
             tools: Some(
@@ -101,15 +106,21 @@ impl completion::CompletionModel for CompletionModel {
                     .map(Tool::from)
                     .collect(),
             ),
-            tool_config: None,
-            system_instruction: Some(Content {
-                parts: vec![Part {
-                    text: Some("system".to_string()),
-                    ..Default::default()
-                }],
-                role: Some(Role::Model),
-            }),
-        };
+            tool_config: completion_request.tool_choice.map(ToolConfig::from),
+            system_instruction,
+        })
+    }
+}
+
+impl completion::CompletionModel for CompletionModel {
+    type Response = GenerateContentResponse;
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn completion(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<completion::CompletionResponse<GenerateContentResponse>, CompletionError> {
+        let request = self.create_request(completion_request)?;
 
         tracing::debug!("Sending completion request to Gemini API");
 
@@ -139,6 +150,167 @@ impl completion::CompletionModel for CompletionModel {
     }
 }
 
+/// Converts the preamble and chat messages to Gemini's system instruction and contents.
+/// Gemini takes system instructions apart from the contents, and matches function responses
+/// to function calls by name rather than by id.
+fn chat(
+    preamble: Option<String>,
+    messages: Vec<completion::Message>,
+) -> (Option<Content>, Vec<Content>) {
+    let mut system = preamble.into_iter().collect::<Vec<_>>();
+    let mut call_names = HashMap::new();
+
+    let contents = messages
+        .into_iter()
+        .filter_map(|message| match message {
+            completion::Message::System { content } => {
+                system.push(content);
+                None
+            }
+            completion::Message::User { content } => Some(Content {
+                parts: content
+                    .into_iter()
+                    .map(|part| match part {
+                        completion::UserContent::Text { text } => Part {
+                            text: Some(text),
+                            ..Default::default()
+                        },
+                        completion::UserContent::Image(completion::Image::Base64 {
+                            media_type,
+                            data,
+                        }) => Part {
+                            inline_data: Some(Blob {
+                                mime_type: media_type,
+                                data,
+                            }),
+                            ..Default::default()
+                        },
+                        completion::UserContent::Image(completion::Image::Url { url }) => Part {
+                            file_data: Some(FileData {
+                                mime_type: None,
+                                file_uri: url,
+                            }),
+                            ..Default::default()
+                        },
+                    })
+                    .collect(),
+                role: Some(Role::User),
+            }),
+            completion::Message::Assistant { content } => Some(Content {
+                parts: content
+                    .into_iter()
+                    .map(|part| match part {
+                        completion::AssistantContent::Text { text } => Part {
+                            text: Some(text),
+                            ..Default::default()
+                        },
+                        completion::AssistantContent::ToolCall(call) => {
+                            call_names.insert(call.id, call.name.clone());
+                            Part {
+                                function_call: Some(FunctionCall {
+                                    name: call.name,
+                                    args: match call.arguments {
+                                        Value::Object(args) => Some(args),
+                                        _ => None,
+                                    },
+                                }),
+                                ..Default::default()
+                            }
+                        }
+                    })
+                    .collect(),
+                role: Some(Role::Model),
+            }),
+            completion::Message::Tool { content } => Some(Content {
+                parts: content
+                    .into_iter()
+                    .map(|result| Part {
+                        function_response: Some(FunctionResponse {
+                            name: call_names.get(&result.id).cloned().unwrap_or(result.id),
+                            // The response must be an object: tool outputs that are not
+                            // JSON objects are wrapped in one
+                            response: Some(match serde_json::from_str::<Value>(&result.content) {
+                                Ok(Value::Object(response)) => response.into_iter().collect(),
+                                Ok(value) => HashMap::from([("result".to_string(), value)]),
+                                Err(_) => HashMap::from([(
+                                    "result".to_string(),
+                                    Value::String(result.content),
+                                )]),
+                            }),
+                        }),
+                        ..Default::default()
+                    })
+                    .collect(),
+                role: Some(Role::User),
+            }),
+        })
+        .collect();
+
+    let system_instruction = (!system.is_empty()).then(|| Content {
+        parts: vec![Part {
+            text: Some(system.join("\n\n")),
+            ..Default::default()
+        }],
+        role: None,
+    });
+
+    (system_instruction, contents)
+}
+
+/// Rewrites a JSON schema, as generated by `schemars`, into the subset of OpenAPI schemas
+/// Gemini accepts: references to definitions are inlined, optional values (a list of types
+/// including `null`, or `anyOf` a schema and `null`) become nullable schemas, and unsupported
+/// formats are dropped.
+fn openapi_schema(schema: &Value, definitions: &Value) -> Value {
+    let Some(object) = schema.as_object() else {
+        return schema.clone();
+    };
+    if let Some(name) = object
+        .get("$ref")
+        .and_then(Value::as_str)
+        .and_then(|reference| reference.strip_prefix("#/definitions/"))
+    {
+        return openapi_schema(&definitions[name], definitions);
+    }
+
+    let is_null = |schema: &Value| schema["type"] == "null";
+    let mut object = object.clone();
+    if let Some(Value::Array(alternatives)) = object.remove("anyOf") {
+        let (nulls, schemas): (Vec<_>, Vec<_>) = alternatives.iter().partition(|s| is_null(s));
+        if let [schema] = schemas.as_slice() {
+            let mut schema = openapi_schema(schema, definitions);
+            if !nulls.is_empty() {
+                schema["nullable"] = Value::Bool(true);
+            }
+            return schema;
+        }
+    }
+    if let Some(Value::Array(types)) = object.get("type").cloned() {
+        if types.iter().any(|t| t == "null") {
+            object.insert("nullable".to_string(), Value::Bool(true));
+        }
+        if let Some(r#type) = types.into_iter().find(|t| t != "null") {
+            object.insert("type".to_string(), r#type);
+        }
+    }
+    // `schemars` describes Rust integer widths with formats such as `uint8`, which Gemini rejects
+    if !matches!(
+        object.get("format").and_then(Value::as_str),
+        None | Some("int32" | "int64" | "float" | "double" | "enum" | "date-time")
+    ) {
+        object.remove("format");
+    }
+    if let Some(Value::Object(properties)) = object.get_mut("properties") {
+        for property in properties.values_mut() {
+            *property = openapi_schema(property, definitions);
+        }
+    }
+    if let Some(items) = object.get_mut("items") {
+        *items = openapi_schema(items, definitions);
+    }
+    Value::Object(object)
+}
+
 impl From<completion::ToolDefinition> for Tool {
     fn from(tool: completion::ToolDefinition) -> Self {
         Self {
@@ -157,32 +329,49 @@ impl TryFrom<GenerateContentResponse> for completion::CompletionResponse<Generat
 
     fn try_from(response: GenerateContentResponse) -> Result<Self, Self::Error> {
         match response.candidates.as_slice() {
-            [ContentCandidate { content, .. }, ..] => Ok(completion::CompletionResponse {
-                choice: match content.parts.first().unwrap() {
-                    Part {
-                        text: Some(text), ..
-                    } => completion::ModelChoice::Message(text.clone()),
-                    Part {
-                        function_call: Some(function_call),
-                        ..
-                    } => {
-                        let args_value = serde_json::Value::Object(
+            [ContentCandidate { content, .. }, ..] => {
+                let text = content
+                    .parts
+                    .iter()
+                    .filter_map(|part| part.text.as_deref())
+                    .collect::<Vec<_>>();
+                let text = (!text.is_empty()).then(|| text.concat());
+
+                // Gemini does not assign ids to function calls
+                let calls = content
+                    .parts
+                    .iter()
+                    .filter_map(|part| part.function_call.as_ref())
+                    .enumerate()
+                    .map(|(i, function_call)| completion::ToolCall {
+                        id: format!("{}_{}", function_call.name, i),
+                        name: function_call.name.clone(),
+                        arguments: serde_json::Value::Object(
                             function_call.args.clone().unwrap_or_default(),
-                        );
-                        completion::ModelChoice::ToolCall(
-                            function_call.name.clone(),
-                            "".to_owned(),
-                            args_value,
-                        )
-                    }
-                    _ => {
+                        ),
+                    })
+                    .collect::<Vec<_>>();
+
+                let choice = match (text, calls.is_empty()) {
+                    (text, false) => completion::ModelChoice::ToolCalls { text, calls },
+                    (Some(text), true) => completion::ModelChoice::Message(text),
+                    (None, true) => {
                         return Err(CompletionError::ResponseError(
-                            "Unsupported response by the model of type ".into(),
+                            "Response did not contain a message or function call".into(),
                         ))
                     }
-                },
-                raw_response: response,
-            }),
+                };
+
+                Ok(completion::CompletionResponse {
+                    choice,
+                    usage: response
+                        .usage_metadata
+                        .as_ref()
+                        .map(completion::Usage::from)
+                        .unwrap_or_default(),
+                    raw_response: response,
+                })
+            }
             _ => Err(CompletionError::ResponseError(
                 "No candidates found in response".into(),
             )),
@@ -200,7 +389,7 @@ pub mod gemini_api_types {
     use serde_json::{Map, Value};
 
     use crate::{
-        completion::CompletionError,
+        completion::{self, CompletionError},
         providers::gemini::gemini_api_types::{CodeExecutionResult, ExecutableCode},
     };
 
@@ -215,6 +404,7 @@ pub mod gemini_api_types {
     #[serde(rename_all = "camelCase")]
     pub struct GenerateContentResponse {
         /// Candidate responses from the model.
+        #[serde(default)]
         pub candidates: Vec<ContentCandidate>,
         /// Returns the prompt's feedback related to the content filters.
         pub prompt_feedback: Option<PromptFeedback>,
@@ -228,6 +418,7 @@ pub mod gemini_api_types {
     #[serde(rename_all = "camelCase")]
     pub struct ContentCandidate {
         /// Output only. Generated content returned from the model.
+        #[serde(default)]
         pub content: Content,
         /// Optional. Output only. The reason why the model stopped generating tokens.
         /// If empty, the model has not stopped generating tokens.
@@ -248,12 +439,14 @@ pub mod gemini_api_types {
         /// Output only. Index of the candidate in the list of response candidates.
         pub index: Option<i32>,
     }
-    #[derive(Debug, Deserialize, Serialize)]
+    #[derive(Debug, Default, Deserialize, Serialize)]
     pub struct Content {
         /// Ordered Parts that constitute a single message. Parts may have different MIME types.
+        #[serde(default)]
         pub parts: Vec<Part>,
         /// The producer of the content. Must be either 'user' or 'model'.
         /// Useful to set for multi-turn conversations, otherwise can be left blank or unset.
+        #[serde(skip_serializing_if = "Option::is_none")]
         pub role: Option<Role>,
     }
 
@@ -370,10 +563,28 @@ pub mod gemini_api_types {
     pub struct UsageMetadata {
         pub prompt_token_count: i32,
         pub cached_content_token_count: Option<i32>,
+        /// Missing from the first responses of a stream
+        #[serde(default)]
         pub candidates_token_count: i32,
+        /// Tokens spent by thinking models before answering, not included in the candidates
+        /// token count
+        #[serde(default)]
+        pub thoughts_token_count: Option<i32>,
         pub total_token_count: i32,
     }
 
+    impl From<&UsageMetadata> for completion::Usage {
+        fn from(usage: &UsageMetadata) -> Self {
+            let thoughts = usage.thoughts_token_count.unwrap_or_default() as u64;
+            completion::Usage {
+                prompt_tokens: usage.prompt_token_count as u64,
+                completion_tokens: usage.candidates_token_count as u64 + thoughts,
+                cached_tokens: usage.cached_content_token_count.unwrap_or_default() as u64,
+                reasoning_tokens: thoughts,
+            }
+        }
+    }
+
     impl std::fmt::Display for UsageMetadata {
         fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
             write!(
@@ -416,7 +627,7 @@ pub mod gemini_api_types {
         ProhibitedContent,
     }
 
-    #[derive(Debug, Deserialize)]
+    #[derive(Debug, Deserialize, Serialize)]
     #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
     pub enum FinishReason {
         /// Default value. This value is unused.
@@ -487,25 +698,31 @@ pub mod gemini_api_types {
     pub struct GenerationConfig {
         /// The set of character sequences (up to 5) that will stop output generation. If specified, the API will stop
         /// at the first appearance of a stop_sequence. The stop sequence will not be included as part of the response.
+        #[serde(skip_serializing_if = "Option::is_none")]
         pub stop_sequences: Option<Vec<String>>,
         /// MIME type of the generated candidate text. Supported MIME types are:
         ///     - text/plain:  (default) Text output
         ///     - application/json: JSON response in the response candidates.
         ///     - text/x.enum: ENUM as a string response in the response candidates.
         /// Refer to the docs for a list of all supported text MIME types
+        #[serde(skip_serializing_if = "Option::is_none")]
         pub response_mime_type: Option<String>,
         /// Output schema of the generated candidate text. Schemas must be a subset of the OpenAPI schema and can be
         /// objects, primitives or arrays. If set, a compatible responseMimeType must also  be set. Compatible MIME
         /// types: application/json: Schema for JSON response. Refer to the JSON text generation guide for more details.
+        #[serde(skip_serializing_if = "Option::is_none")]
         pub response_schema: Option<Schema>,
         /// Number of generated responses to return. Currently, this value can only be set to 1. If
         /// unset, this will default to 1.
+        #[serde(skip_serializing_if = "Option::is_none")]
         pub candidate_count: Option<i32>,
         /// The maximum number of tokens to include in a response candidate. Note: The default value varies by model, see
         /// the Model.output_token_limit attribute of the Model returned from the getModel function.
+        #[serde(skip_serializing_if = "Option::is_none")]
         pub max_output_tokens: Option<u64>,
         /// Controls the randomness of the output. Note: The default value varies by model, see the Model.temperature
         /// attribute of the Model returned from the getModel function. Values can range from [0.0, 2.0].
+        #[serde(skip_serializing_if = "Option::is_none")]
         pub temperature: Option<f64>,
         /// The maximum cumulative probability of tokens to consider when sampling. The model uses combined Top-k and
         /// Top-p (nucleus) sampling. Tokens are sorted based on their assigned probabilities so that only the most
@@ -513,18 +730,21 @@ pub mod gemini_api_types {
         /// Nucleus sampling limits the number of tokens based on the cumulative probability. Note: The default value
         /// varies by Model and is specified by theModel.top_p attribute returned from the getModel function. An empty
         /// topK attribute indicates that the model doesn't apply top-k sampling and doesn't allow setting topK on requests.
+        #[serde(skip_serializing_if = "Option::is_none")]
         pub top_p: Option<f64>,
         /// The maximum number of tokens to consider when sampling. Gemini models use Top-p (nucleus) sampling or a
         /// combination of Top-k and nucleus sampling. Top-k sampling considers the set of topK most probable tokens.
         /// Models running with nucleus sampling don't allow topK setting. Note: The default value varies by Model and is
         /// specified by theModel.top_p attribute returned from the getModel function. An empty topK attribute indicates
         /// that the model doesn't apply top-k sampling and doesn't allow setting topK on requests.
+        #[serde(skip_serializing_if = "Option::is_none")]
         pub top_k: Option<i32>,
         /// Presence penalty applied to the next token's logprobs if the token has already been seen in the response.
         /// This penalty is binary on/off and not dependent on the number of times the token is used (after the first).
         /// Use frequencyPenalty for a penalty that increases with each use. A positive penalty will discourage the use
         /// of tokens that have already been used in the response, increasing the vocabulary. A negative penalty will
         /// encourage the use of tokens that have already been used in the response, decreasing the vocabulary.
+        #[serde(skip_serializing_if = "Option::is_none")]
         pub presence_penalty: Option<f64>,
         /// Frequency penalty applied to the next token's logprobs, multiplied by the number of times each token has been
         /// seen in the response so far. A positive penalty will discourage the use of tokens that have already been
@@ -533,12 +753,18 @@ pub mod gemini_api_types {
         /// negative penalty will encourage the model to reuse tokens proportional to the number of times the token has
         /// been used. Small negative values will reduce the vocabulary of a response. Larger negative values will cause
         /// the model to  repeating a common token until it hits the maxOutputTokens limit: "...the the the the the...".
+        #[serde(skip_serializing_if = "Option::is_none")]
         pub frequency_penalty: Option<f64>,
         /// If true, export the logprobs results in response.
+        #[serde(skip_serializing_if = "Option::is_none")]
         pub response_logprobs: Option<bool>,
         /// Only valid if responseLogprobs=True. This sets the number of top logprobs to return at each decoding step in
         /// [Candidate.logprobs_result].
+        #[serde(skip_serializing_if = "Option::is_none")]
         pub logprobs: Option<i32>,
+        /// Seed used in decoding. If not set, the request uses a randomly generated seed.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub seed: Option<u64>,
     }
 
     impl Default for GenerationConfig {
@@ -556,6 +782,7 @@ pub mod gemini_api_types {
                 frequency_penalty: None,
                 response_logprobs: None,
                 logprobs: None,
+                seed: None,
             }
         }
     }
@@ -676,10 +903,50 @@ pub mod gemini_api_types {
         pub parameters: Option<Vec<Schema>>,
     }
 
+    /// Configuration of the tools of a request. From [Gemini API Reference](https://ai.google.dev/api/caching#ToolConfig)
     #[derive(Debug, Serialize)]
     #[serde(rename_all = "camelCase")]
     pub struct ToolConfig {
-        pub schema: Option<Schema>,
+        pub function_calling_config: Option<FunctionCallingConfig>,
+    }
+
+    #[derive(Debug, Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct FunctionCallingConfig {
+        pub mode: FunctionCallingMode,
+        /// With [FunctionCallingMode::Any], the functions the model may call
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub allowed_function_names: Option<Vec<String>>,
+    }
+
+    #[derive(Debug, Serialize)]
+    #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+    pub enum FunctionCallingMode {
+        /// The model decides whether to call a function
+        Auto,
+        /// The model always calls a function
+        Any,
+        /// The model does not call any function
+        None,
+    }
+
+    impl From<completion::ToolChoice> for ToolConfig {
+        fn from(tool_choice: completion::ToolChoice) -> Self {
+            let (mode, allowed_function_names) = match tool_choice {
+                completion::ToolChoice::Auto => (FunctionCallingMode::Auto, None),
+                completion::ToolChoice::None => (FunctionCallingMode::None, None),
+                completion::ToolChoice::Required => (FunctionCallingMode::Any, None),
+                completion::ToolChoice::Specific(name) => {
+                    (FunctionCallingMode::Any, Some(vec![name]))
+                }
+            };
+            Self {
+                function_calling_config: Some(FunctionCallingConfig {
+                    mode,
+                    allowed_function_names,
+                }),
+            }
+        }
     }
 
     #[derive(Debug, Serialize)]
@@ -704,3 +971,141 @@ pub mod gemini_api_types {
         Off,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::completion::{
+        tests::{lookup_tool, tool_chat},
+        CompletionModel as _,
+    };
+    use serde_json::json;
+
+    #[test]
+    fn test_chat() {
+        let (system_instruction, contents) = chat(None, tool_chat());
+
+        assert_eq!(
+            serde_json::to_value(system_instruction).unwrap(),
+            serde_json::to_value(Content {
+                parts: vec![Part {
+                    text: Some("Be brief.".into()),
+                    ..Default::default()
+                }],
+                role: None,
+            })
+            .unwrap()
+        );
+        let contents = serde_json::to_value(contents).unwrap();
+        assert_eq!(contents.as_array().unwrap().len(), 3);
+        assert_eq!(
+            contents[0]["parts"][1]["fileData"]["fileUri"],
+            "https://example.com/a.png"
+        );
+        assert_eq!(
+            contents[0]["parts"][2]["inlineData"]["mimeType"],
+            "image/png"
+        );
+        assert_eq!(contents[1]["role"], "model");
+        assert_eq!(
+            contents[1]["parts"][1]["functionCall"],
+            json!({ "name": "lookup", "args": { "image": 1 } })
+        );
+
+        // Function responses are named after the call they answer, and outputs that are not
+        // objects are wrapped in one
+        assert_eq!(contents[2]["role"], "user");
+        assert_eq!(
+            contents[2]["parts"][0]["functionResponse"],
+            json!({ "name": "lookup", "response": { "result": "A cat" } })
+        );
+        assert_eq!(
+            contents[2]["parts"][1]["functionResponse"],
+            json!({ "name": "lookup", "response": { "animal": "dog" } })
+        );
+    }
+
+    #[test]
+    fn test_generation_config() {
+        let model = CompletionModel::new(Client::new("key"), GEMINI_1_5_FLASH);
+
+        // Parameters that are not set are left out, for Gemini's defaults to apply
+        let request = model
+            .create_request(model.completion_request("Hi").build())
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(request.generation_config).unwrap(),
+            json!({})
+        );
+
+        let request = model
+            .create_request(
+                model
+                    .completion_request("Hi")
+                    .temperature(0.5)
+                    .max_tokens(100)
+                    .stop_sequence("END")
+                    .top_p(0.9)
+                    .seed(42)
+                    .frequency_penalty(0.1)
+                    .presence_penalty(0.2)
+                    .additional_params(json!({ "topK": 40 }))
+                    .build(),
+            )
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(request.generation_config).unwrap(),
+            json!({
+                "temperature": 0.5,
+                "maxOutputTokens": 100,
+                "stopSequences": ["END"],
+                "topP": 0.9,
+                "topK": 40,
+                "seed": 42,
+                "frequencyPenalty": 0.1,
+                "presencePenalty": 0.2,
+            })
+        );
+    }
+
+    #[test]
+    fn test_tool_config() {
+        let model = CompletionModel::new(Client::new("key"), GEMINI_1_5_FLASH);
+        let tool_config = |choice: Option<completion::ToolChoice>| {
+            let request = model
+                .create_request(
+                    model
+                        .completion_request("Hi")
+                        .tool(lookup_tool())
+                        .tool_choice_opt(choice)
+                        .build(),
+                )
+                .unwrap();
+            serde_json::to_value(request.tool_config).unwrap()
+        };
+
+        // Left to Gemini's default unless asked for
+        assert_eq!(tool_config(None), json!(null));
+        assert_eq!(
+            tool_config(Some(completion::ToolChoice::Auto)),
+            json!({ "functionCallingConfig": { "mode": "AUTO" } })
+        );
+        assert_eq!(
+            tool_config(Some(completion::ToolChoice::None)),
+            json!({ "functionCallingConfig": { "mode": "NONE" } })
+        );
+        assert_eq!(
+            tool_config(Some(completion::ToolChoice::Required)),
+            json!({ "functionCallingConfig": { "mode": "ANY" } })
+        );
+        assert_eq!(
+            tool_config(Some(completion::ToolChoice::Specific("lookup".into()))),
+            json!({
+                "functionCallingConfig": {
+                    "mode": "ANY",
+                    "allowedFunctionNames": ["lookup"],
+                }
+            })
+        );
+    }
+}
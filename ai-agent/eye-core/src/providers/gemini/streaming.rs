@@ -0,0 +1,164 @@
+// ================================================================
+//! Google Gemini Streaming Integration
+//! From [Gemini API Reference](https://ai.google.dev/api/generate-content#method:-models.streamgeneratecontent)
+// ================================================================
+
+use async_stream::stream;
+use bytes::Bytes;
+use futures::Stream;
+use serde_json::Value;
+
+use super::completion::{gemini_api_types::GenerateContentResponse, CompletionModel};
+use crate::{
+    completion::{self, CompletionError, CompletionRequest},
+    streaming::{
+        sse_events, FinalResponse, StreamingChoice, StreamingCompletionModel, StreamingResult,
+    },
+};
+
+impl StreamingCompletionModel for CompletionModel {
+    async fn stream(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<StreamingResult, CompletionError> {
+        let request = self.create_request(completion_request)?;
+
+        let response = self
+            .client
+            .post(&format!(
+                "/v1beta/models/{}:streamGenerateContent",
+                self.model
+            ))
+            .query(&[("alt", "sse")])
+            .json(&request)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(CompletionError::ProviderError(response.text().await?));
+        }
+
+        Ok(Box::pin(streaming_choices(response.bytes_stream())))
+    }
+}
+
+/// Reads Gemini's stream of partial responses. Text is yielded as it arrives, and function calls,
+/// which Gemini sends whole, as soon as they are received. Every response carries the usage so
+/// far, and the last one is yielded with the final response at the end of the stream.
+fn streaming_choices<E>(
+    bytes: impl Stream<Item = Result<Bytes, E>>,
+) -> impl Stream<Item = Result<StreamingChoice, CompletionError>>
+where
+    CompletionError: From<E>,
+{
+    stream! {
+        let mut final_response = FinalResponse::default();
+        // Gemini does not assign ids to function calls
+        let mut calls = 0;
+        for await event in sse_events(bytes) {
+            let event = match event {
+                Ok(event) => event,
+                Err(error) => {
+                    yield Err(error);
+                    return;
+                }
+            };
+            let response = match serde_json::from_str::<GenerateContentResponse>(&event.data) {
+                Ok(response) => response,
+                Err(error) => {
+                    yield Err(CompletionError::JsonError(error));
+                    return;
+                }
+            };
+
+            if let Some(usage_metadata) = &response.usage_metadata {
+                final_response.usage = completion::Usage::from(usage_metadata);
+            }
+            let Some(candidate) = response.candidates.into_iter().next() else {
+                continue;
+            };
+            if let Some(finish_reason) = &candidate.finish_reason {
+                // The reason as Gemini names it, e.g. `STOP`
+                final_response.finish_reason = serde_json::to_value(finish_reason)
+                    .ok()
+                    .and_then(|reason| reason.as_str().map(str::to_owned));
+            }
+            for part in candidate.content.parts {
+                if let Some(text) = part.text.filter(|text| !text.is_empty()) {
+                    yield Ok(final_response.add(StreamingChoice::Message(text)));
+                }
+                if let Some(function_call) = part.function_call {
+                    let id = format!("{}_{}", function_call.name, calls);
+                    calls += 1;
+                    yield Ok(final_response.add(StreamingChoice::ToolCall(
+                        function_call.name,
+                        id,
+                        Value::Object(function_call.args.unwrap_or_default()),
+                    )));
+                }
+            }
+        }
+
+        yield Ok(StreamingChoice::Final(final_response));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::streaming::tests::chunked;
+    use futures::StreamExt;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_streaming_choices() {
+        let body = include_str!("../../../tests/data/gemini_streaming.sse");
+
+        for size in [1, 9, body.len()] {
+            let choices = streaming_choices(chunked(body, size))
+                .map(Result::unwrap)
+                .collect::<Vec<_>>()
+                .await;
+
+            assert_eq!(
+                choices,
+                vec![
+                    StreamingChoice::Message("Let me check the weather in Z".into()),
+                    StreamingChoice::Message("ürich.".into()),
+                    StreamingChoice::ToolCall(
+                        "get_weather".into(),
+                        "get_weather_0".into(),
+                        json!({ "city": "Zürich" }),
+                    ),
+                    StreamingChoice::ToolCall(
+                        "get_weather".into(),
+                        "get_weather_1".into(),
+                        json!({ "city": "Bern" }),
+                    ),
+                    StreamingChoice::Final(FinalResponse {
+                        message: "Let me check the weather in Zürich.".into(),
+                        tool_calls: vec![
+                            completion::ToolCall {
+                                id: "get_weather_0".into(),
+                                name: "get_weather".into(),
+                                arguments: json!({ "city": "Zürich" }),
+                            },
+                            completion::ToolCall {
+                                id: "get_weather_1".into(),
+                                name: "get_weather".into(),
+                                arguments: json!({ "city": "Bern" }),
+                            },
+                        ],
+                        usage: completion::Usage {
+                            prompt_tokens: 40,
+                            completion_tokens: 24,
+                            ..Default::default()
+                        },
+                        finish_reason: Some("STOP".into()),
+                    }),
+                ],
+                "chunks of {size} bytes"
+            );
+        }
+    }
+}
@@ -11,9 +11,11 @@
 
 use crate::{
     agent::AgentBuilder,
-    completion::{self, CompletionError, CompletionRequest},
+    completion::{self, CompletionError, CompletionRequest, SamplingParam},
     extractor::ExtractorBuilder,
     json_utils,
+    providers::openai,
+    streaming::{StreamingCompletionModel, StreamingResult},
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -139,6 +141,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
     type Error = CompletionError;
 
     fn try_from(value: CompletionResponse) -> std::prelude::v1::Result<Self, Self::Error> {
+        let usage = completion::Usage::from(&value.usage);
         match value.choices.as_slice() {
             [Choice {
                 message:
@@ -149,6 +152,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
                 ..
             }, ..] => Ok(completion::CompletionResponse {
                 choice: completion::ModelChoice::Message(content.to_string()),
+                usage,
                 raw_response: value,
             }),
             _ => Err(CompletionError::ResponseError(
@@ -187,6 +191,16 @@ impl std::fmt::Display for Usage {
     }
 }
 
+impl From<&Usage> for completion::Usage {
+    fn from(usage: &Usage) -> Self {
+        completion::Usage {
+            prompt_tokens: usage.prompt_tokens as u64,
+            completion_tokens: usage.total_tokens.saturating_sub(usage.prompt_tokens) as u64,
+            ..Default::default()
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct CompletionModel {
     client: Client,
@@ -202,46 +216,58 @@ impl CompletionModel {
     }
 }
 
-impl completion::CompletionModel for CompletionModel {
-    type Response = CompletionResponse;
-
-    #[cfg_attr(feature = "worker", worker::send)]
-    async fn completion(
+impl CompletionModel {
+    /// Builds the chat completion request body, which streaming requests extend
+    fn create_completion_request(
         &self,
         mut completion_request: CompletionRequest,
-    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+    ) -> Result<serde_json::Value, CompletionError> {
+        completion_request.check_unsupported(
+            "Moonshot",
+            &[SamplingParam::Seed, SamplingParam::ResponseFormat],
+        )?;
+
+        let sampling_params = openai::sampling_params(&completion_request);
+
         let mut full_history = if let Some(preamble) = &completion_request.preamble {
-            vec![completion::Message {
-                role: "system".into(),
-                content: preamble.clone(),
-            }]
+            vec![completion::Message::system(preamble.clone())]
         } else {
             vec![]
         };
 
         full_history.append(&mut completion_request.chat_history);
 
-        full_history.push(completion::Message {
-            role: "user".into(),
-            content: completion_request.prompt_with_context(),
-        });
+        full_history.push(completion_request.prompt_with_context());
 
         let request = json!({
             "model": self.model,
-            "messages": full_history,
-            "temperature": completion_request.temperature,
+            "messages": openai::chat_messages(full_history),
         });
 
+        let request = json_utils::merge(request, sampling_params);
+
+        Ok(if let Some(params) = completion_request.additional_params {
+            json_utils::merge(request, params)
+        } else {
+            request
+        })
+    }
+}
+
+impl completion::CompletionModel for CompletionModel {
+    type Response = CompletionResponse;
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn completion(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+        let request = self.create_completion_request(completion_request)?;
+
         let response = self
             .client
             .post("/chat/completions")
-            .json(
-                &if let Some(params) = completion_request.additional_params {
-                    json_utils::merge(request, params)
-                } else {
-                    request
-                },
-            )
+            .json(&request)
             .send()
             .await?;
 
@@ -261,3 +287,15 @@ impl completion::CompletionModel for CompletionModel {
         }
     }
 }
+
+impl StreamingCompletionModel for CompletionModel {
+    async fn stream(&self, request: CompletionRequest) -> Result<StreamingResult, CompletionError> {
+        let mut request = self.create_completion_request(request)?;
+        json_utils::merge_inplace(&mut request, json!({ "stream": true }));
+
+        openai::send_streaming_request::<Usage>(
+            self.client.post("/chat/completions").json(&request),
+        )
+        .await
+    }
+}
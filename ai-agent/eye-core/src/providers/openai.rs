@@ -13,11 +13,19 @@ use crate::{
     completion::{self, CompletionError, CompletionRequest},
     embeddings::{self, EmbeddingError, EmbeddingsBuilder},
     extractor::ExtractorBuilder,
-    json_utils, Embed,
+    json_utils,
+    streaming::{
+        sse_events, FinalResponse, StreamingChoice, StreamingCompletionModel, StreamingResult,
+    },
+    Embed,
 };
+use async_stream::stream;
+use bytes::Bytes;
+use futures::Stream;
 use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::json;
+use std::collections::BTreeMap;
 
 // ================================================================
 // Main OpenAI Client
@@ -223,6 +231,10 @@ pub struct EmbeddingData {
 pub struct Usage {
     pub prompt_tokens: usize,
     pub total_tokens: usize,
+    #[serde(default)]
+    pub prompt_tokens_details: Option<PromptTokensDetails>,
+    #[serde(default)]
+    pub completion_tokens_details: Option<CompletionTokensDetails>,
 }
 
 impl std::fmt::Display for Usage {
@@ -235,6 +247,35 @@ impl std::fmt::Display for Usage {
     }
 }
 
+impl From<&Usage> for completion::Usage {
+    fn from(usage: &Usage) -> Self {
+        completion::Usage {
+            prompt_tokens: usage.prompt_tokens as u64,
+            completion_tokens: usage.total_tokens.saturating_sub(usage.prompt_tokens) as u64,
+            cached_tokens: usage
+                .prompt_tokens_details
+                .as_ref()
+                .map_or(0, |details| details.cached_tokens as u64),
+            reasoning_tokens: usage
+                .completion_tokens_details
+                .as_ref()
+                .map_or(0, |details| details.reasoning_tokens as u64),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct PromptTokensDetails {
+    #[serde(default)]
+    pub cached_tokens: usize,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct CompletionTokensDetails {
+    #[serde(default)]
+    pub reasoning_tokens: usize,
+}
+
 #[derive(Clone)]
 pub struct EmbeddingModel {
     client: Client,
@@ -377,10 +418,16 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
     type Error = CompletionError;
 
     fn try_from(value: CompletionResponse) -> Result<Self, Self::Error> {
+        let usage = value
+            .usage
+            .as_ref()
+            .map(completion::Usage::from)
+            .unwrap_or_default();
         match value.choices.as_slice() {
             [Choice {
                 message:
                     Message {
+                        content,
                         tool_calls: Some(calls),
                         ..
                     },
@@ -388,14 +435,23 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
             }, ..]
                 if !calls.is_empty() =>
             {
-                let call = calls.first().unwrap();
+                let calls = calls
+                    .iter()
+                    .map(|call| {
+                        Ok(completion::ToolCall {
+                            id: call.id.clone(),
+                            name: call.function.name.clone(),
+                            arguments: serde_json::from_str(&call.function.arguments)?,
+                        })
+                    })
+                    .collect::<Result<_, CompletionError>>()?;
 
                 Ok(completion::CompletionResponse {
-                    choice: completion::ModelChoice::ToolCall(
-                        call.function.name.clone(),
-                        "".to_owned(),
-                        serde_json::from_str(&call.function.arguments)?,
-                    ),
+                    choice: completion::ModelChoice::ToolCalls {
+                        text: content.clone(),
+                        calls,
+                    },
+                    usage,
                     raw_response: value,
                 })
             }
@@ -408,6 +464,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
                 ..
             }, ..] => Ok(completion::CompletionResponse {
                 choice: completion::ModelChoice::Message(content.to_string()),
+                usage,
                 raw_response: value,
             }),
             _ => Err(CompletionError::ResponseError(
@@ -460,6 +517,133 @@ pub struct Function {
     pub arguments: String,
 }
 
+/// The sampling parameters set on the request, as OpenAI chat completion fields, which
+/// OpenAI-compatible providers share. Parameters that are not set are left out.
+pub fn sampling_params(request: &CompletionRequest) -> serde_json::Value {
+    let params = [
+        ("temperature", request.temperature.map(|value| json!(value))),
+        ("max_tokens", request.max_tokens.map(|value| json!(value))),
+        (
+            "stop",
+            (!request.stop.is_empty()).then(|| json!(request.stop)),
+        ),
+        ("top_p", request.top_p.map(|value| json!(value))),
+        ("seed", request.seed.map(|value| json!(value))),
+        (
+            "frequency_penalty",
+            request.frequency_penalty.map(|value| json!(value)),
+        ),
+        (
+            "presence_penalty",
+            request.presence_penalty.map(|value| json!(value)),
+        ),
+    ];
+
+    serde_json::Value::Object(
+        params
+            .into_iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value?)))
+            .collect(),
+    )
+}
+
+/// Converts a tool choice to OpenAI's format, which OpenAI-compatible providers share
+pub fn tool_choice(tool_choice: &completion::ToolChoice) -> serde_json::Value {
+    match tool_choice {
+        completion::ToolChoice::Auto => json!("auto"),
+        completion::ToolChoice::None => json!("none"),
+        completion::ToolChoice::Required => json!("required"),
+        completion::ToolChoice::Specific(name) => json!({
+            "type": "function",
+            "function": { "name": name },
+        }),
+    }
+}
+
+/// Converts a response format to OpenAI's `response_format` field. Schemas are not sent in
+/// strict mode, which would require every property to be required.
+pub fn response_format(response_format: &completion::ResponseFormat) -> serde_json::Value {
+    match response_format {
+        completion::ResponseFormat::Json => json!({ "type": "json_object" }),
+        completion::ResponseFormat::JsonSchema { name, schema } => json!({
+            "type": "json_schema",
+            "json_schema": { "name": name, "schema": schema },
+        }),
+    }
+}
+
+/// Converts chat messages to OpenAI's chat format, which OpenAI-compatible providers share.
+/// A tool message holding several results becomes one `tool` message per result.
+pub fn chat_messages(messages: Vec<completion::Message>) -> Vec<serde_json::Value> {
+    messages
+        .into_iter()
+        .flat_map(|message| match message {
+            completion::Message::System { content } => {
+                vec![json!({ "role": "system", "content": content })]
+            }
+            completion::Message::User { content } => {
+                let content = match content.as_slice() {
+                    [completion::UserContent::Text { text }] => json!(text),
+                    parts => json!(parts
+                        .iter()
+                        .map(|part| match part {
+                            completion::UserContent::Text { text } => {
+                                json!({ "type": "text", "text": text })
+                            }
+                            completion::UserContent::Image(completion::Image::Url { url }) => {
+                                json!({ "type": "image_url", "image_url": { "url": url } })
+                            }
+                            completion::UserContent::Image(completion::Image::Base64 {
+                                media_type,
+                                data,
+                            }) => json!({
+                                "type": "image_url",
+                                "image_url": { "url": format!("data:{media_type};base64,{data}") }
+                            }),
+                        })
+                        .collect::<Vec<_>>()),
+                };
+                vec![json!({ "role": "user", "content": content })]
+            }
+            completion::Message::Assistant { .. } => {
+                let text = message.text();
+                let tool_calls = message
+                    .tool_calls()
+                    .into_iter()
+                    .map(|call| {
+                        json!({
+                            "id": call.id,
+                            "type": "function",
+                            "function": {
+                                "name": call.name,
+                                "arguments": call.arguments.to_string(),
+                            },
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                let mut message = json!({
+                    "role": "assistant",
+                    "content": if text.is_empty() { None } else { Some(text) },
+                });
+                if !tool_calls.is_empty() {
+                    message["tool_calls"] = json!(tool_calls);
+                }
+                vec![message]
+            }
+            completion::Message::Tool { content } => content
+                .into_iter()
+                .map(|result| {
+                    json!({
+                        "role": "tool",
+                        "tool_call_id": result.id,
+                        "content": result.content,
+                    })
+                })
+                .collect(),
+        })
+        .collect()
+}
+
 #[derive(Clone)]
 pub struct CompletionModel {
     client: Client,
@@ -476,20 +660,17 @@ impl CompletionModel {
     }
 }
 
-impl completion::CompletionModel for CompletionModel {
-    type Response = CompletionResponse;
-
-    #[cfg_attr(feature = "worker", worker::send)]
-    async fn completion(
+impl CompletionModel {
+    /// Builds the chat completion request body, which streaming requests extend
+    fn create_completion_request(
         &self,
         mut completion_request: CompletionRequest,
-    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+    ) -> serde_json::Value {
+        let sampling_params = sampling_params(&completion_request);
+
         // Add preamble to chat history (if available)
         let mut full_history = if let Some(preamble) = &completion_request.preamble {
-            vec![completion::Message {
-                role: "system".into(),
-                content: preamble.clone(),
-            }]
+            vec![completion::Message::system(preamble.clone())]
         } else {
             vec![]
         };
@@ -498,40 +679,52 @@ impl completion::CompletionModel for CompletionModel {
         full_history.append(&mut completion_request.chat_history);
 
         // Add context documents to chat history
-        let prompt_with_context = completion_request.prompt_with_context();
-
-        // Add context documents to chat history
-        full_history.push(completion::Message {
-            role: "user".into(),
-            content: prompt_with_context,
-        });
+        full_history.push(completion_request.prompt_with_context());
 
         let request = if completion_request.tools.is_empty() {
             json!({
                 "model": self.model,
-                "messages": full_history,
-                "temperature": completion_request.temperature,
+                "messages": chat_messages(full_history),
             })
         } else {
             json!({
                 "model": self.model,
-                "messages": full_history,
-                "temperature": completion_request.temperature,
+                "messages": chat_messages(full_history),
                 "tools": completion_request.tools.into_iter().map(ToolDefinition::from).collect::<Vec<_>>(),
-                "tool_choice": "auto",
+                "tool_choice": tool_choice(&completion_request.tool_choice.unwrap_or_default()),
             })
         };
 
+        let mut request = json_utils::merge(request, sampling_params);
+        if let Some(format) = &completion_request.response_format {
+            json_utils::merge_inplace(
+                &mut request,
+                json!({ "response_format": response_format(format) }),
+            );
+        }
+
+        if let Some(params) = completion_request.additional_params {
+            json_utils::merge(request, params)
+        } else {
+            request
+        }
+    }
+}
+
+impl completion::CompletionModel for CompletionModel {
+    type Response = CompletionResponse;
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn completion(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+        let request = self.create_completion_request(completion_request);
+
         let response = self
             .client
             .post("/chat/completions")
-            .json(
-                &if let Some(params) = completion_request.additional_params {
-                    json_utils::merge(request, params)
-                } else {
-                    request
-                },
-            )
+            .json(&request)
             .send()
             .await?;
 
@@ -551,3 +744,425 @@ impl completion::CompletionModel for CompletionModel {
         }
     }
 }
+
+// ================================================================
+// OpenAI Streaming API
+// ================================================================
+/// A chunk of a streaming chat completion, generic over the usage reported by
+/// OpenAI-compatible providers
+#[derive(Debug, Deserialize)]
+pub struct StreamingCompletionChunk<U> {
+    #[serde(default)]
+    pub choices: Vec<StreamingChoiceChunk>,
+    pub usage: Option<U>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StreamingChoiceChunk {
+    #[serde(default)]
+    pub delta: StreamingDelta,
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct StreamingDelta {
+    pub content: Option<String>,
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCallDelta>,
+}
+
+/// A fragment of a tool call: the id and name come in the first fragment of the call, and
+/// the arguments are split across fragments
+#[derive(Debug, Deserialize)]
+pub struct ToolCallDelta {
+    pub index: usize,
+    pub id: Option<String>,
+    pub function: Option<FunctionDelta>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FunctionDelta {
+    pub name: Option<String>,
+    pub arguments: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamingError {
+    error: ApiErrorResponse,
+}
+
+#[derive(Default)]
+struct ToolCallState {
+    id: String,
+    name: String,
+    arguments: String,
+}
+
+/// Reads a streaming chat completion body from an OpenAI-compatible provider, which reports its
+/// usage as `U`. Text is yielded as it arrives. The fragments of each tool call are reassembled,
+/// and the calls are yielded in order once the model finishes its turn. The final response, with
+/// the usage that providers send in the last chunk (if at all), is yielded at the end of the
+/// stream.
+pub fn streaming_choices<U, E>(
+    bytes: impl Stream<Item = Result<Bytes, E>>,
+) -> impl Stream<Item = Result<StreamingChoice, CompletionError>>
+where
+    U: DeserializeOwned,
+    completion::Usage: for<'a> From<&'a U>,
+    CompletionError: From<E>,
+{
+    fn finish(
+        calls: &mut BTreeMap<usize, ToolCallState>,
+    ) -> Vec<Result<StreamingChoice, CompletionError>> {
+        std::mem::take(calls)
+            .into_iter()
+            .map(|(index, call)| {
+                let arguments = if call.arguments.trim().is_empty() {
+                    serde_json::Value::Object(Default::default())
+                } else {
+                    serde_json::from_str(&call.arguments)?
+                };
+                let id = if call.id.is_empty() {
+                    format!("{}_{}", call.name, index)
+                } else {
+                    call.id
+                };
+                Ok(StreamingChoice::ToolCall(call.name, id, arguments))
+            })
+            .collect()
+    }
+
+    stream! {
+        let mut calls = BTreeMap::new();
+        let mut response = FinalResponse::default();
+        for await event in sse_events(bytes) {
+            let event = match event {
+                Ok(event) => event,
+                Err(error) => {
+                    yield Err(error);
+                    return;
+                }
+            };
+            if event.data == "[DONE]" {
+                break;
+            }
+            if let Ok(StreamingError { error }) = serde_json::from_str(&event.data) {
+                yield Err(CompletionError::ProviderError(error.message));
+                return;
+            }
+            let chunk = match serde_json::from_str::<StreamingCompletionChunk<U>>(&event.data) {
+                Ok(chunk) => chunk,
+                Err(error) => {
+                    yield Err(CompletionError::JsonError(error));
+                    return;
+                }
+            };
+
+            if let Some(usage) = &chunk.usage {
+                response.usage = completion::Usage::from(usage);
+            }
+            for choice in chunk.choices {
+                if let Some(text) = choice.delta.content.filter(|text| !text.is_empty()) {
+                    yield Ok(response.add(StreamingChoice::Message(text)));
+                }
+                for delta in choice.delta.tool_calls {
+                    let call: &mut ToolCallState = calls.entry(delta.index).or_default();
+                    if let Some(id) = delta.id.filter(|id| !id.is_empty()) {
+                        call.id = id;
+                    }
+                    if let Some(function) = delta.function {
+                        if let Some(name) = function.name.filter(|name| !name.is_empty()) {
+                            call.name = name;
+                        }
+                        if let Some(arguments) = function.arguments {
+                            call.arguments.push_str(&arguments);
+                        }
+                    }
+                }
+                if choice.finish_reason.is_some() {
+                    response.finish_reason = choice.finish_reason;
+                    for call in finish(&mut calls) {
+                        yield call.map(|call| response.add(call));
+                    }
+                }
+            }
+        }
+
+        // Some providers end the stream without a finish reason
+        for call in finish(&mut calls) {
+            yield call.map(|call| response.add(call));
+        }
+        yield Ok(StreamingChoice::Final(response));
+    }
+}
+
+/// Sends a streaming chat completion request to an OpenAI-compatible provider, which reports
+/// its usage as `U`, and streams the response
+pub async fn send_streaming_request<U>(
+    request: reqwest::RequestBuilder,
+) -> Result<StreamingResult, CompletionError>
+where
+    U: DeserializeOwned + Send + 'static,
+    completion::Usage: for<'a> From<&'a U>,
+{
+    let response = request.send().await?;
+    if !response.status().is_success() {
+        return Err(CompletionError::ProviderError(response.text().await?));
+    }
+    Ok(Box::pin(streaming_choices::<U, _>(response.bytes_stream())))
+}
+
+impl StreamingCompletionModel for CompletionModel {
+    async fn stream(&self, request: CompletionRequest) -> Result<StreamingResult, CompletionError> {
+        let mut request = self.create_completion_request(request);
+        json_utils::merge_inplace(
+            &mut request,
+            json!({ "stream": true, "stream_options": { "include_usage": true } }),
+        );
+
+        send_streaming_request::<Usage>(self.client.post("/chat/completions").json(&request)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::completion::{
+        tests::{lookup_tool, tool_chat},
+        CompletionModel as _,
+    };
+    use crate::streaming::tests::chunked;
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn test_streaming_reassembles_tool_calls() {
+        let body = include_str!("../../tests/data/openai_streaming_tool_calls.sse");
+
+        // Chunk sizes that split the body at different places, including inside characters
+        for size in [1, 7, 64, body.len()] {
+            let choices = streaming_choices::<Usage, _>(chunked(body, size))
+                .map(Result::unwrap)
+                .collect::<Vec<_>>()
+                .await;
+
+            let calls = vec![
+                completion::ToolCall {
+                    id: "call_weather".into(),
+                    name: "get_weather".into(),
+                    arguments: json!({ "city": "Zürich", "unit": "°C" }),
+                },
+                completion::ToolCall {
+                    id: "call_time".into(),
+                    name: "get_time".into(),
+                    arguments: json!({ "timezone": "Europe/Zurich" }),
+                },
+            ];
+            assert_eq!(
+                choices,
+                vec![
+                    StreamingChoice::ToolCall(
+                        "get_weather".into(),
+                        "call_weather".into(),
+                        json!({ "city": "Zürich", "unit": "°C" }),
+                    ),
+                    StreamingChoice::ToolCall(
+                        "get_time".into(),
+                        "call_time".into(),
+                        json!({ "timezone": "Europe/Zurich" }),
+                    ),
+                    StreamingChoice::Final(FinalResponse {
+                        message: String::new(),
+                        tool_calls: calls,
+                        usage: completion::Usage {
+                            prompt_tokens: 82,
+                            completion_tokens: 41,
+                            ..Default::default()
+                        },
+                        finish_reason: Some("tool_calls".into()),
+                    }),
+                ],
+                "chunks of {size} bytes"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_streaming_text() {
+        let body = include_str!("../../tests/data/openai_streaming_text.sse");
+
+        let choices = streaming_choices::<Usage, _>(chunked(body, 5))
+            .map(Result::unwrap)
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(
+            choices,
+            vec![
+                StreamingChoice::Message("Grüezi".into()),
+                StreamingChoice::Message(" mitenand!".into()),
+                StreamingChoice::Final(FinalResponse {
+                    message: "Grüezi mitenand!".into(),
+                    tool_calls: vec![],
+                    usage: completion::Usage {
+                        prompt_tokens: 12,
+                        completion_tokens: 5,
+                        ..Default::default()
+                    },
+                    finish_reason: Some("stop".into()),
+                }),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_streaming_error_event() {
+        let body = "data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hi\"}}]}\n\n\
+            data: {\"error\":{\"message\":\"The server had an error\"}}\n\n";
+
+        let choices = streaming_choices::<Usage, _>(chunked(body, 16))
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(choices.len(), 2);
+        assert_eq!(
+            choices[0].as_ref().unwrap(),
+            &StreamingChoice::Message("Hi".into())
+        );
+        assert!(matches!(
+            &choices[1],
+            Err(CompletionError::ProviderError(message)) if message == "The server had an error"
+        ));
+    }
+
+    #[test]
+    fn test_chat_messages() {
+        assert_eq!(
+            chat_messages(tool_chat()),
+            vec![
+                json!({ "role": "system", "content": "Be brief." }),
+                json!({
+                    "role": "user",
+                    "content": [
+                        { "type": "text", "text": "What is on these?" },
+                        { "type": "image_url", "image_url": { "url": "https://example.com/a.png" } },
+                        {
+                            "type": "image_url",
+                            "image_url": { "url": "data:image/png;base64,iVBORw0KGgo=" }
+                        },
+                    ],
+                }),
+                json!({
+                    "role": "assistant",
+                    "content": "Looking them up",
+                    "tool_calls": [
+                        {
+                            "id": "call_1",
+                            "type": "function",
+                            "function": { "name": "lookup", "arguments": "{\"image\":1}" },
+                        },
+                        {
+                            "id": "call_2",
+                            "type": "function",
+                            "function": { "name": "lookup", "arguments": "{\"image\":2}" },
+                        },
+                    ],
+                }),
+                // One message per result, each with the id of its call
+                json!({ "role": "tool", "tool_call_id": "call_1", "content": "A cat" }),
+                json!({ "role": "tool", "tool_call_id": "call_2", "content": "{\"animal\":\"dog\"}" }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_chat_messages_text_only() {
+        // A single text part is sent as a plain string, and an assistant message without text
+        // has a null content
+        assert_eq!(
+            chat_messages(vec![
+                completion::Message::user("Hi"),
+                completion::Message::tool_call("call_1", "lookup", json!({})),
+            ]),
+            vec![
+                json!({ "role": "user", "content": "Hi" }),
+                json!({
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": { "name": "lookup", "arguments": "{}" },
+                    }],
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_request_sampling_params() {
+        let model = CompletionModel::new(Client::new("key"), GPT_4O);
+
+        // Parameters that are not set are left out of the request
+        let request = model.create_completion_request(model.completion_request("Hi").build());
+        for param in [
+            "temperature",
+            "max_tokens",
+            "stop",
+            "top_p",
+            "seed",
+            "frequency_penalty",
+            "presence_penalty",
+        ] {
+            assert!(request.get(param).is_none(), "{param} should not be sent");
+        }
+
+        let request = model.create_completion_request(
+            model
+                .completion_request("Hi")
+                .temperature(0.5)
+                .max_tokens(100)
+                .stop_sequence("\n\n")
+                .top_p(0.9)
+                .seed(42)
+                .frequency_penalty(0.1)
+                .presence_penalty(0.2)
+                .build(),
+        );
+        assert_eq!(request["temperature"], 0.5);
+        assert_eq!(request["max_tokens"], 100);
+        assert_eq!(request["stop"], json!(["\n\n"]));
+        assert_eq!(request["top_p"], 0.9);
+        assert_eq!(request["seed"], 42);
+        assert_eq!(request["frequency_penalty"], 0.1);
+        assert_eq!(request["presence_penalty"], 0.2);
+    }
+
+    #[test]
+    fn test_request_tool_choice() {
+        let model = CompletionModel::new(Client::new("key"), GPT_4O);
+        let tool_choice = |choice: Option<completion::ToolChoice>| {
+            let request = model.create_completion_request(
+                model
+                    .completion_request("Hi")
+                    .tool(lookup_tool())
+                    .tool_choice_opt(choice)
+                    .build(),
+            );
+            request["tool_choice"].clone()
+        };
+
+        assert_eq!(tool_choice(None), json!("auto"));
+        assert_eq!(
+            tool_choice(Some(completion::ToolChoice::None)),
+            json!("none")
+        );
+        assert_eq!(
+            tool_choice(Some(completion::ToolChoice::Required)),
+            json!("required")
+        );
+        assert_eq!(
+            tool_choice(Some(completion::ToolChoice::Specific("lookup".into()))),
+            json!({ "type": "function", "function": { "name": "lookup" } })
+        );
+    }
+}
@@ -11,9 +11,11 @@
 
 use crate::{
     agent::AgentBuilder,
-    completion::{self, CompletionError},
+    completion::{self, CompletionError, SamplingParam},
     extractor::ExtractorBuilder,
     json_utils,
+    providers::openai,
+    streaming::{StreamingCompletionModel, StreamingResult},
 };
 
 use schemars::JsonSchema;
@@ -161,16 +163,28 @@ impl std::fmt::Display for Usage {
     }
 }
 
+impl From<&Usage> for completion::Usage {
+    fn from(usage: &Usage) -> Self {
+        completion::Usage {
+            prompt_tokens: usage.prompt_tokens as u64,
+            completion_tokens: usage.completion_tokens as u64,
+            ..Default::default()
+        }
+    }
+}
+
 impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionResponse> {
     type Error = CompletionError;
 
     fn try_from(value: CompletionResponse) -> Result<Self, Self::Error> {
+        let usage = completion::Usage::from(&value.usage);
         match value.choices.as_slice() {
             [Choice {
                 message: Message { content, .. },
                 ..
             }, ..] => Ok(completion::CompletionResponse {
                 choice: completion::ModelChoice::Message(content.to_string()),
+                usage,
                 raw_response: value,
             }),
             _ => Err(CompletionError::ResponseError(
@@ -195,52 +209,68 @@ impl CompletionModel {
     }
 }
 
-impl completion::CompletionModel for CompletionModel {
-    type Response = CompletionResponse;
-
-    #[cfg_attr(feature = "worker", worker::send)]
-    async fn completion(
+impl CompletionModel {
+    /// Builds the chat completion request body, which streaming requests extend
+    fn create_completion_request(
         &self,
         completion_request: completion::CompletionRequest,
-    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+    ) -> Result<serde_json::Value, CompletionError> {
+        completion_request.check_unsupported(
+            "Perplexity",
+            &[
+                SamplingParam::Stop,
+                SamplingParam::Seed,
+                SamplingParam::ResponseFormat,
+            ],
+        )?;
+
+        let sampling_params = openai::sampling_params(&completion_request);
+
         // Add preamble to messages (if available)
         let mut messages = if let Some(preamble) = &completion_request.preamble {
-            vec![completion::Message {
-                role: "system".into(),
-                content: preamble.clone(),
-            }]
+            vec![completion::Message::system(preamble.clone())]
         } else {
             vec![]
         };
 
-        // Add context documents to chat history
+        // Add context documents to the prompt
         let prompt_with_context = completion_request.prompt_with_context();
 
         // Add chat history to messages
         messages.extend(completion_request.chat_history);
 
-        // Add user prompt to messages
-        messages.push(completion::Message {
-            role: "user".to_string(),
-            content: prompt_with_context,
-        });
+        // Add prompt to messages
+        messages.push(prompt_with_context);
 
         let request = json!({
             "model": self.model,
-            "messages": messages,
-            "temperature": completion_request.temperature,
+            "messages": openai::chat_messages(messages),
         });
 
+        let request = json_utils::merge(request, sampling_params);
+
+        Ok(if let Some(params) = completion_request.additional_params {
+            json_utils::merge(request, params)
+        } else {
+            request
+        })
+    }
+}
+
+impl completion::CompletionModel for CompletionModel {
+    type Response = CompletionResponse;
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn completion(
+        &self,
+        completion_request: completion::CompletionRequest,
+    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+        let request = self.create_completion_request(completion_request)?;
+
         let response = self
             .client
             .post("/chat/completions")
-            .json(
-                &if let Some(ref params) = completion_request.additional_params {
-                    json_utils::merge(request.clone(), params.clone())
-                } else {
-                    request.clone()
-                },
-            )
+            .json(&request)
             .send()
             .await?;
 
@@ -260,3 +290,18 @@ impl completion::CompletionModel for CompletionModel {
         }
     }
 }
+
+impl StreamingCompletionModel for CompletionModel {
+    async fn stream(
+        &self,
+        request: completion::CompletionRequest,
+    ) -> Result<StreamingResult, CompletionError> {
+        let mut request = self.create_completion_request(request)?;
+        json_utils::merge_inplace(&mut request, json!({ "stream": true }));
+
+        openai::send_streaming_request::<Usage>(
+            self.client.post("/chat/completions").json(&request),
+        )
+        .await
+    }
+}
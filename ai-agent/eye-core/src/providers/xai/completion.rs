@@ -4,12 +4,14 @@
 // ================================================================
 
 use crate::{
-    completion::{self, CompletionError},
+    completion::{self, CompletionError, SamplingParam},
     json_utils,
+    providers::openai,
+    streaming::{StreamingCompletionModel, StreamingResult},
 };
 
 use serde_json::json;
-use xai_api_types::{CompletionResponse, ToolDefinition};
+use xai_api_types::{CompletionResponse, ToolDefinition, Usage};
 
 use super::client::{xai_api_types::ApiResponse, Client};
 
@@ -35,52 +37,58 @@ impl CompletionModel {
     }
 }
 
-impl completion::CompletionModel for CompletionModel {
-    type Response = CompletionResponse;
-
-    #[cfg_attr(feature = "worker", worker::send)]
-    async fn completion(
+impl CompletionModel {
+    /// Builds the chat completion request body, which streaming requests extend
+    fn create_completion_request(
         &self,
         mut completion_request: completion::CompletionRequest,
-    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+    ) -> Result<serde_json::Value, CompletionError> {
+        completion_request.check_unsupported("xAI", &[SamplingParam::ResponseFormat])?;
+
+        let sampling_params = openai::sampling_params(&completion_request);
+
         let mut messages = if let Some(preamble) = &completion_request.preamble {
-            vec![completion::Message {
-                role: "system".into(),
-                content: preamble.clone(),
-            }]
+            vec![completion::Message::system(preamble.clone())]
         } else {
             vec![]
         };
         messages.append(&mut completion_request.chat_history);
 
-        let prompt_with_context = completion_request.prompt_with_context();
-
-        messages.push(completion::Message {
-            role: "user".into(),
-            content: prompt_with_context,
-        });
+        messages.push(completion_request.prompt_with_context());
 
         let mut request = if completion_request.tools.is_empty() {
             json!({
                 "model": self.model,
-                "messages": messages,
-                "temperature": completion_request.temperature,
+                "messages": openai::chat_messages(messages),
             })
         } else {
             json!({
                 "model": self.model,
-                "messages": messages,
-                "temperature": completion_request.temperature,
+                "messages": openai::chat_messages(messages),
                 "tools": completion_request.tools.into_iter().map(ToolDefinition::from).collect::<Vec<_>>(),
-                "tool_choice": "auto",
+                "tool_choice": openai::tool_choice(&completion_request.tool_choice.unwrap_or_default()),
             })
         };
 
-        request = if let Some(params) = completion_request.additional_params {
+        request = json_utils::merge(request, sampling_params);
+
+        Ok(if let Some(params) = completion_request.additional_params {
             json_utils::merge(request, params)
         } else {
             request
-        };
+        })
+    }
+}
+
+impl completion::CompletionModel for CompletionModel {
+    type Response = CompletionResponse;
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn completion(
+        &self,
+        completion_request: completion::CompletionRequest,
+    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+        let request = self.create_completion_request(completion_request)?;
 
         let response = self
             .client
@@ -100,6 +108,24 @@ impl completion::CompletionModel for CompletionModel {
     }
 }
 
+impl StreamingCompletionModel for CompletionModel {
+    async fn stream(
+        &self,
+        request: completion::CompletionRequest,
+    ) -> Result<StreamingResult, CompletionError> {
+        let mut request = self.create_completion_request(request)?;
+        json_utils::merge_inplace(
+            &mut request,
+            json!({ "stream": true, "stream_options": { "include_usage": true } }),
+        );
+
+        openai::send_streaming_request::<Usage>(
+            self.client.post("/v1/chat/completions").json(&request),
+        )
+        .await
+    }
+}
+
 pub mod xai_api_types {
     use serde::{Deserialize, Serialize};
 
@@ -109,39 +135,51 @@ pub mod xai_api_types {
         type Error = CompletionError;
 
         fn try_from(value: CompletionResponse) -> Result<Self, Self::Error> {
+            let usage = completion::Usage::from(&value.usage);
             match value.choices.as_slice() {
                 [Choice {
                     message:
                         Message {
-                            content: Some(content),
-                            ..
-                        },
-                    ..
-                }, ..] => Ok(completion::CompletionResponse {
-                    choice: completion::ModelChoice::Message(content.to_string()),
-                    raw_response: value,
-                }),
-                [Choice {
-                    message:
-                        Message {
+                            content,
                             tool_calls: Some(calls),
                             ..
                         },
                     ..
-                }, ..] => {
-                    let call = calls.first().ok_or(CompletionError::ResponseError(
-                        "Tool selection is empty".into(),
-                    ))?;
+                }, ..]
+                    if !calls.is_empty() =>
+                {
+                    let calls = calls
+                        .iter()
+                        .map(|call| {
+                            Ok(completion::ToolCall {
+                                id: call.id.clone(),
+                                name: call.function.name.clone(),
+                                arguments: serde_json::from_str(&call.function.arguments)?,
+                            })
+                        })
+                        .collect::<Result<_, CompletionError>>()?;
 
                     Ok(completion::CompletionResponse {
-                        choice: completion::ModelChoice::ToolCall(
-                            call.function.name.clone(),
-                            "".to_owned(),
-                            serde_json::from_str(&call.function.arguments)?,
-                        ),
+                        choice: completion::ModelChoice::ToolCalls {
+                            text: content.clone(),
+                            calls,
+                        },
+                        usage,
                         raw_response: value,
                     })
                 }
+                [Choice {
+                    message:
+                        Message {
+                            content: Some(content),
+                            ..
+                        },
+                    ..
+                }, ..] => Ok(completion::CompletionResponse {
+                    choice: completion::ModelChoice::Message(content.to_string()),
+                    usage,
+                    raw_response: value,
+                }),
                 _ => Err(CompletionError::ResponseError(
                     "Response did not contain a message or tool call".into(),
                 )),
@@ -208,4 +246,14 @@ pub mod xai_api_types {
         pub prompt_tokens: i32,
         pub total_tokens: i32,
     }
+
+    impl From<&Usage> for completion::Usage {
+        fn from(usage: &Usage) -> Self {
+            completion::Usage {
+                prompt_tokens: usage.prompt_tokens as u64,
+                completion_tokens: usage.completion_tokens as u64,
+                ..Default::default()
+            }
+        }
+    }
 }
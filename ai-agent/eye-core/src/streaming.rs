@@ -8,11 +8,16 @@
 //! - [StreamingCompletion]: Defines a low-level streaming LLM completion interface
 //! - [StreamingCompletionModel]: Defines a streaming completion model interface
 //!
+//! It also provides the building blocks providers use to read streaming responses: [lines] splits
+//! a response body into lines, and [sse_events] reads the server-sent events of a response body.
 
 use crate::agent::Agent;
 use crate::completion::{
-    CompletionError, CompletionModel, CompletionRequest, CompletionRequestBuilder, Message,
+    CompletionError, CompletionModel, CompletionRequest, CompletionRequestBuilder,
+    CompletionResponse, Message, ModelChoice, ToolCall, Usage,
 };
+use async_stream::stream;
+use bytes::Bytes;
 use futures::{Stream, StreamExt};
 use std::boxed::Box;
 use std::fmt::{Display, Formatter};
@@ -20,13 +25,65 @@ use std::future::Future;
 use std::pin::Pin;
 
 /// Enum representing a streaming chunk from the model
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum StreamingChoice {
     /// A text chunk from a message response
     Message(String),
 
     /// A tool call response chunk
     ToolCall(String, String, serde_json::Value),
+
+    /// The whole response, sent once the stream is complete
+    Final(FinalResponse),
+}
+
+/// The response a stream has sent, put together from its chunks
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FinalResponse {
+    /// The text of the message, its chunks joined
+    pub message: String,
+    /// The tool calls, in the order they were sent
+    pub tool_calls: Vec<ToolCall>,
+    /// The tokens used by the request and the completion
+    pub usage: Usage,
+    /// Why the model stopped, as the provider reports it (e.g. `stop`, `tool_calls`, `end_turn`)
+    pub finish_reason: Option<String>,
+}
+
+impl FinalResponse {
+    /// Adds a chunk to the response and returns it, so providers can record each chunk as they
+    /// yield it
+    pub fn add(&mut self, choice: StreamingChoice) -> StreamingChoice {
+        match &choice {
+            StreamingChoice::Message(text) => self.message.push_str(text),
+            StreamingChoice::ToolCall(name, id, arguments) => self.tool_calls.push(ToolCall {
+                id: id.clone(),
+                name: name.clone(),
+                arguments: arguments.clone(),
+            }),
+            StreamingChoice::Final(_) => {}
+        }
+        choice
+    }
+}
+
+impl From<FinalResponse> for CompletionResponse<FinalResponse> {
+    fn from(response: FinalResponse) -> Self {
+        let choice = if response.tool_calls.is_empty() {
+            ModelChoice::Message(response.message.clone())
+        } else {
+            ModelChoice::ToolCalls {
+                text: (!response.message.is_empty()).then(|| response.message.clone()),
+                calls: response.tool_calls.clone(),
+            }
+        };
+
+        CompletionResponse {
+            choice,
+            usage: response.usage,
+            raw_response: response,
+        }
+    }
 }
 
 impl Display for StreamingChoice {
@@ -36,11 +93,24 @@ impl Display for StreamingChoice {
             StreamingChoice::ToolCall(name, id, params) => {
                 write!(f, "Tool call: {} {} {:?}", name, id, params)
             }
+            StreamingChoice::Final(response) => write!(
+                f,
+                "Finished ({}): {} prompt tokens, {} completion tokens",
+                response
+                    .finish_reason
+                    .as_deref()
+                    .unwrap_or("unknown reason"),
+                response.usage.prompt_tokens,
+                response.usage.completion_tokens
+            ),
         }
     }
 }
 
-pub type StreamingResult = Pin<Box<dyn Stream<Item = Result<StreamingChoice, CompletionError>>>>;
+/// A streaming completion response. It can be sent to another task, such as a spawned task or a
+/// web handler, and dropping it cancels the request.
+pub type StreamingResult =
+    Pin<Box<dyn Stream<Item = Result<StreamingChoice, CompletionError>> + Send>>;
 
 /// Trait for high-level streaming prompt interface
 pub trait StreamingPrompt: Send + Sync {
@@ -77,10 +147,11 @@ pub trait StreamingCompletionModel: CompletionModel {
     fn stream(
         &self,
         request: CompletionRequest,
-    ) -> impl Future<Output = Result<StreamingResult, CompletionError>>;
+    ) -> impl Future<Output = Result<StreamingResult, CompletionError>> + Send;
 }
 
-/// helper function to stream a completion request to stdout
+/// helper function to stream a completion request to stdout. Tool calls are run, but their
+/// results are only printed: use [Agent::stream_chat_events] to send them back to the model.
 pub async fn stream_to_stdout<M: StreamingCompletionModel>(
     agent: Agent<M>,
     stream: &mut StreamingResult,
@@ -100,6 +171,7 @@ pub async fn stream_to_stdout<M: StreamingCompletionModel>(
                     .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
                 println!("\nResult: {}", res);
             }
+            Ok(StreamingChoice::Final(_)) => {}
             Err(e) => {
                 eprintln!("Error: {}", e);
                 break;
@@ -110,3 +182,277 @@ pub async fn stream_to_stdout<M: StreamingCompletionModel>(
 
     Ok(())
 }
+
+/// Reads a stream to its end and returns the response it sent, as [CompletionModel::completion]
+/// would have
+pub async fn collect_response(
+    mut stream: StreamingResult,
+) -> Result<CompletionResponse<FinalResponse>, CompletionError> {
+    while let Some(chunk) = stream.next().await {
+        if let StreamingChoice::Final(response) = chunk? {
+            return Ok(response.into());
+        }
+    }
+    Err(CompletionError::ResponseError(
+        "Stream ended without a final response".into(),
+    ))
+}
+
+/// Splits a streaming response body into lines, without their line endings. A line may be split
+/// across chunks of the body, even in the middle of a character, so it is only decoded once
+/// complete.
+pub fn lines<E>(
+    bytes: impl Stream<Item = Result<Bytes, E>>,
+) -> impl Stream<Item = Result<String, CompletionError>>
+where
+    CompletionError: From<E>,
+{
+    fn decode(line: Vec<u8>) -> Result<String, CompletionError> {
+        let line = String::from_utf8(line)
+            .map_err(|error| CompletionError::ResponseError(error.to_string()))?;
+        Ok(line.trim_end_matches(['\r', '\n']).to_string())
+    }
+
+    stream! {
+        let mut buffer = Vec::new();
+        for await chunk in bytes {
+            match chunk {
+                Ok(chunk) => buffer.extend_from_slice(&chunk),
+                Err(error) => {
+                    yield Err(CompletionError::from(error));
+                    return;
+                }
+            }
+            while let Some(end) = buffer.iter().position(|byte| *byte == b'\n') {
+                yield decode(buffer.drain(..=end).collect());
+            }
+        }
+        if !buffer.is_empty() {
+            yield decode(buffer);
+        }
+    }
+}
+
+/// A server-sent event, as sent by providers streaming a completion
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SseEvent {
+    /// The type of the event, if the provider names it
+    pub event: Option<String>,
+    /// The data of the event, its lines joined with newlines
+    pub data: String,
+}
+
+/// Reads the server-sent events of a streaming response body. Comments and events without data
+/// are skipped.
+pub fn sse_events<E>(
+    bytes: impl Stream<Item = Result<Bytes, E>>,
+) -> impl Stream<Item = Result<SseEvent, CompletionError>>
+where
+    CompletionError: From<E>,
+{
+    stream! {
+        let mut event = None;
+        let mut data = Vec::new();
+        for await line in lines(bytes) {
+            let line = match line {
+                Ok(line) => line,
+                Err(error) => {
+                    yield Err(error);
+                    return;
+                }
+            };
+            // An empty line ends the event
+            if line.is_empty() {
+                if !data.is_empty() {
+                    yield Ok(SseEvent {
+                        event: event.take(),
+                        data: data.join("\n"),
+                    });
+                    data.clear();
+                }
+                event = None;
+                continue;
+            }
+            let (field, value) = line.split_once(':').unwrap_or((&line, ""));
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            match field {
+                "event" => event = Some(value.to_string()),
+                "data" => data.push(value.to_string()),
+                _ => {}
+            }
+        }
+        if !data.is_empty() {
+            yield Ok(SseEvent {
+                event,
+                data: data.join("\n"),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::completion::tests::ScriptedModel;
+    use futures::stream;
+
+    /// Splits a recorded response body into chunks of `size` bytes, which cut through lines and
+    /// characters alike
+    pub(crate) fn chunked(
+        body: &str,
+        size: usize,
+    ) -> impl Stream<Item = Result<Bytes, CompletionError>> {
+        let chunks = body
+            .as_bytes()
+            .chunks(size)
+            .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+            .collect::<Vec<_>>();
+        stream::iter(chunks)
+    }
+
+    /// Streams the scripted choices word by word
+    impl StreamingCompletionModel for ScriptedModel {
+        async fn stream(
+            &self,
+            request: CompletionRequest,
+        ) -> Result<StreamingResult, CompletionError> {
+            let response = self.completion(request).await?;
+            let (text, calls) = match response.choice {
+                ModelChoice::Message(text) => (text, vec![]),
+                ModelChoice::ToolCalls { text, calls } => (text.unwrap_or_default(), calls),
+            };
+
+            let mut final_response = FinalResponse {
+                usage: response.usage,
+                ..Default::default()
+            };
+            let mut chunks = text
+                .split_inclusive(' ')
+                .map(|word| StreamingChoice::Message(word.into()))
+                .chain(
+                    calls
+                        .into_iter()
+                        .map(|call| StreamingChoice::ToolCall(call.name, call.id, call.arguments)),
+                )
+                .map(|chunk| Ok(final_response.add(chunk)))
+                .collect::<Vec<_>>();
+            chunks.push(Ok(StreamingChoice::Final(final_response)));
+            Ok(Box::pin(stream::iter(chunks)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lines_across_chunks() {
+        let lines = lines(chunked("first\r\nsecond ü\n\nlast", 3))
+            .map(Result::unwrap)
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(lines, vec!["first", "second ü", "", "last"]);
+    }
+
+    #[tokio::test]
+    async fn test_lines_stop_at_error() {
+        let body = stream::iter(vec![
+            Ok(Bytes::from("one\ntw")),
+            Err(CompletionError::ResponseError("connection reset".into())),
+            Ok(Bytes::from("o\n")),
+        ]);
+
+        let lines = lines(body).collect::<Vec<_>>().await;
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].as_ref().unwrap(), "one");
+        assert!(matches!(lines[1], Err(CompletionError::ResponseError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_sse_events() {
+        let body = ": comment\n\
+            event: message_start\n\
+            data: {\"a\":1}\n\
+            \n\
+            id: 2\n\
+            data:first\n\
+            data: second\n\
+            \n\
+            \n\
+            event: ping\n\
+            \n\
+            data: unterminated";
+
+        let events = sse_events(chunked(body, 5))
+            .map(Result::unwrap)
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(
+            events,
+            vec![
+                SseEvent {
+                    event: Some("message_start".into()),
+                    data: "{\"a\":1}".into(),
+                },
+                SseEvent {
+                    event: None,
+                    data: "first\nsecond".into(),
+                },
+                SseEvent {
+                    event: None,
+                    data: "unterminated".into(),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_collect_response() {
+        let call = ToolCall {
+            id: "call_1".into(),
+            name: "lookup".into(),
+            arguments: serde_json::json!({ "mint": "So11111111111111111111111111111111111111112" }),
+        };
+        let final_response = FinalResponse {
+            message: "Checking the token.".into(),
+            tool_calls: vec![call.clone()],
+            usage: Usage {
+                prompt_tokens: 10,
+                completion_tokens: 4,
+                ..Default::default()
+            },
+            finish_reason: Some("tool_calls".into()),
+        };
+        let chunks: StreamingResult = Box::pin(stream::iter(vec![
+            Ok(StreamingChoice::Message("Checking the token.".into())),
+            Ok(StreamingChoice::ToolCall(
+                call.name.clone(),
+                call.id.clone(),
+                call.arguments.clone(),
+            )),
+            Ok(StreamingChoice::Final(final_response.clone())),
+        ]));
+
+        let response = collect_response(chunks).await.unwrap();
+
+        match response.choice {
+            ModelChoice::ToolCalls { text, calls } => {
+                assert_eq!(text.as_deref(), Some("Checking the token."));
+                assert_eq!(calls, vec![call]);
+            }
+            choice => panic!("Expected tool calls, got {:?}", choice),
+        }
+        assert_eq!(response.usage.total_tokens(), 14);
+        assert_eq!(response.raw_response, final_response);
+    }
+
+    #[tokio::test]
+    async fn test_collect_response_without_final() {
+        let chunks: StreamingResult = Box::pin(stream::iter(vec![Ok(StreamingChoice::Message(
+            "Cut off".into(),
+        ))]));
+
+        let result = collect_response(chunks).await;
+
+        assert!(matches!(result, Err(CompletionError::ResponseError(_))));
+    }
+}
@@ -12,9 +12,10 @@
 use crate::eternalai_system_prompt_manager_toolset;
 use crate::json_utils;
 use eye::agent::AgentBuilder;
-use eye::completion::{CompletionError, CompletionRequest};
+use eye::completion::{CompletionError, CompletionRequest, SamplingParam};
 use eye::embeddings::{EmbeddingError, EmbeddingsBuilder};
 use eye::extractor::ExtractorBuilder;
+use eye::providers::openai;
 use eye::{completion, embeddings, Embed};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -238,6 +239,16 @@ impl std::fmt::Display for Usage {
     }
 }
 
+impl From<&Usage> for completion::Usage {
+    fn from(usage: &Usage) -> Self {
+        completion::Usage {
+            prompt_tokens: usage.prompt_tokens as u64,
+            completion_tokens: usage.total_tokens.saturating_sub(usage.prompt_tokens) as u64,
+            ..Default::default()
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct EmbeddingModel {
     client: Client,
@@ -353,25 +364,40 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
     type Error = CompletionError;
 
     fn try_from(value: CompletionResponse) -> Result<Self, Self::Error> {
+        let usage = value
+            .usage
+            .as_ref()
+            .map(completion::Usage::from)
+            .unwrap_or_default();
         match value.choices.as_slice() {
             [Choice {
                 message:
                     Message {
+                        content,
                         tool_calls: Some(calls),
                         ..
                     },
                 ..
-            }, ..] => {
-                let call = calls.first().ok_or(CompletionError::ResponseError(
-                    "Tool selection is empty".into(),
-                ))?;
+            }, ..]
+                if !calls.is_empty() =>
+            {
+                let calls = calls
+                    .iter()
+                    .map(|call| {
+                        Ok(completion::ToolCall {
+                            id: call.id.clone(),
+                            name: call.function.name.clone(),
+                            arguments: serde_json::from_str(&call.function.arguments)?,
+                        })
+                    })
+                    .collect::<Result<_, CompletionError>>()?;
 
                 Ok(completion::CompletionResponse {
-                    choice: completion::ModelChoice::ToolCall(
-                        call.function.name.clone(),
-                        call.id.clone(),
-                        serde_json::from_str(&call.function.arguments)?,
-                    ),
+                    choice: completion::ModelChoice::ToolCalls {
+                        text: content.clone(),
+                        calls,
+                    },
+                    usage,
                     raw_response: value,
                 })
             }
@@ -384,6 +410,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
                 ..
             }, ..] => Ok(completion::CompletionResponse {
                 choice: completion::ModelChoice::Message(content.to_string()),
+                usage,
                 raw_response: value,
             }),
             _ => Err(CompletionError::ResponseError(
@@ -461,12 +488,13 @@ impl completion::CompletionModel for CompletionModel {
         &self,
         mut completion_request: CompletionRequest,
     ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+        completion_request.check_unsupported("EternalAI", &[SamplingParam::ResponseFormat])?;
+
+        let sampling_params = openai::sampling_params(&completion_request);
+
         // Add preamble to chat history (if available)
         let mut full_history = if let Some(preamble) = &completion_request.preamble {
-            vec![completion::Message {
-                role: "system".into(),
-                content: preamble.clone(),
-            }]
+            vec![completion::Message::system(preamble.clone())]
         } else {
             vec![]
         };
@@ -503,11 +531,7 @@ impl completion::CompletionModel for CompletionModel {
                     tracing::info!("on-chain system prompt is none")
                 }
                 Some(value) => {
-                    let temp = completion::Message {
-                        role: "system".into(),
-                        content: value,
-                    };
-                    full_history.push(temp);
+                    full_history.push(completion::Message::system(value));
                 }
             }
         }
@@ -516,13 +540,7 @@ impl completion::CompletionModel for CompletionModel {
         full_history.append(&mut completion_request.chat_history);
 
         // Add context documents to chat history
-        let prompt_with_context = completion_request.prompt_with_context();
-
-        // Add context documents to chat history
-        full_history.push(completion::Message {
-            role: "user".into(),
-            content: prompt_with_context,
-        });
+        full_history.push(completion_request.prompt_with_context());
 
         let mut chain_id = self.chain_id.clone();
         if chain_id.is_empty() {
@@ -533,22 +551,22 @@ impl completion::CompletionModel for CompletionModel {
             json!({
                 "model": self.model,
                 "chain_id": chain_id,
-                "messages": full_history,
-                "temperature": completion_request.temperature,
+                "messages": openai::chat_messages(full_history),
             })
         } else {
             json!({
                 "model": self.model,
                 "chain_id": chain_id,
-                "messages": full_history,
-                "temperature": completion_request.temperature,
+                "messages": openai::chat_messages(full_history),
                 "tools": completion_request.tools.into_iter().map(ToolDefinition::from).collect::<Vec<_>>(),
-                "tool_choice": "auto",
+                "tool_choice": openai::tool_choice(&completion_request.tool_choice.unwrap_or_default()),
             })
         };
 
         tracing::debug!("request: {:?}", request.to_string());
 
+        let request = json_utils::merge(request, sampling_params);
+
         let response = self
             .client
             .post("/chat/completions")
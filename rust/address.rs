@@ -0,0 +1,237 @@
+use std::fmt;
+use std::str::FromStr;
+
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha3::{Digest, Keccak256};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Chain {
+    Evm,
+    Solana,
+}
+
+impl fmt::Display for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Chain::Evm => write!(f, "evm"),
+            Chain::Solana => write!(f, "solana"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AddressError {
+    Empty,
+    InvalidHex(String),
+    BadChecksum(String),
+    InvalidBase58(String),
+}
+
+impl fmt::Display for AddressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddressError::Empty => write!(f, "empty address"),
+            AddressError::InvalidHex(s) => write!(f, "invalid EVM address: {}", s),
+            AddressError::BadChecksum(s) => write!(f, "EVM address fails EIP-55 checksum: {}", s),
+            AddressError::InvalidBase58(s) => write!(f, "invalid Solana address: {}", s),
+        }
+    }
+}
+
+impl std::error::Error for AddressError {}
+
+/// A validated on-chain address in its canonical form.
+///
+/// EVM addresses are stored EIP-55 checksummed, Solana addresses as their
+/// base58 encoding of a 32-byte public key. Two addresses compare equal only
+/// if they name the same account on the same chain.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Address {
+    chain: Chain,
+    value: String,
+}
+
+impl Address {
+    pub fn evm_zero() -> Self {
+        Address {
+            chain: Chain::Evm,
+            value: format!("0x{}", "0".repeat(40)),
+        }
+    }
+
+    pub fn chain(&self) -> Chain {
+        self.chain
+    }
+
+    fn parse_evm(s: &str) -> Result<Self, AddressError> {
+        let hex = &s[2..];
+        if hex.len() != 40 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(AddressError::InvalidHex(s.to_string()));
+        }
+        let checksummed = to_checksum(hex);
+        let mixed_case = hex.chars().any(|c| c.is_ascii_lowercase())
+            && hex.chars().any(|c| c.is_ascii_uppercase());
+        // All-lower and all-upper input carry no checksum; mixed case must match it.
+        if mixed_case && checksummed[2..] != *hex {
+            return Err(AddressError::BadChecksum(s.to_string()));
+        }
+        Ok(Address {
+            chain: Chain::Evm,
+            value: checksummed,
+        })
+    }
+
+    fn parse_solana(s: &str) -> Result<Self, AddressError> {
+        match bs58::decode(s).into_vec() {
+            Ok(bytes) if bytes.len() == 32 => Ok(Address {
+                chain: Chain::Solana,
+                value: bs58::encode(bytes).into_string(),
+            }),
+            _ => Err(AddressError::InvalidBase58(s.to_string())),
+        }
+    }
+}
+
+fn to_checksum(hex: &str) -> String {
+    let lower = hex.to_ascii_lowercase();
+    let hash = Keccak256::digest(lower.as_bytes());
+    let mut out = String::with_capacity(42);
+    out.push_str("0x");
+    for (i, c) in lower.chars().enumerate() {
+        let nibble = (hash[i / 2] >> (if i % 2 == 0 { 4 } else { 0 })) & 0x0f;
+        if c.is_ascii_alphabetic() && nibble >= 8 {
+            out.push(c.to_ascii_uppercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+impl FromStr for Address {
+    type Err = AddressError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(AddressError::Empty);
+        }
+        if s.starts_with("0x") || s.starts_with("0X") {
+            Address::parse_evm(s)
+        } else {
+            Address::parse_solana(s)
+        }
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.value)
+    }
+}
+
+impl Serialize for Address {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.value)
+    }
+}
+
+impl<'de> Deserialize<'de> for Address {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl ToSql for Address {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.value.as_str()))
+    }
+}
+
+impl FromSql for Address {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|e: AddressError| FromSqlError::Other(Box::new(e)))
+    }
+}
+
+/// Parses a comma-separated list of addresses, skipping blank entries.
+pub fn parse_list(s: &str) -> Result<Vec<Address>, AddressError> {
+    s.split(',')
+        .filter(|part| !part.trim().is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EIP55_VECTORS: [&str; 4] = [
+        "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+        "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+        "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+        "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+    ];
+
+    #[test]
+    fn evm_addresses_are_checksummed() {
+        for expected in EIP55_VECTORS {
+            let lower: Address = expected.to_ascii_lowercase().parse().unwrap();
+            let upper: Address = format!("0x{}", expected[2..].to_ascii_uppercase()).parse().unwrap();
+            let mixed: Address = expected.parse().unwrap();
+            assert_eq!(lower.to_string(), expected);
+            assert_eq!(upper, lower);
+            assert_eq!(mixed, lower);
+            assert_eq!(mixed.chain(), Chain::Evm);
+        }
+    }
+
+    #[test]
+    fn evm_addresses_with_a_wrong_checksum_are_rejected() {
+        // The first vector with the case of its first letter flipped
+        let wrong = "0x5AAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+        assert_eq!(wrong.parse::<Address>(), Err(AddressError::BadChecksum(wrong.to_string())));
+        for invalid in ["0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeA", "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeg"] {
+            assert!(matches!(invalid.parse::<Address>(), Err(AddressError::InvalidHex(_))));
+        }
+    }
+
+    #[test]
+    fn solana_addresses_are_parsed() {
+        let mint: Address = "  So11111111111111111111111111111111111111112\n".parse().unwrap();
+        assert_eq!(mint.to_string(), "So11111111111111111111111111111111111111112");
+        assert_eq!(mint.chain(), Chain::Solana);
+        assert_eq!("".parse::<Address>(), Err(AddressError::Empty));
+        assert_eq!("   ".parse::<Address>(), Err(AddressError::Empty));
+
+        for invalid in [
+            // Too short and too long for a 32-byte key
+            "So1111111111111111111111111111111",
+            "So111111111111111111111111111111111111111111112",
+            // Whitespace inside the address, and characters outside the base58 alphabet
+            "So111111111111111111111 11111111111111111112",
+            "So0OIl1111111111111111111111111111111111112",
+        ] {
+            assert!(
+                matches!(invalid.parse::<Address>(), Err(AddressError::InvalidBase58(_))),
+                "{invalid:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn lists_skip_blank_entries() {
+        let list = parse_list(
+            " 0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed, ,So11111111111111111111111111111111111111112,",
+        )
+        .unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].to_string(), EIP55_VECTORS[0]);
+        assert!(parse_list("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed,nope").is_err());
+    }
+}
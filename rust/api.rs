@@ -0,0 +1,260 @@
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use axum::extract::{Query, State};
+use axum::http::{HeaderValue, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use futures::{Stream, StreamExt};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::{broadcast, watch};
+use tokio_stream::wrappers::BroadcastStream;
+use tower_http::cors::CorsLayer;
+
+use crate::coins::{self, CoinRow};
+use crate::decisions::{self, Decision, SecurityResult};
+use crate::paper::{self, Position};
+
+/// Published on the alert stream whenever a coin is accepted.
+#[derive(Debug, Clone, Serialize)]
+pub struct AlertEvent {
+    pub contract_address: String,
+    pub name: String,
+    pub symbol: String,
+    pub initial_liquidity: f64,
+    pub creator_fee: f64,
+    pub holders: i64,
+    /// Profiles that accepted the coin.
+    pub profiles: Vec<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub enabled: bool,
+    pub bind: SocketAddr,
+    pub allowed_origin: String,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            enabled: false,
+            bind: SocketAddr::from(([127, 0, 0, 1], 8787)),
+            allowed_origin: "http://localhost:3000".to_string(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AppState {
+    db: Arc<Mutex<Connection>>,
+    runtime_config: Arc<Value>,
+    alerts: broadcast::Sender<AlertEvent>,
+}
+
+impl AppState {
+    /// `db` should be a connection of its own; the monitor loop keeps its
+    /// connection and the server only ever reads.
+    pub fn new(db: Connection, runtime_config: Value, alerts: broadcast::Sender<AlertEvent>) -> Self {
+        AppState {
+            db: Arc::new(Mutex::new(db)),
+            runtime_config: Arc::new(runtime_config),
+            alerts,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ListParams {
+    limit: Option<u32>,
+    address: Option<String>,
+}
+
+impl ListParams {
+    fn limit(&self) -> u32 {
+        self.limit.unwrap_or(50).min(500)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum PositionStatus {
+    Open,
+    Closed,
+}
+
+#[derive(Debug, Deserialize)]
+struct PositionParams {
+    profile: Option<String>,
+    /// Both open and closed positions when absent.
+    status: Option<PositionStatus>,
+}
+
+struct ApiError(String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = Json(serde_json::json!({ "error": self.0 }));
+        (StatusCode::INTERNAL_SERVER_ERROR, body).into_response()
+    }
+}
+
+type ApiResult<T> = Result<Json<T>, ApiError>;
+
+async fn with_db<T, F>(state: &AppState, f: F) -> ApiResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&Connection) -> rusqlite::Result<T> + Send + 'static,
+{
+    let db = state.db.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db.lock().map_err(|_| ApiError("database lock poisoned".into()))?;
+        f(&conn).map(Json).map_err(|e| ApiError(e.to_string()))
+    })
+    .await
+    .map_err(|e| ApiError(e.to_string()))?
+}
+
+async fn coins(State(state): State<AppState>, Query(params): Query<ListParams>) -> ApiResult<Vec<CoinRow>> {
+    let limit = params.limit();
+    with_db(&state, move |db| coins::recent(db, limit)).await
+}
+
+async fn decisions(State(state): State<AppState>, Query(params): Query<ListParams>) -> ApiResult<Vec<Decision>> {
+    let limit = params.limit();
+    with_db(&state, move |db| decisions::recent_decisions(db, limit)).await
+}
+
+async fn security(
+    State(state): State<AppState>,
+    Query(params): Query<ListParams>,
+) -> ApiResult<Vec<SecurityResult>> {
+    let limit = params.limit();
+    with_db(&state, move |db| {
+        decisions::recent_security_results(db, params.address.as_deref(), limit)
+    })
+    .await
+}
+
+async fn positions(State(state): State<AppState>, Query(params): Query<PositionParams>) -> ApiResult<Vec<Position>> {
+    with_db(&state, move |db| {
+        let book = paper::book(db, params.profile.as_deref())?;
+        Ok(match params.status {
+            Some(status) => book
+                .into_iter()
+                .filter(|p| p.closed == (status == PositionStatus::Closed))
+                .collect(),
+            None => book,
+        })
+    })
+    .await
+}
+
+async fn runtime_config(State(state): State<AppState>) -> Json<Value> {
+    Json((*state.runtime_config).clone())
+}
+
+async fn alert_stream(State(state): State<AppState>) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let stream = BroadcastStream::new(state.alerts.subscribe()).filter_map(|msg| async move {
+        // A lagging client just misses the alerts it was too slow for.
+        let alert = msg.ok()?;
+        Event::default().event("alert").json_data(alert).ok().map(Ok)
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+pub fn router(state: AppState, allowed_origin: &str) -> Router {
+    let mut router = Router::new()
+        .route("/api/coins", get(coins))
+        .route("/api/decisions", get(decisions))
+        .route("/api/security", get(security))
+        .route("/api/positions", get(positions))
+        .route("/api/config", get(runtime_config))
+        .route("/api/alerts/stream", get(alert_stream))
+        .with_state(state);
+    if let Ok(origin) = HeaderValue::from_str(allowed_origin) {
+        router = router.layer(CorsLayer::new().allow_origin(origin));
+    }
+    router
+}
+
+/// Serves the API until `shutdown` fires.
+pub async fn serve(
+    config: ServerConfig,
+    state: AppState,
+    mut shutdown: watch::Receiver<bool>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let listener = tokio::net::TcpListener::bind(config.bind).await?;
+    println!("[API] Listening on http://{}", config.bind);
+    axum::serve(listener, router(state, &config.allowed_origin))
+        .with_graceful_shutdown(async move {
+            let _ = shutdown.wait_for(|stop| *stop).await;
+        })
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address::Address;
+    use crate::migrations;
+
+    fn state_with_positions() -> AppState {
+        let mut db = Connection::open_in_memory().unwrap();
+        migrations::migrate(&mut db).unwrap();
+        let creator = Address::evm_zero();
+        for (profile, address) in [
+            ("default", "So11111111111111111111111111111111111111112"),
+            ("default", "4k3Dyjzvzp8eMZWUXbBCjEvwSkkk59S5iCNLY3QrkX6R"),
+            ("degen", "JUPyiwrYJFskUPiHa7hkeR8VUtAeFoSYbKedZNsDvCN"),
+        ] {
+            let address: Address = address.parse().unwrap();
+            paper::open(&db, profile, &address, &creator, 0.5, 1_700_000_000).unwrap();
+        }
+        db.execute(
+            "UPDATE paper_positions SET exit_proceeds = 1.0, closed_at = 1700000600
+             WHERE contract_address = '4k3Dyjzvzp8eMZWUXbBCjEvwSkkk59S5iCNLY3QrkX6R'",
+            [],
+        )
+        .unwrap();
+        AppState::new(db, Value::Null, broadcast::channel(1).0)
+    }
+
+    async fn list(profile: Option<&str>, status: Option<PositionStatus>) -> Vec<(String, String, bool)> {
+        let params = PositionParams {
+            profile: profile.map(String::from),
+            status,
+        };
+        let Ok(Json(book)) = positions(State(state_with_positions()), Query(params)).await else {
+            panic!("positions request failed");
+        };
+        book.into_iter()
+            .map(|p| (p.profile, p.contract_address[..4].to_string(), p.closed))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn positions_filter_by_profile_and_status() {
+        let all = list(None, None).await;
+        assert_eq!(all.len(), 3);
+
+        let default = list(Some("default"), None).await;
+        assert_eq!(
+            default,
+            [("default".into(), "So11".into(), false), ("default".into(), "4k3D".into(), true)]
+        );
+
+        let open = list(Some("default"), Some(PositionStatus::Open)).await;
+        assert_eq!(open, [("default".into(), "So11".into(), false)]);
+
+        let closed = list(None, Some(PositionStatus::Closed)).await;
+        assert_eq!(closed, [("default".into(), "4k3D".into(), true)]);
+    }
+}
@@ -0,0 +1,362 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::address::Address;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    Coin,
+    Dev,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    Config,
+    Manual,
+    AutoRule,
+    SharedFeed,
+    Import,
+}
+
+macro_rules! sql_enum {
+    ($ty:ident { $($variant:ident => $name:literal),+ $(,)? }) => {
+        impl $ty {
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $($ty::$variant => $name,)+
+                }
+            }
+        }
+
+        impl fmt::Display for $ty {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        impl FromStr for $ty {
+            type Err = String;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match s.trim() {
+                    $($name => Ok($ty::$variant),)+
+                    other => Err(format!("unknown {} '{}'", stringify!($ty), other)),
+                }
+            }
+        }
+    };
+}
+
+sql_enum!(EntryKind { Coin => "coin", Dev => "dev" });
+sql_enum!(Source {
+    Config => "config",
+    Manual => "manual",
+    AutoRule => "auto_rule",
+    SharedFeed => "shared_feed",
+    Import => "import",
+});
+
+/// One row of the `blacklist` table, also the record format used for
+/// CSV/JSON import and export and for remote feeds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlacklistEntry {
+    pub kind: EntryKind,
+    pub address: Address,
+    #[serde(default)]
+    pub reason: Option<String>,
+    #[serde(default = "default_source")]
+    pub source: Source,
+    /// Unix seconds after which the entry no longer applies.
+    #[serde(default)]
+    pub expires_at: Option<i64>,
+}
+
+fn default_source() -> Source {
+    Source::Manual
+}
+
+impl BlacklistEntry {
+    pub fn new(kind: EntryKind, address: Address, source: Source) -> Self {
+        BlacklistEntry {
+            kind,
+            address,
+            reason: None,
+            source,
+            expires_at: None,
+        }
+    }
+
+    pub fn with_reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+
+    pub fn expires_in(mut self, ttl: Duration) -> Self {
+        self.expires_at = Some(unix_now() + ttl.as_secs() as i64);
+        self
+    }
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+pub fn create_table(db: &Connection) -> rusqlite::Result<()> {
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS blacklist (
+            kind TEXT NOT NULL,
+            address TEXT NOT NULL,
+            reason TEXT,
+            source TEXT NOT NULL,
+            expires_at INTEGER,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (kind, address)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Adds or replaces an entry. Used for manual and auto-rule entries, which
+/// take precedence over anything already stored for the same address.
+pub fn upsert(db: &Connection, entry: &BlacklistEntry) -> rusqlite::Result<()> {
+    db.execute(
+        "INSERT OR REPLACE INTO blacklist (kind, address, reason, source, expires_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            entry.kind.as_str(),
+            entry.address,
+            entry.reason,
+            entry.source.as_str(),
+            entry.expires_at
+        ],
+    )?;
+    Ok(())
+}
+
+/// Returns the active entry for `address`, ignoring expired ones.
+pub fn lookup(
+    db: &Connection,
+    kind: EntryKind,
+    address: &Address,
+) -> rusqlite::Result<Option<BlacklistEntry>> {
+    db.query_row(
+        "SELECT kind, address, reason, source, expires_at FROM blacklist
+         WHERE kind = ?1 AND address = ?2 AND (expires_at IS NULL OR expires_at > ?3)",
+        params![kind.as_str(), address, unix_now()],
+        row_to_entry,
+    )
+    .optional()
+}
+
+/// Removes an entry regardless of its source. Returns whether one existed.
+pub fn remove(db: &Connection, kind: EntryKind, address: &Address) -> rusqlite::Result<bool> {
+    let removed = db.execute(
+        "DELETE FROM blacklist WHERE kind = ?1 AND address = ?2",
+        params![kind.as_str(), address],
+    )?;
+    Ok(removed > 0)
+}
+
+pub fn list(db: &Connection, include_expired: bool) -> rusqlite::Result<Vec<BlacklistEntry>> {
+    let mut stmt = db.prepare(
+        "SELECT kind, address, reason, source, expires_at FROM blacklist
+         WHERE ?1 OR expires_at IS NULL OR expires_at > ?2
+         ORDER BY kind, address",
+    )?;
+    let rows = stmt.query_map(params![include_expired, unix_now()], row_to_entry)?;
+    rows.collect()
+}
+
+pub fn purge_expired(db: &Connection) -> rusqlite::Result<usize> {
+    db.execute(
+        "DELETE FROM blacklist WHERE expires_at IS NOT NULL AND expires_at <= ?1",
+        params![unix_now()],
+    )
+}
+
+fn row_to_entry(row: &rusqlite::Row<'_>) -> rusqlite::Result<BlacklistEntry> {
+    let text_err = |idx: usize, e: String| {
+        rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, e.into())
+    };
+    let kind: String = row.get(0)?;
+    let source: String = row.get(3)?;
+    Ok(BlacklistEntry {
+        kind: kind.parse().map_err(|e| text_err(0, e))?,
+        address: row.get(1)?,
+        reason: row.get(2)?,
+        source: source.parse().map_err(|e| text_err(3, e))?,
+        expires_at: row.get(4)?,
+    })
+}
+
+/// Replaces every entry owned by `source` with `entries` in one transaction.
+///
+/// Entries already present under another source are left alone, so a
+/// manual entry is never downgraded by a config reload or feed sync.
+pub fn replace_source(
+    db: &mut Connection,
+    source: Source,
+    entries: &[BlacklistEntry],
+) -> rusqlite::Result<usize> {
+    let tx = db.transaction()?;
+    tx.execute("DELETE FROM blacklist WHERE source = ?1", params![source.as_str()])?;
+    let mut inserted = 0;
+    for entry in entries {
+        inserted += tx.execute(
+            "INSERT OR IGNORE INTO blacklist (kind, address, reason, source, expires_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                entry.kind.as_str(),
+                entry.address,
+                entry.reason,
+                source.as_str(),
+                entry.expires_at
+            ],
+        )?;
+    }
+    tx.commit()?;
+    Ok(inserted)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+impl Format {
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("csv") => Ok(Format::Csv),
+            Some("json") => Ok(Format::Json),
+            _ => Err(format!("cannot infer format of {} (expected .csv or .json)", path.display())),
+        }
+    }
+}
+
+pub fn parse_entries(data: &str, format: Format) -> Result<Vec<BlacklistEntry>, Box<dyn std::error::Error>> {
+    match format {
+        Format::Json => Ok(serde_json::from_str(data)?),
+        Format::Csv => {
+            let mut reader = csv::Reader::from_reader(data.as_bytes());
+            let entries = reader.deserialize().collect::<Result<Vec<_>, _>>()?;
+            Ok(entries)
+        }
+    }
+}
+
+pub fn render_entries(entries: &[BlacklistEntry], format: Format) -> Result<String, Box<dyn std::error::Error>> {
+    match format {
+        Format::Json => Ok(serde_json::to_string_pretty(entries)?),
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            for entry in entries {
+                writer.serialize(entry)?;
+            }
+            Ok(String::from_utf8(writer.into_inner()?)?)
+        }
+    }
+}
+
+/// Imports entries from a file under the `import` source, whatever source
+/// the file gives them. An entry already stored under another source is left
+/// alone; one from an earlier import is refreshed. Returns how many entries
+/// were stored.
+pub fn import_file(db: &Connection, path: &Path) -> Result<usize, Box<dyn std::error::Error>> {
+    let entries = parse_entries(&fs::read_to_string(path)?, Format::from_path(path)?)?;
+    let mut imported = 0;
+    for entry in &entries {
+        imported += db.execute(
+            "INSERT INTO blacklist (kind, address, reason, source, expires_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT (kind, address) DO UPDATE SET reason = excluded.reason, expires_at = excluded.expires_at
+             WHERE blacklist.source = excluded.source",
+            params![
+                entry.kind.as_str(),
+                entry.address,
+                entry.reason,
+                Source::Import.as_str(),
+                entry.expires_at
+            ],
+        )?;
+    }
+    Ok(imported)
+}
+
+pub fn export_file(db: &Connection, path: &Path) -> Result<usize, Box<dyn std::error::Error>> {
+    let entries = list(db, false)?;
+    fs::write(path, render_entries(&entries, Format::from_path(path)?)?)?;
+    Ok(entries.len())
+}
+
+/// Fetches a remote blacklist feed, a JSON array in the export format. The
+/// caller stores the result with [`replace_source`] under `shared_feed`;
+/// any `source` field the feed carries is ignored.
+pub async fn fetch_feed(url: &str) -> Result<Vec<BlacklistEntry>, Box<dyn std::error::Error>> {
+    let body = reqwest::Client::new()
+        .get(url)
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    parse_entries(&body, Format::Json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINT: &str = "So11111111111111111111111111111111111111112";
+    const OTHER: &str = "JUPyiwrYJFskUPiHa7hkeR8VUtAeFoSYbKedZNsDvCN";
+
+    fn import(db: &Connection, entries: serde_json::Value) -> usize {
+        let path = std::env::temp_dir().join(format!("blacklist_import_{}.json", std::process::id()));
+        fs::write(&path, entries.to_string()).unwrap();
+        let imported = import_file(db, &path).unwrap();
+        fs::remove_file(&path).unwrap();
+        imported
+    }
+
+    #[test]
+    fn imports_keep_entries_from_other_sources() {
+        let mut db = Connection::open_in_memory().unwrap();
+        create_table(&db).unwrap();
+        let manual = BlacklistEntry::new(EntryKind::Coin, MINT.parse().unwrap(), Source::Manual).with_reason("scam");
+        upsert(&db, &manual).unwrap();
+
+        let entries = serde_json::json!([
+            {"kind": "coin", "address": MINT, "reason": "from file", "expires_at": 1},
+            {"kind": "dev", "address": OTHER, "reason": "from file", "source": "manual"},
+        ]);
+        assert_eq!(import(&db, entries.clone()), 1);
+
+        let kept = lookup(&db, EntryKind::Coin, &manual.address).unwrap().unwrap();
+        assert_eq!((kept.source, kept.reason.as_deref(), kept.expires_at), (Source::Manual, Some("scam"), None));
+        let imported = lookup(&db, EntryKind::Dev, &OTHER.parse().unwrap()).unwrap().unwrap();
+        assert_eq!(imported.source, Source::Import);
+
+        // A feed sync only replaces its own rows
+        replace_source(&mut db, Source::SharedFeed, &[]).unwrap();
+        assert_eq!(list(&db, true).unwrap().len(), 2);
+
+        // A second import refreshes its own rows
+        let entries = serde_json::json!([{"kind": "dev", "address": OTHER, "reason": "updated"}]);
+        assert_eq!(import(&db, entries), 1);
+        let refreshed = lookup(&db, EntryKind::Dev, &imported.address).unwrap().unwrap();
+        assert_eq!(refreshed.reason.as_deref(), Some("updated"));
+    }
+}
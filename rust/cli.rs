@@ -0,0 +1,519 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use rusqlite::Connection;
+
+use crate::address::Address;
+use crate::blacklist::{self, BlacklistEntry, EntryKind, Source};
+use crate::risk::{self, KillSwitch};
+use crate::secrets::{self, Keystore, Secret};
+use crate::{coins, decisions, export, migrations, optimizer, paper, social, tracker, unix_now, Config};
+
+#[derive(Debug, Parser)]
+#[command(name = "pumpfun-bot", about = "Monitors PumpFun migrations and manages the bot database")]
+pub struct Cli {
+    /// SQLite database used by every command.
+    #[arg(long, global = true, default_value = "pumpfun.db")]
+    pub db: PathBuf,
+    /// Config file, read by `run` and `optimize`.
+    #[arg(long, global = true, default_value = "config.ini")]
+    pub config: String,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Monitor migrations and send alerts (the default).
+    Run,
+    /// Inspect coins that passed the filters.
+    #[command(subcommand)]
+    Coins(CoinsCommand),
+    /// Summarise pipeline decisions.
+    #[command(subcommand)]
+    Decisions(DecisionsCommand),
+    /// Manage blacklist entries.
+    #[command(subcommand)]
+    Blacklist(BlacklistCommand),
+    /// Dump a table to a file, or to stdout for csv and jsonl.
+    Export {
+        #[arg(long, default_value = "csv")]
+        format: export::Format,
+        #[arg(long, default_value = "coins")]
+        table: String,
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Search for filter settings against tracked coin outcomes.
+    Optimize {
+        #[arg(value_enum, default_value = "grid")]
+        search: SearchKind,
+        #[arg(long, default_value_t = 500)]
+        samples: usize,
+        #[arg(long, default_value_t = 42)]
+        seed: u64,
+        #[arg(long, default_value_t = 10)]
+        min_coins: usize,
+        /// Compare against this profile's filters instead of `[FILTERS]`.
+        #[arg(long)]
+        profile: Option<String>,
+    },
+    /// Show the paper-trading book of each profile.
+    Paper {
+        #[arg(long)]
+        profile: Option<String>,
+        /// List every position, not just the totals.
+        #[arg(long)]
+        positions: bool,
+    },
+    /// Inspect risk limits or flip the kill switch.
+    #[command(subcommand)]
+    Risk(RiskCommand),
+    /// Manage the encrypted keystore referenced by `secret:<name>` values.
+    Secrets {
+        /// Keystore file; keep it in line with `[SECRETS] KEYSTORE`.
+        #[arg(long, default_value = secrets::DEFAULT_KEYSTORE)]
+        keystore: PathBuf,
+        #[command(subcommand)]
+        command: SecretsCommand,
+    },
+    /// Database maintenance.
+    #[command(subcommand)]
+    Db(DbCommand),
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CoinsCommand {
+    /// Most recently saved coins.
+    List {
+        #[arg(long, default_value_t = 20)]
+        limit: u32,
+    },
+    /// Everything recorded about one coin.
+    Show { address: Address },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum DecisionsCommand {
+    /// Accept/reject totals and the most common rejection reasons.
+    Stats {
+        /// Only count decisions from the last N hours.
+        #[arg(long)]
+        hours: Option<i64>,
+        /// Only count one profile's decisions.
+        #[arg(long)]
+        profile: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum BlacklistCommand {
+    /// Add or replace a manual entry.
+    Add {
+        kind: EntryKind,
+        address: Address,
+        #[arg(long)]
+        reason: Option<String>,
+        /// Expire the entry after this many hours.
+        #[arg(long)]
+        ttl_hours: Option<u64>,
+    },
+    /// Remove an entry whatever its source.
+    Rm { kind: EntryKind, address: Address },
+    /// List active entries.
+    Ls {
+        /// Include expired entries.
+        #[arg(long)]
+        all: bool,
+    },
+    /// Import entries from a .csv or .json file.
+    Import { path: PathBuf },
+    /// Export active entries to a .csv or .json file.
+    Export { path: PathBuf },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum RiskCommand {
+    /// Kill switch state and each book's exposure.
+    Status,
+    /// Stop all new orders until `resume`.
+    Kill {
+        #[arg(long, default_value = "engaged from the CLI")]
+        reason: String,
+    },
+    /// Release the kill switch.
+    Resume,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SecretsCommand {
+    /// Create an empty keystore.
+    Init,
+    /// Add or replace a secret. The value is prompted for, so it never
+    /// lands in shell history.
+    Set {
+        name: String,
+        /// Read the value from the first line of stdin instead.
+        #[arg(long)]
+        stdin: bool,
+    },
+    /// Remove a secret.
+    Rm { name: String },
+    /// List secret names; values are never printed.
+    Ls,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum DbCommand {
+    /// Apply pending schema migrations.
+    Migrate,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum SearchKind {
+    Grid,
+    Random,
+}
+
+/// Opens the database and brings its schema up to date, so admin commands
+/// work against a file the bot has never run on.
+pub fn open_db(path: &Path) -> Result<Connection, Box<dyn std::error::Error>> {
+    let mut db = Connection::open(path)?;
+    migrations::migrate(&mut db)?;
+    Ok(db)
+}
+
+/// Runs every command except `run`, which stays in `main`.
+pub fn execute(cli: &Cli, command: &Command) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        Command::Run => Err("`run` is handled by main".into()),
+        Command::Coins(command) => coins_command(&open_db(&cli.db)?, command),
+        Command::Decisions(DecisionsCommand::Stats { hours, profile }) => {
+            decision_stats(&open_db(&cli.db)?, profile.as_deref(), *hours)
+        }
+        Command::Blacklist(command) => blacklist_command(&open_db(&cli.db)?, command),
+        Command::Export { format, table, out } => {
+            let n = export::export_table(&open_db(&cli.db)?, table, *format, out.as_deref())?;
+            eprintln!("Exported {} rows from {}", n, table);
+            Ok(())
+        }
+        Command::Optimize {
+            search,
+            samples,
+            seed,
+            min_coins,
+            profile,
+        } => {
+            let config = Config::load(&cli.config)?;
+            let filters = match profile {
+                Some(name) => {
+                    config
+                        .profiles
+                        .iter()
+                        .find(|p| &p.name == name)
+                        .ok_or_else(|| format!("no profile named '{}'", name))?
+                        .filters
+                }
+                None => config.filters,
+            };
+            let search = match search {
+                SearchKind::Grid => optimizer::Search::Grid,
+                SearchKind::Random => optimizer::Search::Random {
+                    samples: *samples,
+                    seed: *seed,
+                },
+            };
+            let options = optimizer::Options {
+                search,
+                min_coins: *min_coins,
+            };
+            optimizer::run(&open_db(&cli.db)?, filters, config.tracking.rug_ratio, &options)
+        }
+        Command::Paper { profile, positions } => paper_book(&open_db(&cli.db)?, profile.as_deref(), *positions),
+        Command::Risk(command) => risk_command(&open_db(&cli.db)?, command),
+        Command::Secrets { keystore, command } => secrets_command(keystore, command),
+        Command::Db(DbCommand::Migrate) => {
+            let mut db = Connection::open(&cli.db)?;
+            let (from, to) = migrations::migrate(&mut db)?;
+            if from == to {
+                println!("Schema is up to date (version {})", to);
+            } else {
+                println!("Migrated schema from version {} to {}", from, to);
+            }
+            Ok(())
+        }
+    }
+}
+
+fn coins_command(db: &Connection, command: &CoinsCommand) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        CoinsCommand::List { limit } => {
+            for coin in coins::recent(db, *limit)? {
+                println!(
+                    "{}  {:<10} {:<44}  liq {:>8.2}  fee {:>5.2}  holders {:>5}",
+                    coin.created_at,
+                    coin.symbol,
+                    coin.contract_address,
+                    coin.initial_liquidity,
+                    coin.creator_fee,
+                    coin.holders
+                );
+            }
+        }
+        CoinsCommand::Show { address } => show_coin(db, address)?,
+    }
+    Ok(())
+}
+
+fn show_coin(db: &Connection, address: &Address) -> Result<(), Box<dyn std::error::Error>> {
+    match coins::find(db, address)? {
+        Some(coin) => {
+            println!("{} ({}) {}", coin.name, coin.symbol, coin.contract_address);
+            println!("  creator    {}", coin.creator_wallet);
+            println!("  liquidity  {:.2}", coin.initial_liquidity);
+            println!("  fee        {:.2}", coin.creator_fee);
+            println!("  holders    {}", coin.holders);
+            println!("  saved at   {}", coin.created_at);
+            let links = [
+                ("website", &coin.website),
+                ("twitter", &coin.twitter),
+                ("telegram", &coin.telegram),
+                ("image", &coin.image_uri),
+            ];
+            for (label, value) in links {
+                if let Some(value) = value {
+                    println!("  {:<10} {}", label, value);
+                }
+            }
+            if let Some(description) = &coin.description {
+                println!("  {}", description);
+            }
+        }
+        None => println!("{} was never saved (it may have been rejected)", address),
+    }
+    let decisions = decisions::decisions_for(db, address)?;
+    if !decisions.is_empty() {
+        println!("Decisions:");
+        for d in decisions {
+            let verdict = if d.accepted { "accepted" } else { "rejected" };
+            println!("  {}  {:<12} {}  {}", d.decided_at, d.profile, verdict, d.reason);
+        }
+    }
+    let checks = decisions::recent_security_results(db, Some(&address.to_string()), 100)?;
+    if !checks.is_empty() {
+        println!("Security checks:");
+        for c in checks.iter().rev() {
+            let verdict = if c.passed { "pass" } else { "FAIL" };
+            println!("  {}  {:<4}  {}  {}", c.checked_at, verdict, c.check_name, c.detail.as_deref().unwrap_or(""));
+        }
+    }
+    let accounts = social::load(db, address)?;
+    if !accounts.is_empty() {
+        println!("Social:");
+        for account in accounts {
+            println!("  {}", account.describe());
+        }
+    }
+    let snapshots = tracker::load_snapshots(db, address)?;
+    if !snapshots.is_empty() {
+        println!("Tracking: {} snapshots", snapshots.len());
+    }
+    if let Some(o) = tracker::load_outcome(db, address)? {
+        println!(
+            "Outcome: peak {:.2}x, final {:.2}x, drawdown {:.0}%, rugged {}",
+            o.peak_multiple,
+            o.final_multiple,
+            o.max_drawdown * 100.0,
+            match o.rugged_after_secs {
+                Some(secs) => format!("after {}m", secs / 60),
+                None => "no".to_string(),
+            }
+        );
+    }
+    Ok(())
+}
+
+fn decision_stats(db: &Connection, profile: Option<&str>, hours: Option<i64>) -> Result<(), Box<dyn std::error::Error>> {
+    let since = hours.map(|h| {
+        (chrono::Utc::now() - chrono::Duration::hours(h))
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string()
+    });
+    let stats = decisions::stats(db, profile, since.as_deref())?;
+    let total = stats.accepted + stats.rejected;
+    if total == 0 {
+        println!("No decisions recorded.");
+        return Ok(());
+    }
+    println!(
+        "{} decisions: {} accepted ({:.1}%), {} rejected",
+        total,
+        stats.accepted,
+        stats.accepted as f64 * 100.0 / total as f64,
+        stats.rejected
+    );
+    for (reason, count) in &stats.reasons {
+        println!("  {:>6}  {:>5.1}%  {}", count, *count as f64 * 100.0 / total as f64, reason);
+    }
+    Ok(())
+}
+
+fn paper_book(db: &Connection, profile: Option<&str>, list: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let book = paper::book(db, profile)?;
+    if book.is_empty() {
+        println!("No paper positions.");
+        return Ok(());
+    }
+    let mut names: Vec<&str> = book.iter().map(|p| p.profile.as_str()).collect();
+    names.sort();
+    names.dedup();
+    for name in names {
+        let positions: Vec<&paper::Position> = book.iter().filter(|p| p.profile == name).collect();
+        let open = positions.iter().filter(|p| !p.closed).count();
+        let size: f64 = positions.iter().map(|p| p.size).sum();
+        let pnl = positions.iter().filter_map(|p| p.pnl()).fold(0.0, |total, pnl| total + pnl);
+        println!(
+            "{:<12} {:>4} positions ({} open)  size {:>8.2}  pnl {:>+8.3} ({:+.1}%)",
+            name,
+            positions.len(),
+            open,
+            size,
+            pnl,
+            if size > 0.0 { pnl * 100.0 / size } else { 0.0 }
+        );
+        if list {
+            for p in positions {
+                println!(
+                    "    {:<44} size {:>6.2}  {}  {}  {}",
+                    p.contract_address,
+                    p.size,
+                    p.multiple.map_or("unpriced".to_string(), |m| format!("{:.2}x", m)),
+                    if p.closed { "closed" } else { "open" },
+                    match (&p.executor, p.entry_price) {
+                        (Some(executor), Some(price)) => format!("{} @ {:.10}", executor, price),
+                        _ => "unfilled".to_string(),
+                    }
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn secrets_command(path: &Path, command: &SecretsCommand) -> Result<(), Box<dyn std::error::Error>> {
+    let open = || -> Result<Keystore, Box<dyn std::error::Error>> {
+        Ok(Keystore::open(path, &secrets::passphrase(false)?)?)
+    };
+    match command {
+        SecretsCommand::Init => {
+            let keystore = Keystore::create(path, &secrets::passphrase(true)?)?;
+            keystore.save()?;
+            println!("Created keystore {}", path.display());
+        }
+        SecretsCommand::Set { name, stdin } => {
+            let mut keystore = open()?;
+            let value = if *stdin {
+                let mut line = String::new();
+                std::io::stdin().read_line(&mut line)?;
+                line.trim_end_matches(['\r', '\n']).to_string()
+            } else {
+                rpassword::prompt_password(format!("Value for {}: ", name))?
+            };
+            if value.is_empty() {
+                return Err("value must not be empty".into());
+            }
+            keystore.set(name, &Secret::new(value));
+            keystore.save()?;
+            println!("Stored {}; reference it as {}{}", name, secrets::REFERENCE_PREFIX, name);
+        }
+        SecretsCommand::Rm { name } => {
+            let mut keystore = open()?;
+            if !keystore.remove(name) {
+                return Err(format!("no secret named '{}'", name).into());
+            }
+            keystore.save()?;
+            println!("Removed {}", name);
+        }
+        SecretsCommand::Ls => {
+            for name in open()?.names() {
+                println!("{}", name);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn risk_command(db: &Connection, command: &RiskCommand) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        RiskCommand::Status => println!("{}", risk::summary(db, unix_now())?),
+        RiskCommand::Kill { reason } => {
+            let switch = KillSwitch {
+                reason: reason.clone(),
+                set_by: "cli".to_string(),
+                set_at: unix_now(),
+            };
+            risk::set_kill_switch(db, Some(&switch))?;
+            println!("Kill switch engaged: {}", reason);
+        }
+        RiskCommand::Resume => {
+            risk::set_kill_switch(db, None)?;
+            println!("Kill switch released.");
+        }
+    }
+    Ok(())
+}
+
+fn blacklist_command(db: &Connection, command: &BlacklistCommand) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        BlacklistCommand::Add {
+            kind,
+            address,
+            reason,
+            ttl_hours,
+        } => {
+            let mut entry = BlacklistEntry::new(*kind, address.clone(), Source::Manual);
+            if let Some(reason) = reason {
+                entry = entry.with_reason(reason.clone());
+            }
+            if let Some(hours) = ttl_hours {
+                entry = entry.expires_in(Duration::from_secs(hours * 60 * 60));
+            }
+            blacklist::upsert(db, &entry)?;
+            println!("Blacklisted {} {}", kind, address);
+        }
+        BlacklistCommand::Rm { kind, address } => {
+            if blacklist::remove(db, *kind, address)? {
+                println!("Removed {} {}", kind, address);
+            } else {
+                return Err(format!("{} {} is not blacklisted", kind, address).into());
+            }
+        }
+        BlacklistCommand::Ls { all } => {
+            for entry in blacklist::list(db, *all)? {
+                let expires = entry
+                    .expires_at
+                    .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+                    .map(|t| format!("  expires {}", t.format("%Y-%m-%d %H:%M")))
+                    .unwrap_or_default();
+                println!(
+                    "{:<4} {:<44} {:<11} {}{}",
+                    entry.kind,
+                    entry.address,
+                    entry.source,
+                    entry.reason.as_deref().unwrap_or("-"),
+                    expires
+                );
+            }
+        }
+        BlacklistCommand::Import { path } => {
+            let n = blacklist::import_file(db, path)?;
+            println!("Imported {} blacklist entries from {}", n, path.display());
+        }
+        BlacklistCommand::Export { path } => {
+            let n = blacklist::export_file(db, path)?;
+            println!("Exported {} blacklist entries to {}", n, path.display());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+
+use crate::address::Address;
+
+/// A row of the `coins` table, i.e. a coin that passed the filters.
+#[derive(Debug, Serialize)]
+pub struct CoinRow {
+    pub contract_address: String,
+    pub name: String,
+    pub symbol: String,
+    pub creator_wallet: String,
+    pub migration_time: i64,
+    pub initial_liquidity: f64,
+    pub creator_fee: f64,
+    pub holders: i64,
+    pub created_at: String,
+    pub description: Option<String>,
+    pub image_uri: Option<String>,
+    pub website: Option<String>,
+    pub twitter: Option<String>,
+    pub telegram: Option<String>,
+    /// Perceptual hash of the image, for copycat detection.
+    pub image_hash: Option<String>,
+}
+
+const COLUMNS: &str = "contract_address, name, symbol, creator_wallet, migration_time,
+    initial_liquidity, creator_fee, holders, created_at, description, image_uri, website, twitter,
+    telegram, image_hash";
+
+pub fn create_table(db: &Connection) -> rusqlite::Result<()> {
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS coins (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            contract_address TEXT UNIQUE,
+            name TEXT,
+            symbol TEXT,
+            creator_wallet TEXT,
+            migration_time DATETIME,
+            initial_liquidity REAL,
+            creator_fee REAL,
+            holders INTEGER,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn row_to_coin(row: &rusqlite::Row<'_>) -> rusqlite::Result<CoinRow> {
+    Ok(CoinRow {
+        contract_address: row.get(0)?,
+        name: row.get(1)?,
+        symbol: row.get(2)?,
+        creator_wallet: row.get(3)?,
+        migration_time: row.get(4)?,
+        initial_liquidity: row.get(5)?,
+        creator_fee: row.get(6)?,
+        holders: row.get(7)?,
+        created_at: row.get(8)?,
+        description: row.get(9)?,
+        image_uri: row.get(10)?,
+        website: row.get(11)?,
+        twitter: row.get(12)?,
+        telegram: row.get(13)?,
+        image_hash: row.get(14)?,
+    })
+}
+
+pub fn recent(db: &Connection, limit: u32) -> rusqlite::Result<Vec<CoinRow>> {
+    let mut stmt = db.prepare(&format!("SELECT {} FROM coins ORDER BY id DESC LIMIT ?1", COLUMNS))?;
+    let rows = stmt.query_map(params![limit], row_to_coin)?;
+    rows.collect()
+}
+
+pub fn find(db: &Connection, address: &Address) -> rusqlite::Result<Option<CoinRow>> {
+    db.query_row(
+        &format!("SELECT {} FROM coins WHERE contract_address = ?1", COLUMNS),
+        params![address],
+        row_to_coin,
+    )
+    .optional()
+}
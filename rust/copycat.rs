@@ -0,0 +1,489 @@
+use std::fmt;
+use std::io::Cursor;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use reqwest::Url;
+use rusqlite::{params, Connection};
+
+use crate::address::Address;
+
+/// Tokens whose names and tickers are popular to imitate. Matching one of
+/// these flags a coin even when the bot has never saved the original.
+const WELL_KNOWN: &[(&str, &str)] = &[
+    ("Bitcoin", "BTC"),
+    ("Ethereum", "ETH"),
+    ("Solana", "SOL"),
+    ("Tether", "USDT"),
+    ("USD Coin", "USDC"),
+    ("Dogecoin", "DOGE"),
+    ("Shiba Inu", "SHIB"),
+    ("Pepe", "PEPE"),
+    ("Bonk", "BONK"),
+    ("dogwifhat", "WIF"),
+    ("Popcat", "POPCAT"),
+    ("Official Trump", "TRUMP"),
+    ("Jupiter", "JUP"),
+    ("Raydium", "RAY"),
+    ("Pudgy Penguins", "PENGU"),
+    ("Fartcoin", "FARTCOIN"),
+];
+
+/// `[COPYCAT]` settings.
+#[derive(Debug, Clone)]
+pub struct CopycatConfig {
+    /// Jaro-Winkler similarity of normalised names from which two coins
+    /// count as the same name.
+    pub name_threshold: f64,
+    /// Largest Hamming distance between image hashes that counts as the
+    /// same image, out of 64 bits.
+    pub image_distance: u32,
+    /// How many of the most recent saved coins to compare against.
+    pub history: u32,
+    pub fetch_images: bool,
+}
+
+impl Default for CopycatConfig {
+    fn default() -> Self {
+        CopycatConfig {
+            name_threshold: 0.92,
+            image_distance: 10,
+            history: 5000,
+            fetch_images: true,
+        }
+    }
+}
+
+/// A 64-bit difference hash of an image. Re-encoding, resizing or small
+/// edits change few bits, so near-identical images have a small distance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageHash(pub u64);
+
+impl ImageHash {
+    /// Hashes a decoded image: shrink to 9x8 grey pixels and record, row by
+    /// row, whether each pixel is brighter than its right neighbour.
+    pub fn of(image: &image::DynamicImage) -> Self {
+        let small = image
+            .resize_exact(9, 8, image::imageops::FilterType::Triangle)
+            .to_luma8();
+        let mut bits = 0u64;
+        for y in 0..8 {
+            for x in 0..8 {
+                let brighter = small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0];
+                bits = bits << 1 | brighter as u64;
+            }
+        }
+        ImageHash(bits)
+    }
+
+    pub fn distance(self, other: ImageHash) -> u32 {
+        (self.0 ^ other.0).count_ones()
+    }
+}
+
+impl fmt::Display for ImageHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl std::str::FromStr for ImageHash {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        u64::from_str_radix(s, 16).map(ImageHash)
+    }
+}
+
+/// Largest image body downloaded, checked against `Content-Length` and
+/// again while streaming.
+const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+/// Largest width or height decoded. Token images are a few hundred pixels.
+const MAX_IMAGE_SIDE: u32 = 4096;
+/// Decoder memory cap, well below a full `MAX_IMAGE_SIDE` square in RGBA.
+const MAX_DECODE_ALLOC: u64 = 64 * 1024 * 1024;
+
+/// Whether an address is reachable on the public internet. Image links
+/// come from coin creators, so anything local must not be fetched.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                // Shared address space used by carrier-grade NAT.
+                || (a == 100 && (64..128).contains(&b))
+                || a == 0)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(v4) => is_public(IpAddr::V4(v4)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // Unique local and link-local ranges.
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// The URL an image is fetched from: `https` links as they are, and IPFS
+/// links through a public gateway. Other schemes are refused.
+fn image_url(uri: &str) -> Result<Url, Box<dyn std::error::Error>> {
+    let url = match uri.strip_prefix("ipfs://") {
+        Some(cid) => Url::parse(&format!("https://ipfs.io/ipfs/{}", cid))?,
+        None => Url::parse(uri)?,
+    };
+    if url.scheme() != "https" {
+        return Err(format!("refusing to fetch a {} image link", url.scheme()).into());
+    }
+    Ok(url)
+}
+
+/// Decodes an image within `MAX_IMAGE_SIDE` and `MAX_DECODE_ALLOC`, so a
+/// small file claiming huge dimensions is rejected before it is expanded.
+fn decode_image(bytes: &[u8]) -> Result<image::DynamicImage, Box<dyn std::error::Error>> {
+    let mut limits = image::Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_SIDE);
+    limits.max_image_height = Some(MAX_IMAGE_SIDE);
+    limits.max_alloc = Some(MAX_DECODE_ALLOC);
+    let mut reader = image::ImageReader::new(Cursor::new(bytes)).with_guessed_format()?;
+    reader.limits(limits);
+    Ok(reader.decode()?)
+}
+
+/// Downloads and hashes a coin image. Only `https` and IPFS links are
+/// followed, never to a private or loopback address, and the download and
+/// the decoded image are both size-capped.
+pub async fn fetch_image_hash(uri: &str, timeout: Duration) -> Result<ImageHash, Box<dyn std::error::Error>> {
+    let url = image_url(uri)?;
+    let host = url.host_str().ok_or("image link has no host")?.to_string();
+    let port = url.port_or_known_default().unwrap_or(443);
+    // Resolve once, check every address and pin the connection to it, so
+    // the host cannot re-resolve to a private address for the request.
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.trim_matches(['[', ']']), port)).await?.collect();
+    let addr = *addrs.first().ok_or("image host did not resolve")?;
+    if let Some(private) = addrs.iter().find(|a| !is_public(a.ip())) {
+        return Err(format!("refusing to fetch an image from {}", private.ip()).into());
+    }
+    let client = reqwest::Client::builder()
+        .resolve(&host, addr)
+        // A redirect could lead anywhere, including back inside.
+        .redirect(reqwest::redirect::Policy::none())
+        .timeout(timeout)
+        .build()?;
+    let mut response = client.get(url).send().await?.error_for_status()?;
+    if response.content_length().is_some_and(|len| len > MAX_IMAGE_BYTES as u64) {
+        return Err(format!("image is larger than {} bytes", MAX_IMAGE_BYTES).into());
+    }
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if bytes.len() + chunk.len() > MAX_IMAGE_BYTES {
+            return Err(format!("image is larger than {} bytes", MAX_IMAGE_BYTES).into());
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(ImageHash::of(&decode_image(&bytes)?))
+}
+
+/// Lower-case letters and digits only, with look-alike digits read as
+/// letters, so "P3PE" and "pepe" compare equal and "$PEPE" drops its `$`.
+pub fn normalize(name: &str) -> String {
+    name.chars()
+        .filter_map(|c| match c.to_ascii_lowercase() {
+            '0' => Some('o'),
+            '1' => Some('i'),
+            '3' => Some('e'),
+            '4' => Some('a'),
+            '5' => Some('s'),
+            '7' => Some('t'),
+            c if c.is_alphanumeric() => Some(c),
+            _ => None,
+        })
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchKind {
+    Name,
+    Symbol,
+    Image,
+}
+
+impl fmt::Display for MatchKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            MatchKind::Name => "name",
+            MatchKind::Symbol => "symbol",
+            MatchKind::Image => "image",
+        })
+    }
+}
+
+/// A token the coin resembles.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match {
+    pub kind: MatchKind,
+    /// The well-known token's name, or the saved coin's address.
+    pub original: String,
+    /// Name similarity in [0, 1], or 1 minus the image distance over 64.
+    pub score: f64,
+}
+
+impl fmt::Display for Match {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} matches {} ({:.2})", self.kind, self.original, self.score)
+    }
+}
+
+/// What the detector knows about the coin under review.
+pub struct Candidate<'a> {
+    pub name: &'a str,
+    pub symbol: &'a str,
+    pub image_hash: Option<ImageHash>,
+}
+
+fn name_similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (normalize(a), normalize(b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    strsim::jaro_winkler(&a, &b)
+}
+
+/// A recently saved coin, as the detector compares against it.
+#[derive(Debug, Clone)]
+pub struct SavedCoin {
+    pub contract_address: String,
+    pub name: Option<String>,
+    pub image_hash: Option<ImageHash>,
+}
+
+/// The `limit` most recently saved coins other than `exclude`. Loading is
+/// kept apart from [`detect`] so the comparisons run off the database thread.
+pub fn recent(db: &Connection, exclude: &Address, limit: u32) -> rusqlite::Result<Vec<SavedCoin>> {
+    let mut stmt = db.prepare(
+        "SELECT contract_address, name, image_hash FROM coins
+         WHERE contract_address != ?1 ORDER BY id DESC LIMIT ?2",
+    )?;
+    let rows = stmt.query_map(params![exclude, limit], |row| {
+        let hash: Option<String> = row.get(2)?;
+        Ok(SavedCoin {
+            contract_address: row.get(0)?,
+            name: row.get(1)?,
+            image_hash: hash.and_then(|h| h.parse().ok()),
+        })
+    })?;
+    rows.collect()
+}
+
+/// Compares the coin against well-known tokens by name and ticker, and
+/// against `saved` coins by name and image. Tickers are not compared with
+/// saved coins: short tickers collide too often to mean much there.
+pub fn detect(config: &CopycatConfig, coin: &Candidate, saved: &[SavedCoin]) -> Vec<Match> {
+    let mut matches = Vec::new();
+    let symbol = normalize(coin.symbol);
+    for (name, ticker) in WELL_KNOWN {
+        let score = name_similarity(coin.name, name);
+        if score >= config.name_threshold {
+            matches.push(Match {
+                kind: MatchKind::Name,
+                original: name.to_string(),
+                score,
+            });
+        } else if !symbol.is_empty() && symbol == normalize(ticker) {
+            matches.push(Match {
+                kind: MatchKind::Symbol,
+                original: name.to_string(),
+                score: 1.0,
+            });
+        }
+    }
+    for other in saved {
+        let score = other.name.as_deref().map_or(0.0, |name| name_similarity(coin.name, name));
+        if score >= config.name_threshold {
+            matches.push(Match {
+                kind: MatchKind::Name,
+                original: other.contract_address.clone(),
+                score,
+            });
+        }
+        if let Some((ours, theirs)) = coin.image_hash.zip(other.image_hash) {
+            let distance = ours.distance(theirs);
+            if distance <= config.image_distance {
+                matches.push(Match {
+                    kind: MatchKind::Image,
+                    original: other.contract_address.clone(),
+                    score: 1.0 - distance as f64 / 64.0,
+                });
+            }
+        }
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_https_and_ipfs_links_are_fetched() {
+        assert_eq!(image_url("ipfs://QmCid").unwrap().as_str(), "https://ipfs.io/ipfs/QmCid");
+        assert!(image_url("https://example.com/logo.png").is_ok());
+        for uri in ["http://example.com/logo.png", "file:///etc/passwd", "ftp://example.com/a.png", "not a url"] {
+            assert!(image_url(uri).is_err(), "{}", uri);
+        }
+    }
+
+    #[test]
+    fn local_addresses_are_not_public() {
+        for ip in ["127.0.0.1", "10.0.0.8", "172.16.4.1", "192.168.1.1", "169.254.169.254", "100.64.0.1", "0.0.0.0"] {
+            assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["::1", "fd00::1", "fe80::1", "::ffff:127.0.0.1"] {
+            assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["1.1.1.1", "104.16.0.1", "2606:4700::1111"] {
+            assert!(is_public(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn oversized_images_are_not_decoded() {
+        let encode = |width, height| {
+            let mut png = Vec::new();
+            image::DynamicImage::new_luma8(width, height)
+                .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+                .unwrap();
+            png
+        };
+        assert!(decode_image(&encode(64, 64)).is_ok());
+        assert!(decode_image(&encode(MAX_IMAGE_SIDE + 1, 1)).is_err());
+    }
+
+    fn candidate<'a>(name: &'a str, symbol: &'a str, image_hash: Option<ImageHash>) -> Candidate<'a> {
+        Candidate {
+            name,
+            symbol,
+            image_hash,
+        }
+    }
+
+    fn hit(kind: MatchKind, original: &str, score: f64) -> Match {
+        Match {
+            kind,
+            original: original.to_string(),
+            score,
+        }
+    }
+
+    fn saved(address: &str, name: &str, image_hash: Option<ImageHash>) -> SavedCoin {
+        SavedCoin {
+            contract_address: address.to_string(),
+            name: Some(name.to_string()),
+            image_hash,
+        }
+    }
+
+    #[test]
+    fn names_are_normalized_before_comparing() {
+        assert_eq!(normalize("$P3PE"), "pepe");
+        assert_eq!(normalize("Shiba Inu"), "shibainu");
+        assert_eq!(normalize(" dog-wif-hat! "), "dogwifhat");
+        assert_eq!(normalize("B0NK 4 L1FE"), "bonkalife");
+        assert_eq!(normalize("ÉLAN"), "élan");
+        assert_eq!(normalize("$$$"), "");
+    }
+
+    #[test]
+    fn well_known_names_and_tickers_are_flagged() {
+        let config = CopycatConfig::default();
+        let found = detect(&config, &candidate("P3PE", "FROG", None), &[]);
+        assert_eq!(found, [hit(MatchKind::Name, "Pepe", 1.0)]);
+        let found = detect(&config, &candidate("Moon Frog", "$bonk", None), &[]);
+        assert_eq!(found, [hit(MatchKind::Symbol, "Bonk", 1.0)]);
+        assert!(detect(&config, &candidate("Moon Frog", "MFROG", None), &[]).is_empty());
+        // An empty name or ticker resembles nothing
+        assert!(detect(&config, &candidate("", "", None), &[]).is_empty());
+    }
+
+    #[test]
+    fn names_match_from_the_threshold_up() {
+        let score = name_similarity("Dogwifhat", "Dogwifcat");
+        assert!(score > 0.9 && score < 1.0, "{}", score);
+        let others = [saved("Other", "Dogwifcat", None)];
+        let at = CopycatConfig {
+            name_threshold: score,
+            ..CopycatConfig::default()
+        };
+        let found = detect(&at, &candidate("Dogwifhat", "DWH", None), &others);
+        assert!(found.iter().any(|m| m.kind == MatchKind::Name && m.original == "Other"), "{:?}", found);
+        let above = CopycatConfig {
+            name_threshold: score + 1e-9,
+            ..CopycatConfig::default()
+        };
+        let found = detect(&above, &candidate("Dogwifhat", "DWH", None), &others);
+        assert!(!found.iter().any(|m| m.original == "Other"), "{:?}", found);
+    }
+
+    #[test]
+    fn image_hashes_differ_by_their_hamming_distance() {
+        assert_eq!(ImageHash(0).distance(ImageHash(0b1011)), 3);
+        assert_eq!(ImageHash(u64::MAX).distance(ImageHash(0)), 64);
+        assert_eq!("00000000000000ff".parse::<ImageHash>().unwrap(), ImageHash(0xff));
+        assert_eq!(ImageHash(0xff).to_string(), "00000000000000ff");
+
+        let gradient = image::DynamicImage::ImageLuma8(image::ImageBuffer::from_fn(90, 80, |x, y| {
+            image::Luma([((x * 7 + y * 3) % 256) as u8])
+        }));
+        let hash = ImageHash::of(&gradient);
+        let resized = ImageHash::of(&gradient.resize_exact(180, 160, image::imageops::FilterType::Triangle));
+        assert!(hash.distance(resized) <= 4, "{}", hash.distance(resized));
+        let mut inverted = gradient.clone();
+        inverted.invert();
+        assert!(hash.distance(ImageHash::of(&inverted)) > 32);
+    }
+
+    #[test]
+    fn images_match_up_to_the_configured_distance() {
+        let config = CopycatConfig::default();
+        let ours = ImageHash(0);
+        let near = ImageHash((1 << config.image_distance) - 1);
+        let far = ImageHash((1 << (config.image_distance + 1)) - 1);
+        let others = [saved("Near", "Alpha", Some(near)), saved("Far", "Beta", Some(far))];
+        let found = detect(&config, &candidate("Zeta Quux", "ZQX", Some(ours)), &others);
+        let score = 1.0 - config.image_distance as f64 / 64.0;
+        assert_eq!(found, [hit(MatchKind::Image, "Near", score)]);
+    }
+
+    #[test]
+    fn a_coin_is_not_a_copy_of_itself() {
+        let mut db = Connection::open_in_memory().unwrap();
+        crate::migrations::migrate(&mut db).unwrap();
+        let own: Address = "So11111111111111111111111111111111111111112".parse().unwrap();
+        let other = "JUPyiwrYJFskUPiHa7hkeR8VUtAeFoSYbKedZNsDvCN";
+        let hash = ImageHash(0x0123_4567_89ab_cdef);
+        let rows = [(own.to_string(), "Moon Cat", hash), (other.to_string(), "Rocket", ImageHash(!hash.0))];
+        for (address, name, hash) in rows {
+            db.execute(
+                "INSERT INTO coins (contract_address, name, image_hash) VALUES (?1, ?2, ?3)",
+                params![address, name, hash.to_string()],
+            )
+            .unwrap();
+        }
+        let recent = recent(&db, &own, 10).unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].contract_address, other);
+        let found = detect(&CopycatConfig::default(), &candidate("Moon Cat", "MCAT", Some(hash)), &recent);
+        assert!(found.is_empty(), "{:?}", found);
+    }
+}
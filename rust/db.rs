@@ -0,0 +1,68 @@
+use std::fmt;
+use std::sync::mpsc;
+use std::thread;
+
+use rusqlite::Connection;
+use tokio::sync::oneshot;
+
+type Job = Box<dyn FnOnce(&mut Connection) + Send>;
+
+#[derive(Debug)]
+pub enum DbError {
+    /// The database thread has exited, so the job never ran.
+    Closed,
+    Sqlite(rusqlite::Error),
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbError::Closed => write!(f, "database thread has shut down"),
+            DbError::Sqlite(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+impl From<rusqlite::Error> for DbError {
+    fn from(e: rusqlite::Error) -> Self {
+        DbError::Sqlite(e)
+    }
+}
+
+/// Handle to a connection owned by a dedicated OS thread.
+///
+/// `rusqlite::Connection` is synchronous, so every query made from async
+/// code is shipped to that thread instead of blocking a tokio worker. Jobs
+/// run one at a time in submission order, which also serialises writes.
+#[derive(Clone)]
+pub struct DbHandle {
+    jobs: mpsc::Sender<Job>,
+}
+
+impl DbHandle {
+    pub fn spawn(mut conn: Connection) -> std::io::Result<Self> {
+        let (jobs, rx) = mpsc::channel::<Job>();
+        thread::Builder::new().name("db".to_string()).spawn(move || {
+            while let Ok(job) = rx.recv() {
+                job(&mut conn);
+            }
+        })?;
+        Ok(DbHandle { jobs })
+    }
+
+    pub async fn call<T, F>(&self, f: F) -> Result<T, DbError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        self.jobs
+            .send(Box::new(move |conn| {
+                let _ = tx.send(f(conn));
+            }))
+            .map_err(|_| DbError::Closed)?;
+        Ok(rx.await.map_err(|_| DbError::Closed)??)
+    }
+}
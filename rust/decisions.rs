@@ -0,0 +1,181 @@
+use rusqlite::{params, Connection};
+use serde::Serialize;
+
+use crate::address::Address;
+
+/// Why a profile accepted or rejected a coin, one row per coin and profile.
+#[derive(Debug, Clone, Serialize)]
+pub struct Decision {
+    pub profile: String,
+    pub contract_address: String,
+    pub symbol: String,
+    pub accepted: bool,
+    pub reason: String,
+    pub decided_at: String,
+}
+
+/// The result of one security check run against a coin.
+#[derive(Debug, Clone, Serialize)]
+pub struct SecurityResult {
+    pub contract_address: String,
+    pub check_name: String,
+    pub passed: bool,
+    pub detail: Option<String>,
+    pub checked_at: String,
+}
+
+pub fn create_tables(db: &Connection) -> rusqlite::Result<()> {
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS decisions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            contract_address TEXT NOT NULL,
+            symbol TEXT,
+            accepted INTEGER NOT NULL,
+            reason TEXT NOT NULL,
+            decided_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS security_results (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            contract_address TEXT NOT NULL,
+            check_name TEXT NOT NULL,
+            passed INTEGER NOT NULL,
+            detail TEXT,
+            checked_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+pub fn record_decision(
+    db: &Connection,
+    profile: &str,
+    address: &Address,
+    symbol: &str,
+    accepted: bool,
+    reason: &str,
+) -> rusqlite::Result<()> {
+    db.execute(
+        "INSERT INTO decisions (profile, contract_address, symbol, accepted, reason) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![profile, address, symbol, accepted, reason],
+    )?;
+    Ok(())
+}
+
+pub fn record_security(
+    db: &Connection,
+    address: &Address,
+    check_name: &str,
+    passed: bool,
+    detail: Option<&str>,
+) -> rusqlite::Result<()> {
+    db.execute(
+        "INSERT INTO security_results (contract_address, check_name, passed, detail) VALUES (?1, ?2, ?3, ?4)",
+        params![address, check_name, passed, detail],
+    )?;
+    Ok(())
+}
+
+pub fn recent_decisions(db: &Connection, limit: u32) -> rusqlite::Result<Vec<Decision>> {
+    let mut stmt = db.prepare(
+        "SELECT profile, contract_address, symbol, accepted, reason, decided_at FROM decisions
+         ORDER BY id DESC LIMIT ?1",
+    )?;
+    let rows = stmt.query_map(params![limit], row_to_decision)?;
+    rows.collect()
+}
+
+pub fn decisions_for(db: &Connection, address: &Address) -> rusqlite::Result<Vec<Decision>> {
+    let mut stmt = db.prepare(
+        "SELECT profile, contract_address, symbol, accepted, reason, decided_at FROM decisions
+         WHERE contract_address = ?1 ORDER BY id",
+    )?;
+    let rows = stmt.query_map(params![address], row_to_decision)?;
+    rows.collect()
+}
+
+fn row_to_decision(row: &rusqlite::Row<'_>) -> rusqlite::Result<Decision> {
+    Ok(Decision {
+        profile: row.get(0)?,
+        contract_address: row.get(1)?,
+        symbol: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+        accepted: row.get(3)?,
+        reason: row.get(4)?,
+        decided_at: row.get(5)?,
+    })
+}
+
+#[derive(Debug, Default)]
+pub struct DecisionStats {
+    pub accepted: i64,
+    pub rejected: i64,
+    /// Rejection counts by reason, most frequent first.
+    pub reasons: Vec<(String, i64)>,
+}
+
+/// Tallies decisions made at or after `since` (SQLite datetime text), or
+/// all of them, optionally for one profile. Reasons are grouped by
+/// [`reason_kind`].
+pub fn stats(db: &Connection, profile: Option<&str>, since: Option<&str>) -> rusqlite::Result<DecisionStats> {
+    let mut stmt = db.prepare(
+        "SELECT accepted, reason, COUNT(*) FROM decisions
+         WHERE (?1 IS NULL OR profile = ?1) AND (?2 IS NULL OR decided_at >= ?2)
+         GROUP BY accepted, reason",
+    )?;
+    let rows = stmt.query_map(params![profile, since], |row| {
+        Ok((row.get::<_, bool>(0)?, row.get::<_, String>(1)?, row.get::<_, i64>(2)?))
+    })?;
+    let mut stats = DecisionStats::default();
+    for row in rows {
+        let (accepted, reason, count) = row?;
+        if accepted {
+            stats.accepted += count;
+            continue;
+        }
+        stats.rejected += count;
+        let kind = reason_kind(&reason);
+        match stats.reasons.iter_mut().find(|(r, _)| *r == kind) {
+            Some((_, n)) => *n += count,
+            None => stats.reasons.push((kind, count)),
+        }
+    }
+    stats.reasons.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    Ok(stats)
+}
+
+/// Strips the measured values from a filter rejection, so that
+/// "liquidity 2.31 < 5" and "liquidity 0.4 < 5" count as one reason.
+pub fn reason_kind(reason: &str) -> String {
+    let end = reason.find(|c: char| c.is_ascii_digit()).unwrap_or(reason.len());
+    let kind = reason[..end].trim_end_matches(|c: char| c.is_whitespace() || "<>=.-".contains(c));
+    if kind.is_empty() {
+        reason.to_string()
+    } else {
+        kind.to_string()
+    }
+}
+
+pub fn recent_security_results(
+    db: &Connection,
+    address: Option<&str>,
+    limit: u32,
+) -> rusqlite::Result<Vec<SecurityResult>> {
+    let mut stmt = db.prepare(
+        "SELECT contract_address, check_name, passed, detail, checked_at FROM security_results
+         WHERE ?1 IS NULL OR contract_address = ?1
+         ORDER BY id DESC LIMIT ?2",
+    )?;
+    let rows = stmt.query_map(params![address, limit], |row| {
+        Ok(SecurityResult {
+            contract_address: row.get(0)?,
+            check_name: row.get(1)?,
+            passed: row.get(2)?,
+            detail: row.get(3)?,
+            checked_at: row.get(4)?,
+        })
+    })?;
+    rows.collect()
+}
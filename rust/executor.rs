@@ -0,0 +1,370 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use futures::future::{self, BoxFuture};
+
+use crate::address::Address;
+
+/// Tokens in a PumpFun pool at migration. With the SOL side taken from the
+/// coin's reported liquidity this gives the pool an order was quoted from.
+pub const MIGRATION_TOKEN_RESERVE: f64 = 206_900_000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    /// Spend SOL for tokens.
+    Buy,
+    /// Spend tokens for SOL.
+    Sell,
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Side::Buy => "buy",
+            Side::Sell => "sell",
+        })
+    }
+}
+
+/// Reserves of a constant-product pool, SOL on one side and the coin on
+/// the other.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pool {
+    pub sol_reserve: f64,
+    pub token_reserve: f64,
+}
+
+impl Pool {
+    /// The pool of a freshly migrated coin holding `liquidity` SOL.
+    pub fn migrated(liquidity: f64) -> Self {
+        Pool {
+            sol_reserve: liquidity,
+            token_reserve: MIGRATION_TOKEN_RESERVE,
+        }
+    }
+
+    /// SOL per token.
+    pub fn spot_price(&self) -> f64 {
+        self.sol_reserve / self.token_reserve
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TradeRequest {
+    pub contract_address: Address,
+    pub side: Side,
+    /// SOL for a buy, tokens for a sell.
+    pub amount_in: f64,
+    /// The pool as last observed, which the order is quoted against.
+    pub quote: Pool,
+    pub max_slippage_bps: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fill {
+    pub executor: &'static str,
+    pub side: Side,
+    pub amount_in: f64,
+    pub amount_out: f64,
+    /// Paid out of `amount_in`.
+    pub fee: f64,
+    /// Effective SOL per token.
+    pub price: f64,
+    /// Shortfall against a fill at the quoted spot price, after fees.
+    pub slippage_bps: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExecError {
+    InvalidAmount,
+    /// The pool has no liquidity on one side.
+    EmptyPool,
+    SlippageExceeded { limit_bps: u32, actual_bps: f64 },
+    Unsupported(&'static str),
+}
+
+impl fmt::Display for ExecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecError::InvalidAmount => write!(f, "amount must be positive"),
+            ExecError::EmptyPool => write!(f, "pool has no liquidity"),
+            ExecError::SlippageExceeded { limit_bps, actual_bps } => {
+                write!(f, "slippage {:.0}bps exceeds limit {}bps", actual_bps, limit_bps)
+            }
+            ExecError::Unsupported(what) => write!(f, "{}", what),
+        }
+    }
+}
+
+impl std::error::Error for ExecError {}
+
+/// Places trades. Implementations decide how far a trade is from real: the
+/// dry run only prices it, the simulator tracks pool state, and an on-chain
+/// backend would submit a transaction.
+pub trait Executor: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn execute<'a>(&'a self, request: &'a TradeRequest) -> BoxFuture<'a, Result<Fill, ExecError>>;
+}
+
+/// Fills every order at the quoted spot price, with no fee or price impact.
+pub struct DryRun;
+
+impl DryRun {
+    fn fill(&self, request: &TradeRequest) -> Result<Fill, ExecError> {
+        if request.amount_in <= 0.0 {
+            return Err(ExecError::InvalidAmount);
+        }
+        if request.quote.sol_reserve <= 0.0 || request.quote.token_reserve <= 0.0 {
+            return Err(ExecError::EmptyPool);
+        }
+        let price = request.quote.spot_price();
+        let amount_out = match request.side {
+            Side::Buy => request.amount_in / price,
+            Side::Sell => request.amount_in * price,
+        };
+        Ok(Fill {
+            executor: self.name(),
+            side: request.side,
+            amount_in: request.amount_in,
+            amount_out,
+            fee: 0.0,
+            price,
+            slippage_bps: 0.0,
+        })
+    }
+}
+
+impl Executor for DryRun {
+    fn name(&self) -> &'static str {
+        "dry_run"
+    }
+
+    fn execute<'a>(&'a self, request: &'a TradeRequest) -> BoxFuture<'a, Result<Fill, ExecError>> {
+        Box::pin(future::ready(self.fill(request)))
+    }
+}
+
+/// A local constant-product (x * y = k) market. Each coin's pool is seeded
+/// from the first quote seen for it and then moves only with the simulator's
+/// own trades, so results are deterministic for a given order sequence.
+pub struct AmmSimulator {
+    fee_bps: u32,
+    pools: Mutex<HashMap<Address, Pool>>,
+}
+
+impl AmmSimulator {
+    pub fn new(fee_bps: u32) -> Self {
+        AmmSimulator {
+            fee_bps,
+            pools: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn swap(&self, request: &TradeRequest) -> Result<Fill, ExecError> {
+        if request.amount_in <= 0.0 {
+            return Err(ExecError::InvalidAmount);
+        }
+        let mut pools = self.pools.lock().unwrap();
+        let pool = pools.entry(request.contract_address.clone()).or_insert(request.quote);
+        let (reserve_in, reserve_out) = match request.side {
+            Side::Buy => (pool.sol_reserve, pool.token_reserve),
+            Side::Sell => (pool.token_reserve, pool.sol_reserve),
+        };
+        if reserve_in <= 0.0 || reserve_out <= 0.0 {
+            return Err(ExecError::EmptyPool);
+        }
+        let fee = request.amount_in * self.fee_bps as f64 / 10_000.0;
+        let net_in = request.amount_in - fee;
+        let amount_out = reserve_out * net_in / (reserve_in + net_in);
+        let at_spot = request.amount_in * reserve_out / reserve_in;
+        let slippage_bps = (at_spot - amount_out) / at_spot * 10_000.0;
+        if slippage_bps > request.max_slippage_bps as f64 {
+            return Err(ExecError::SlippageExceeded {
+                limit_bps: request.max_slippage_bps,
+                actual_bps: slippage_bps,
+            });
+        }
+        // The fee stays in the pool, as on Uniswap-style AMMs.
+        match request.side {
+            Side::Buy => {
+                pool.sol_reserve += request.amount_in;
+                pool.token_reserve -= amount_out;
+            }
+            Side::Sell => {
+                pool.token_reserve += request.amount_in;
+                pool.sol_reserve -= amount_out;
+            }
+        }
+        let price = match request.side {
+            Side::Buy => request.amount_in / amount_out,
+            Side::Sell => amount_out / request.amount_in,
+        };
+        Ok(Fill {
+            executor: self.name(),
+            side: request.side,
+            amount_in: request.amount_in,
+            amount_out,
+            fee,
+            price,
+            slippage_bps,
+        })
+    }
+}
+
+impl Executor for AmmSimulator {
+    fn name(&self) -> &'static str {
+        "simulator"
+    }
+
+    fn execute<'a>(&'a self, request: &'a TradeRequest) -> BoxFuture<'a, Result<Fill, ExecError>> {
+        Box::pin(future::ready(self.swap(request)))
+    }
+}
+
+/// Stand-in for a backend that signs and submits swaps. It refuses every
+/// order so that selecting it can never silently fall back to paper fills.
+pub struct OnChain;
+
+impl Executor for OnChain {
+    fn name(&self) -> &'static str {
+        "onchain"
+    }
+
+    fn execute<'a>(&'a self, _request: &'a TradeRequest) -> BoxFuture<'a, Result<Fill, ExecError>> {
+        Box::pin(future::ready(Err(ExecError::Unsupported(
+            "on-chain execution is not implemented yet",
+        ))))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    DryRun,
+    Simulator,
+    OnChain,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "dry_run" => Ok(Backend::DryRun),
+            "simulator" => Ok(Backend::Simulator),
+            "onchain" => Ok(Backend::OnChain),
+            other => Err(format!(
+                "unknown execution backend '{}' (expected dry_run, simulator or onchain)",
+                other
+            )),
+        }
+    }
+}
+
+/// `[EXECUTION]` settings.
+#[derive(Debug, Clone)]
+pub struct ExecutionConfig {
+    pub backend: Backend,
+    /// Swap fee charged by the simulator.
+    pub fee_bps: u32,
+    pub max_slippage_bps: u32,
+}
+
+impl Default for ExecutionConfig {
+    fn default() -> Self {
+        ExecutionConfig {
+            backend: Backend::DryRun,
+            fee_bps: 25,
+            max_slippage_bps: 500,
+        }
+    }
+}
+
+pub fn build(config: &ExecutionConfig) -> Arc<dyn Executor> {
+    match config.backend {
+        Backend::DryRun => Arc::new(DryRun),
+        Backend::Simulator => Arc::new(AmmSimulator::new(config.fee_bps)),
+        Backend::OnChain => Arc::new(OnChain),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(side: Side, amount_in: f64, max_slippage_bps: u32) -> TradeRequest {
+        TradeRequest {
+            contract_address: Address::evm_zero(),
+            side,
+            amount_in,
+            quote: Pool {
+                sol_reserve: 100.0,
+                token_reserve: 1_000_000.0,
+            },
+            max_slippage_bps,
+        }
+    }
+
+    fn approx(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6 * b.abs().max(1.0)
+    }
+
+    #[test]
+    fn swaps_keep_the_constant_product() {
+        let amm = AmmSimulator::new(0);
+        let buy = amm.swap(&request(Side::Buy, 10.0, 10_000)).unwrap();
+        assert!(approx(buy.amount_out, 1_000_000.0 * 10.0 / 110.0));
+        assert!(approx(buy.price, 10.0 / buy.amount_out));
+        let pool = amm.pools.lock().unwrap()[&Address::evm_zero()];
+        assert!(approx(pool.sol_reserve * pool.token_reserve, 100.0 * 1_000_000.0));
+
+        // Selling the tokens back into a fee-free pool returns the SOL.
+        let sell = amm.swap(&request(Side::Sell, buy.amount_out, 10_000)).unwrap();
+        assert!(approx(sell.amount_out, 10.0));
+    }
+
+    #[test]
+    fn fee_is_deducted_from_the_input_and_stays_in_the_pool() {
+        let amm = AmmSimulator::new(100);
+        let fill = amm.swap(&request(Side::Buy, 10.0, 10_000)).unwrap();
+        assert!(approx(fill.fee, 0.1));
+        assert!(approx(fill.amount_out, 1_000_000.0 * 9.9 / 109.9));
+        let pool = amm.pools.lock().unwrap()[&Address::evm_zero()];
+        assert!(approx(pool.sol_reserve, 110.0));
+        assert!(approx(pool.token_reserve, 1_000_000.0 - fill.amount_out));
+    }
+
+    #[test]
+    fn slippage_over_the_limit_is_rejected_without_moving_the_pool() {
+        let amm = AmmSimulator::new(0);
+        // 10 SOL into 100 moves the price by about 909bps.
+        match amm.swap(&request(Side::Buy, 10.0, 500)) {
+            Err(ExecError::SlippageExceeded { limit_bps, actual_bps }) => {
+                assert_eq!(limit_bps, 500);
+                assert!(approx(actual_bps, 10_000.0 / 11.0));
+            }
+            other => panic!("expected a slippage error, got {:?}", other),
+        }
+        let fill = amm.swap(&request(Side::Buy, 10.0, 1_000)).unwrap();
+        assert!(approx(fill.amount_out, 1_000_000.0 * 10.0 / 110.0));
+        assert_eq!(amm.swap(&request(Side::Buy, 0.0, 1_000)), Err(ExecError::InvalidAmount));
+    }
+
+    #[tokio::test]
+    async fn dry_run_fills_at_the_quoted_spot_price() {
+        let buy = DryRun.execute(&request(Side::Buy, 1.0, 0)).await.unwrap();
+        assert_eq!(buy.executor, "dry_run");
+        assert!(approx(buy.amount_out, 10_000.0));
+        assert!(approx(buy.price, 0.0001));
+        assert_eq!((buy.fee, buy.slippage_bps), (0.0, 0.0));
+
+        let sell = DryRun.execute(&request(Side::Sell, 10_000.0, 0)).await.unwrap();
+        assert!(approx(sell.amount_out, 1.0));
+
+        let mut empty = request(Side::Buy, 1.0, 0);
+        empty.quote.sol_reserve = 0.0;
+        assert_eq!(DryRun.execute(&empty).await, Err(ExecError::EmptyPool));
+    }
+}
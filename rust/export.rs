@@ -0,0 +1,145 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use rusqlite::types::ValueRef;
+use rusqlite::Connection;
+use serde_json::{Map, Value};
+
+/// Tables that may be exported. The name is interpolated into SQL, so it
+/// must come from this list rather than straight from the command line.
+pub const TABLES: &[&str] = &[
+    "coins",
+    "decisions",
+    "security_results",
+    "blacklist",
+    "coin_snapshots",
+    "coin_outcomes",
+    "paper_positions",
+    "risk_decisions",
+    "twitter_metrics",
+    "twitter_posts",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Jsonl,
+    Parquet,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Format::Csv),
+            "jsonl" => Ok(Format::Jsonl),
+            "parquet" => Ok(Format::Parquet),
+            other => Err(format!("unknown export format '{}' (expected csv, jsonl or parquet)", other)),
+        }
+    }
+}
+
+type Row = Map<String, Value>;
+
+/// Reads every row of `table` as a JSON object keyed by column name.
+fn load_rows(db: &Connection, table: &str) -> Result<(Vec<String>, Vec<Row>), Box<dyn std::error::Error>> {
+    if !TABLES.contains(&table) {
+        return Err(format!("cannot export '{}' (expected one of: {})", table, TABLES.join(", ")).into());
+    }
+    let mut stmt = db.prepare(&format!("SELECT * FROM {}", table))?;
+    let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+    let mut rows = stmt.query([])?;
+    let mut out = Vec::new();
+    while let Some(row) = rows.next()? {
+        let mut object = Map::new();
+        for (i, name) in columns.iter().enumerate() {
+            let value = match row.get_ref(i)? {
+                ValueRef::Null => Value::Null,
+                ValueRef::Integer(n) => n.into(),
+                ValueRef::Real(x) => x.into(),
+                ValueRef::Text(t) => String::from_utf8_lossy(t).into_owned().into(),
+                ValueRef::Blob(b) => b.iter().map(|byte| format!("{:02x}", byte)).collect::<String>().into(),
+            };
+            object.insert(name.clone(), value);
+        }
+        out.push(object);
+    }
+    Ok((columns, out))
+}
+
+/// Writes `table` to `out`, or to stdout when no path is given. Returns the
+/// number of rows written.
+pub fn export_table(
+    db: &Connection,
+    table: &str,
+    format: Format,
+    out: Option<&Path>,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let (columns, rows) = load_rows(db, table)?;
+    match format {
+        Format::Csv => write_csv(&columns, &rows, open_output(out)?)?,
+        Format::Jsonl => write_jsonl(&rows, open_output(out)?)?,
+        Format::Parquet => write_parquet(&rows, out.ok_or("parquet export needs --out <file>")?)?,
+    }
+    Ok(rows.len())
+}
+
+fn open_output(out: Option<&Path>) -> io::Result<Box<dyn Write>> {
+    Ok(match out {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(io::stdout().lock()),
+    })
+}
+
+fn write_csv(columns: &[String], rows: &[Row], out: Box<dyn Write>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut writer = csv::Writer::from_writer(out);
+    writer.write_record(columns)?;
+    for row in rows {
+        writer.write_record(columns.iter().map(|c| match &row[c] {
+            Value::Null => String::new(),
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        }))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_jsonl(rows: &[Row], mut out: Box<dyn Write>) -> Result<(), Box<dyn std::error::Error>> {
+    for row in rows {
+        serde_json::to_writer(&mut out, row)?;
+        out.write_all(b"\n")?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(feature = "parquet")]
+fn write_parquet(rows: &[Row], path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    use std::sync::Arc;
+
+    use arrow_json::reader::{infer_json_schema_from_iterator, ReaderBuilder};
+    use parquet::arrow::ArrowWriter;
+
+    let schema = Arc::new(infer_json_schema_from_iterator(
+        rows.iter().map(|row| Ok(Value::Object(row.clone()))),
+    )?);
+    let mut decoder = ReaderBuilder::new(schema.clone())
+        .with_batch_size(rows.len().max(1))
+        .build_decoder()?;
+    decoder.serialize(rows)?;
+    let mut writer = ArrowWriter::try_new(File::create(path)?, schema, None)?;
+    if let Some(batch) = decoder.flush()? {
+        writer.write(&batch)?;
+    }
+    writer.close()?;
+    Ok(())
+}
+
+#[cfg(not(feature = "parquet"))]
+fn write_parquet(_rows: &[Row], _path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    Err("parquet export requires building with `--features parquet`".into())
+}
@@ -0,0 +1,132 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::social::{self, SocialMetrics};
+
+/// The `[FILTERS]` thresholds a coin must pass before it is alerted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Filters {
+    pub min_liquidity: f64,
+    pub max_creator_fee: f64,
+    pub min_holders: i64,
+    pub block_new_coins_minutes: u64,
+}
+
+impl Default for Filters {
+    fn default() -> Self {
+        Filters {
+            min_liquidity: 5.0,
+            max_creator_fee: 10.0,
+            min_holders: 25,
+            block_new_coins_minutes: 10,
+        }
+    }
+}
+
+impl Filters {
+    /// `age` is how long after migration the coin was observed; `None` when
+    /// the migration time lies in the future or is unknown.
+    pub fn passes(&self, liquidity: f64, creator_fee: f64, holders: i64, age: Option<Duration>) -> bool {
+        self.rejection(liquidity, creator_fee, holders, age).is_none()
+    }
+
+    /// Like [`Filters::passes`], but names the first filter the coin fails.
+    pub fn rejection(
+        &self,
+        liquidity: f64,
+        creator_fee: f64,
+        holders: i64,
+        age: Option<Duration>,
+    ) -> Option<String> {
+        if liquidity < self.min_liquidity {
+            return Some(format!("liquidity {:.2} < {}", liquidity, self.min_liquidity));
+        }
+        if creator_fee > self.max_creator_fee {
+            return Some(format!("creator fee {:.2} > {}", creator_fee, self.max_creator_fee));
+        }
+        if holders < self.min_holders {
+            return Some(format!("holders {} < {}", holders, self.min_holders));
+        }
+        if let Some(age) = age {
+            if age < Duration::from_secs(self.block_new_coins_minutes * 60) {
+                return Some(format!(
+                    "migrated {}m ago < {}m",
+                    age.as_secs() / 60,
+                    self.block_new_coins_minutes
+                ));
+            }
+        }
+        None
+    }
+}
+
+/// Renders the filters as a `[FILTERS]` config block.
+impl fmt::Display for Filters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "[FILTERS]")?;
+        writeln!(f, "MIN_LIQUIDITY = {}", self.min_liquidity)?;
+        writeln!(f, "MAX_CREATOR_FEE = {}", self.max_creator_fee)?;
+        writeln!(f, "MIN_HOLDERS = {}", self.min_holders)?;
+        write!(f, "BLOCK_NEW_COINS_MINUTES = {}", self.block_new_coins_minutes)
+    }
+}
+
+/// Thresholds on a coin's social accounts. The defaults disable every one,
+/// so coins without linked accounts pass unless a threshold is set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SocialFilters {
+    pub min_followers: i64,
+    pub min_account_age_days: i64,
+    /// Highest share of bot-like followers tolerated, from 0 to 1.
+    pub max_bot_follower_ratio: f64,
+    pub min_engagement_rate: f64,
+}
+
+impl Default for SocialFilters {
+    fn default() -> Self {
+        SocialFilters {
+            min_followers: 0,
+            min_account_age_days: 0,
+            max_bot_follower_ratio: 1.0,
+            min_engagement_rate: 0.0,
+        }
+    }
+}
+
+impl SocialFilters {
+    pub fn enabled(&self) -> bool {
+        *self != SocialFilters::default()
+    }
+
+    /// Names the first threshold the coin's primary account fails. Metrics
+    /// a provider could not supply, such as engagement without posts, are
+    /// not held against the coin.
+    pub fn rejection(&self, metrics: &[SocialMetrics]) -> Option<String> {
+        if !self.enabled() {
+            return None;
+        }
+        let Some(account) = social::primary(metrics) else {
+            return Some("no social accounts".to_string());
+        };
+        if account.followers < self.min_followers {
+            return Some(format!("followers {} < {}", account.followers, self.min_followers));
+        }
+        if account.account_age_days < self.min_account_age_days {
+            return Some(format!(
+                "social account {}d old < {}d",
+                account.account_age_days, self.min_account_age_days
+            ));
+        }
+        if let Some(ratio) = account.bot_follower_ratio {
+            if ratio > self.max_bot_follower_ratio {
+                return Some(format!("bot-like followers {:.2} > {}", ratio, self.max_bot_follower_ratio));
+            }
+        }
+        if let Some(rate) = account.engagement_rate {
+            if rate < self.min_engagement_rate {
+                return Some(format!("engagement {:.4} < {}", rate, self.min_engagement_rate));
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,140 @@
+use std::time::Duration;
+
+use futures::StreamExt;
+use serde_json::Value;
+use tokio::sync::{mpsc, watch};
+use tokio::time::sleep;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::RawCoinData;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IngestMode {
+    Poll,
+    Stream,
+}
+
+impl std::str::FromStr for IngestMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "poll" => Ok(IngestMode::Poll),
+            "stream" => Ok(IngestMode::Stream),
+            other => Err(format!("unknown INGEST_MODE '{}' (expected poll or stream)", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IngestConfig {
+    pub mode: IngestMode,
+    pub stream_url: String,
+    pub page_size: u64,
+    pub max_backfill_pages: u64,
+}
+
+impl Default for IngestConfig {
+    fn default() -> Self {
+        IngestConfig {
+            mode: IngestMode::Poll,
+            stream_url: "wss://api.pump.fun/migrations/stream".to_string(),
+            page_size: 50,
+            max_backfill_pages: 20,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum StreamEvent {
+    /// The socket is (re)connected. Anything migrated while it was down has
+    /// to be backfilled over REST.
+    Connected,
+    Coin(Box<RawCoinData>),
+    Disconnected,
+}
+
+/// Accepts either a bare migration object or one wrapped as `{"data": ...}`.
+fn parse_event(text: &str) -> Option<RawCoinData> {
+    let mut value: Value = serde_json::from_str(text).ok()?;
+    if let Some(data) = value.get_mut("data") {
+        value = data.take();
+    }
+    serde_json::from_value(value).ok()
+}
+
+/// Keeps a WebSocket subscription to the migration feed open until
+/// `shutdown` fires, reconnecting with exponential backoff.
+pub fn spawn_stream(
+    url: String,
+    api_key: String,
+    mut shutdown: watch::Receiver<bool>,
+) -> mpsc::Receiver<StreamEvent> {
+    let (tx, rx) = mpsc::channel(256);
+    tokio::spawn(async move {
+        let mut backoff = Duration::from_secs(1);
+        while !*shutdown.borrow() {
+            let mut request = match url.as_str().into_client_request() {
+                Ok(request) => request,
+                Err(e) => {
+                    println!("[STREAM] Invalid STREAM_URL {}: {}", url, e);
+                    return;
+                }
+            };
+            if let Ok(value) = HeaderValue::from_str(&format!("Bearer {}", api_key)) {
+                request.headers_mut().insert("Authorization", value);
+            }
+            match tokio_tungstenite::connect_async(request).await {
+                Ok((mut socket, _)) => {
+                    println!("[STREAM] Connected to {}", url);
+                    backoff = Duration::from_secs(1);
+                    if tx.send(StreamEvent::Connected).await.is_err() {
+                        return;
+                    }
+                    loop {
+                        let msg = tokio::select! {
+                            msg = socket.next() => msg,
+                            _ = shutdown.changed() => return,
+                        };
+                        match msg {
+                            Some(Ok(Message::Text(text))) => {
+                                if let Some(coin) = parse_event(&text) {
+                                    if tx.send(StreamEvent::Coin(Box::new(coin))).await.is_err() {
+                                        return;
+                                    }
+                                }
+                            }
+                            Some(Ok(Message::Close(_))) | None => break,
+                            Some(Ok(_)) => {}
+                            Some(Err(e)) => {
+                                println!("[STREAM] {}", e);
+                                break;
+                            }
+                        }
+                    }
+                    if tx.send(StreamEvent::Disconnected).await.is_err() {
+                        return;
+                    }
+                }
+                Err(e) => println!("[STREAM] Connect failed: {}", e),
+            }
+            println!("[STREAM] Reconnecting in {}s", backoff.as_secs());
+            tokio::select! {
+                _ = sleep(backoff) => {}
+                _ = shutdown.changed() => return,
+            }
+            backoff = (backoff * 2).min(Duration::from_secs(60));
+        }
+    });
+    rx
+}
+
+/// Waits for the next stream event; never resolves when there is no stream.
+pub async fn next_event(stream: &mut Option<mpsc::Receiver<StreamEvent>>) -> Option<StreamEvent> {
+    match stream {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
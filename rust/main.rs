@@ -1,37 +1,102 @@
+mod address;
+mod api;
+mod blacklist;
+mod cli;
+mod coins;
+mod copycat;
+mod db;
+mod decisions;
+mod executor;
+mod export;
+mod filters;
+mod ingest;
+mod migrations;
+mod optimizer;
+mod paper;
+mod pipeline;
+mod profiles;
+mod risk;
+mod secrets;
+mod social;
+mod state;
+mod telegram;
+mod tracker;
+
 use std::fs;
 use std::path::Path;
+use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use rusqlite::{params, Connection};
+use clap::Parser;
+use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use tokio::time::sleep;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::task::JoinHandle;
+use tokio::time::{interval, sleep_until, timeout, Instant, MissedTickBehavior};
+
+use address::Address;
+use api::{AlertEvent, ServerConfig};
+use blacklist::{BlacklistEntry, EntryKind, Source};
+use db::DbHandle;
+use executor::{ExecutionConfig, Executor};
+use copycat::CopycatConfig;
+use filters::{Filters, SocialFilters};
+use ingest::{IngestConfig, IngestMode, StreamEvent};
+use pipeline::{Completion, Pipeline, WorkerConfig};
+use profiles::Profile;
+use risk::RiskConfig;
+use secrets::Secret;
+use social::{Platform, SocialConfig, SocialProvider};
+use state::{Alert, LoopState};
+use tracker::{Tracker, TrackerConfig};
 
 #[derive(Debug, Clone)]
 struct Config {
-    pumpfun_key: String,
-    infura_key: String,
-    etherscan_key: String,
+    pumpfun_key: Secret,
+    infura_key: Secret,
+    etherscan_key: Secret,
     poll_interval: u64,
-    min_liquidity: f64,
-    max_creator_fee: f64,
-    min_holders: i64,
-    block_new_coins_minutes: u64,
+    ingest: IngestConfig,
+    filters: Filters,
+    profiles: Vec<Profile>,
+    social: SocialConfig,
+    copycat: CopycatConfig,
     max_coins_per_creator: i64,
-    coin_addresses: Vec<String>,
-    dev_addresses: Vec<String>,
-    telegram_bot_token: String,
+    coin_addresses: Vec<Address>,
+    dev_addresses: Vec<Address>,
+    blacklist_feed_url: String,
+    blacklist_feed_refresh_minutes: u64,
+    telegram_bot_token: Secret,
     telegram_channel_id: i64,
+    /// Chat allowed to send commands such as `/kill`; 0 disables commands.
+    telegram_admin_chat_id: i64,
+    risk: RiskConfig,
+    execution: ExecutionConfig,
+    tracking: TrackerConfig,
+    server: ServerConfig,
+    workers: WorkerConfig,
 }
 
 impl Config {
     fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
         if !Path::new(path).exists() {
-            let example = r#"[API]
+            let example = r#"# Keys and tokens may be kept in an encrypted keystore instead, as
+# `secret:<name>` references, e.g. PUMPFUN_KEY = secret:pumpfun. Create it with
+# `pumpfun-bot secrets init` and add entries with `pumpfun-bot secrets set`.
+# The passphrase is read from PUMPFUN_KEYSTORE_PASSPHRASE or prompted for.
+[SECRETS]
+KEYSTORE = keystore.json
+
+[API]
 PUMPFUN_KEY = your_pumpfun_api_key_here
 INFURA_KEY = your_infura_key_here
 ETHERSCAN_KEY = your_etherscan_api_key_here
 POLL_INTERVAL = 60
+INGEST_MODE = poll
+STREAM_URL = wss://api.pump.fun/migrations/stream
+PAGE_SIZE = 50
+MAX_BACKFILL_PAGES = 20
 
 [FILTERS]
 MIN_LIQUIDITY = 5.0
@@ -39,14 +104,80 @@ MAX_CREATOR_FEE = 10.0
 MIN_HOLDERS = 25
 BLOCK_NEW_COINS_MINUTES = 10
 MAX_COINS_PER_CREATOR = 3
+# Social thresholds, judged on the coin's Twitter account (or Telegram when
+# it has none). Setting any of them rejects coins without social accounts.
+# MIN_FOLLOWERS = 500
+# MIN_ACCOUNT_AGE_DAYS = 7
+# MAX_BOT_FOLLOWER_RATIO = 0.5
+# MIN_ENGAGEMENT_RATE = 0.001
+
+# Optional strategy profiles, each evaluated against every coin. Keys left
+# out fall back to [FILTERS] and [TELEGRAM]. Without any profile sections a
+# single "default" profile uses [FILTERS].
+# [PROFILE.aggressive]
+# MIN_LIQUIDITY = 2.0
+# MIN_HOLDERS = 10
+# REQUIRED_CHECKS = blacklist_coin, blacklist_dev, copycat
+# TELEGRAM_CHANNEL_ID = 123456789
+# PAPER_SIZE = 0.1
+
+[SOCIAL]
+# none, fixture (accounts from FIXTURE_PATH) or http (see social.rs)
+PROVIDER = none
+FIXTURE_PATH = social_fixtures.json
+API_URL =
+API_KEY =
+TIMEOUT_SECONDS = 10
+MAX_POSTS = 20
+
+[COPYCAT]
+# Flags coins imitating a well-known token or a saved coin. Profiles reject
+# them only when their REQUIRED_CHECKS include copycat.
+NAME_THRESHOLD = 0.92
+IMAGE_DISTANCE = 10
+HISTORY = 5000
+FETCH_IMAGES = true
 
 [BLACKLISTS]
 COIN_ADDRESSES = 0x0000000000000000000000000000000000000000
 DEV_ADDRESSES = 0x0000000000000000000000000000000000000000
+FEED_URL =
+FEED_REFRESH_MINUTES = 15
 
 [TELEGRAM]
 BOT_TOKEN = your_telegram_bot_token
 CHANNEL_ID = 123456789
+ADMIN_CHAT_ID = 0
+
+[RISK]
+MAX_POSITION_SIZE = 0.5
+MAX_OPEN_POSITIONS = 10
+MAX_CREATOR_EXPOSURE = 0.5
+DAILY_LOSS_LIMIT = 1.0
+
+[EXECUTION]
+# dry_run, simulator or onchain (not implemented yet)
+BACKEND = dry_run
+FEE_BPS = 25
+MAX_SLIPPAGE_BPS = 500
+
+[TRACKING]
+WINDOW_HOURS = 24
+INITIAL_INTERVAL_SECONDS = 60
+BACKOFF = 1.5
+MAX_INTERVAL_MINUTES = 60
+RUG_RATIO = 0.2
+
+[WORKERS]
+COUNT = 4
+QUEUE_SIZE = 32
+SECURITY_TIMEOUT_SECONDS = 15
+ANALYSIS_TIMEOUT_SECONDS = 30
+
+[SERVER]
+ENABLED = false
+BIND = 127.0.0.1:8787
+ALLOWED_ORIGIN = http://localhost:3000
 "#;
             fs::write(path, example)?;
             return Err(format!("Config file not found. Example created at {}", path).into());
@@ -58,39 +189,214 @@ CHANNEL_ID = 123456789
         let filters_section = ini.section(Some("FILTERS")).unwrap();
         let blacklists_section = ini.section(Some("BLACKLISTS")).unwrap();
         let telegram_section = ini.section(Some("TELEGRAM")).unwrap();
-
-        let config = Config {
-            pumpfun_key: api_section.get("PUMPFUN_KEY").unwrap_or("").to_string(),
-            infura_key: api_section.get("INFURA_KEY").unwrap_or("").to_string(),
-            etherscan_key: api_section.get("ETHERSCAN_KEY").unwrap_or("").to_string(),
-            poll_interval: api_section.get("POLL_INTERVAL").unwrap_or("60").parse()?,
+        let mut secrets = secrets::Resolver::new(
+            ini.section(Some("SECRETS"))
+                .and_then(|s| s.get("KEYSTORE"))
+                .map_or(secrets::DEFAULT_KEYSTORE, str::trim),
+        );
+        let ingest_defaults = IngestConfig::default();
+        let tracking_section = ini.section(Some("TRACKING"));
+        let tracking_get = |key: &str| tracking_section.and_then(|s| s.get(key));
+        let tracking_defaults = TrackerConfig::default();
+        let server_section = ini.section(Some("SERVER"));
+        let server_get = |key: &str| server_section.and_then(|s| s.get(key));
+        let server_defaults = ServerConfig::default();
+        let workers_section = ini.section(Some("WORKERS"));
+        let workers_get = |key: &str| workers_section.and_then(|s| s.get(key));
+        let workers_defaults = WorkerConfig::default();
+        let risk_section = ini.section(Some("RISK"));
+        let risk_get = |key: &str| risk_section.and_then(|s| s.get(key));
+        let risk_defaults = RiskConfig::default();
+        let execution_section = ini.section(Some("EXECUTION"));
+        let execution_get = |key: &str| execution_section.and_then(|s| s.get(key));
+        let execution_defaults = ExecutionConfig::default();
+        let social_section = ini.section(Some("SOCIAL"));
+        let social_get = |key: &str| social_section.and_then(|s| s.get(key));
+        let social_defaults = SocialConfig::default();
+        let copycat_section = ini.section(Some("COPYCAT"));
+        let copycat_get = |key: &str| copycat_section.and_then(|s| s.get(key));
+        let copycat_defaults = CopycatConfig::default();
+        let filters = Filters {
             min_liquidity: filters_section.get("MIN_LIQUIDITY").unwrap_or("5.0").parse()?,
             max_creator_fee: filters_section.get("MAX_CREATOR_FEE").unwrap_or("10.0").parse()?,
             min_holders: filters_section.get("MIN_HOLDERS").unwrap_or("25").parse()?,
             block_new_coins_minutes: filters_section.get("BLOCK_NEW_COINS_MINUTES").unwrap_or("10").parse()?,
+        };
+        let social_filter_defaults = SocialFilters::default();
+        let social_filters = SocialFilters {
+            min_followers: filters_section
+                .get("MIN_FOLLOWERS")
+                .map_or(Ok(social_filter_defaults.min_followers), str::parse)?,
+            min_account_age_days: filters_section
+                .get("MIN_ACCOUNT_AGE_DAYS")
+                .map_or(Ok(social_filter_defaults.min_account_age_days), str::parse)?,
+            max_bot_follower_ratio: filters_section
+                .get("MAX_BOT_FOLLOWER_RATIO")
+                .map_or(Ok(social_filter_defaults.max_bot_follower_ratio), str::parse)?,
+            min_engagement_rate: filters_section
+                .get("MIN_ENGAGEMENT_RATE")
+                .map_or(Ok(social_filter_defaults.min_engagement_rate), str::parse)?,
+        };
+        let telegram_channel_id = telegram_section.get("CHANNEL_ID").unwrap_or("0").parse()?;
+
+        let config = Config {
+            pumpfun_key: secrets.resolve("API.PUMPFUN_KEY", api_section.get("PUMPFUN_KEY").unwrap_or(""))?,
+            infura_key: secrets.resolve("API.INFURA_KEY", api_section.get("INFURA_KEY").unwrap_or(""))?,
+            etherscan_key: secrets.resolve("API.ETHERSCAN_KEY", api_section.get("ETHERSCAN_KEY").unwrap_or(""))?,
+            poll_interval: api_section.get("POLL_INTERVAL").unwrap_or("60").parse()?,
+            ingest: IngestConfig {
+                mode: api_section.get("INGEST_MODE").unwrap_or("poll").parse()?,
+                stream_url: api_section
+                    .get("STREAM_URL")
+                    .map_or(ingest_defaults.stream_url, |s| s.trim().to_string()),
+                page_size: api_section.get("PAGE_SIZE").map_or(Ok(ingest_defaults.page_size), str::parse)?,
+                max_backfill_pages: api_section
+                    .get("MAX_BACKFILL_PAGES")
+                    .map_or(Ok(ingest_defaults.max_backfill_pages), str::parse)?,
+            },
+            filters,
+            profiles: profiles::parse(&ini, filters, social_filters, telegram_channel_id)?,
+            social: SocialConfig {
+                provider: social_get("PROVIDER").map_or(Ok(social_defaults.provider), str::parse)?,
+                fixture_path: social_get("FIXTURE_PATH").map_or(social_defaults.fixture_path, |s| s.trim().to_string()),
+                api_url: social_get("API_URL").map_or(social_defaults.api_url, |s| s.trim().to_string()),
+                api_key: secrets.resolve("SOCIAL.API_KEY", social_get("API_KEY").unwrap_or(""))?,
+                timeout: social_get("TIMEOUT_SECONDS")
+                    .map_or(Ok(social_defaults.timeout), |s| s.parse().map(Duration::from_secs))?,
+                max_posts: social_get("MAX_POSTS").map_or(Ok(social_defaults.max_posts), str::parse)?,
+            },
+            copycat: CopycatConfig {
+                name_threshold: copycat_get("NAME_THRESHOLD").map_or(Ok(copycat_defaults.name_threshold), str::parse)?,
+                image_distance: copycat_get("IMAGE_DISTANCE").map_or(Ok(copycat_defaults.image_distance), str::parse)?,
+                history: copycat_get("HISTORY").map_or(Ok(copycat_defaults.history), str::parse)?,
+                fetch_images: copycat_get("FETCH_IMAGES").map_or(Ok(copycat_defaults.fetch_images), str::parse)?,
+            },
             max_coins_per_creator: filters_section.get("MAX_COINS_PER_CREATOR").unwrap_or("3").parse()?,
-            coin_addresses: blacklists_section.get("COIN_ADDRESSES").unwrap_or("")
-                .split(',')
-                .map(|s| s.trim().to_string())
-                .collect(),
-            dev_addresses: blacklists_section.get("DEV_ADDRESSES").unwrap_or("")
-                .split(',')
-                .map(|s| s.trim().to_string())
-                .collect(),
-            telegram_bot_token: telegram_section.get("BOT_TOKEN").unwrap_or("").to_string(),
-            telegram_channel_id: telegram_section.get("CHANNEL_ID").unwrap_or("0").parse()?,
+            coin_addresses: address::parse_list(blacklists_section.get("COIN_ADDRESSES").unwrap_or(""))
+                .map_err(|e| format!("BLACKLISTS.COIN_ADDRESSES: {}", e))?,
+            dev_addresses: address::parse_list(blacklists_section.get("DEV_ADDRESSES").unwrap_or(""))
+                .map_err(|e| format!("BLACKLISTS.DEV_ADDRESSES: {}", e))?,
+            blacklist_feed_url: blacklists_section.get("FEED_URL").unwrap_or("").trim().to_string(),
+            blacklist_feed_refresh_minutes: blacklists_section.get("FEED_REFRESH_MINUTES").unwrap_or("15").parse()?,
+            telegram_bot_token: secrets.resolve("TELEGRAM.BOT_TOKEN", telegram_section.get("BOT_TOKEN").unwrap_or(""))?,
+            telegram_channel_id,
+            telegram_admin_chat_id: telegram_section.get("ADMIN_CHAT_ID").unwrap_or("0").parse()?,
+            risk: RiskConfig {
+                max_position_size: risk_get("MAX_POSITION_SIZE").map_or(Ok(risk_defaults.max_position_size), str::parse)?,
+                max_open_positions: risk_get("MAX_OPEN_POSITIONS")
+                    .map_or(Ok(risk_defaults.max_open_positions), str::parse)?,
+                max_creator_exposure: risk_get("MAX_CREATOR_EXPOSURE")
+                    .map_or(Ok(risk_defaults.max_creator_exposure), str::parse)?,
+                daily_loss_limit: risk_get("DAILY_LOSS_LIMIT").map_or(Ok(risk_defaults.daily_loss_limit), str::parse)?,
+            },
+            execution: ExecutionConfig {
+                backend: execution_get("BACKEND").map_or(Ok(execution_defaults.backend), str::parse)?,
+                fee_bps: execution_get("FEE_BPS").map_or(Ok(execution_defaults.fee_bps), str::parse)?,
+                max_slippage_bps: execution_get("MAX_SLIPPAGE_BPS")
+                    .map_or(Ok(execution_defaults.max_slippage_bps), str::parse)?,
+            },
+            tracking: TrackerConfig {
+                window_hours: tracking_get("WINDOW_HOURS").map_or(Ok(tracking_defaults.window_hours), str::parse)?,
+                initial_interval_secs: tracking_get("INITIAL_INTERVAL_SECONDS")
+                    .map_or(Ok(tracking_defaults.initial_interval_secs), str::parse)?,
+                backoff: tracking_get("BACKOFF").map_or(Ok(tracking_defaults.backoff), str::parse)?,
+                max_interval_secs: tracking_get("MAX_INTERVAL_MINUTES")
+                    .map_or(Ok(tracking_defaults.max_interval_secs / 60), str::parse::<u64>)?
+                    * 60,
+                rug_ratio: tracking_get("RUG_RATIO").map_or(Ok(tracking_defaults.rug_ratio), str::parse)?,
+            },
+            server: ServerConfig {
+                enabled: server_get("ENABLED").map_or(Ok(server_defaults.enabled), str::parse)?,
+                bind: server_get("BIND").map_or(Ok(server_defaults.bind), str::parse)?,
+                allowed_origin: server_get("ALLOWED_ORIGIN")
+                    .map_or(server_defaults.allowed_origin, str::to_string),
+            },
+            workers: WorkerConfig {
+                count: workers_get("COUNT").map_or(Ok(workers_defaults.count), str::parse)?,
+                queue_size: workers_get("QUEUE_SIZE").map_or(Ok(workers_defaults.queue_size), str::parse)?,
+                security_timeout: workers_get("SECURITY_TIMEOUT_SECONDS")
+                    .map_or(Ok(workers_defaults.security_timeout), |s| s.parse().map(Duration::from_secs))?,
+                analysis_timeout: workers_get("ANALYSIS_TIMEOUT_SECONDS")
+                    .map_or(Ok(workers_defaults.analysis_timeout), |s| s.parse().map(Duration::from_secs))?,
+            },
         };
         Ok(config)
     }
+
+    /// The settings exposed by the API server. Keys and tokens are left out.
+    fn public_json(&self) -> Value {
+        serde_json::json!({
+            "poll_interval": self.poll_interval,
+            "ingest_mode": format!("{:?}", self.ingest.mode).to_lowercase(),
+            "filters": {
+                "min_liquidity": self.filters.min_liquidity,
+                "max_creator_fee": self.filters.max_creator_fee,
+                "min_holders": self.filters.min_holders,
+                "block_new_coins_minutes": self.filters.block_new_coins_minutes,
+            },
+            "profiles": self.profiles.iter().map(|p| serde_json::json!({
+                "name": p.name,
+                "filters": {
+                    "min_liquidity": p.filters.min_liquidity,
+                    "max_creator_fee": p.filters.max_creator_fee,
+                    "min_holders": p.filters.min_holders,
+                    "block_new_coins_minutes": p.filters.block_new_coins_minutes,
+                },
+                "social": {
+                    "min_followers": p.social.min_followers,
+                    "min_account_age_days": p.social.min_account_age_days,
+                    "max_bot_follower_ratio": p.social.max_bot_follower_ratio,
+                    "min_engagement_rate": p.social.min_engagement_rate,
+                },
+                "required_checks": p.required_checks,
+                "paper_size": p.paper_size,
+            })).collect::<Vec<_>>(),
+            "social_provider": format!("{:?}", self.social.provider).to_lowercase(),
+            "copycat": {
+                "name_threshold": self.copycat.name_threshold,
+                "image_distance": self.copycat.image_distance,
+                "history": self.copycat.history,
+                "fetch_images": self.copycat.fetch_images,
+            },
+            "max_coins_per_creator": self.max_coins_per_creator,
+            "blacklist_feed_enabled": !self.blacklist_feed_url.is_empty(),
+            "blacklist_feed_refresh_minutes": self.blacklist_feed_refresh_minutes,
+            "telegram_enabled": !self.telegram_bot_token.is_empty() && self.telegram_channel_id != 0,
+            "risk": {
+                "max_position_size": self.risk.max_position_size,
+                "max_open_positions": self.risk.max_open_positions,
+                "max_creator_exposure": self.risk.max_creator_exposure,
+                "daily_loss_limit": self.risk.daily_loss_limit,
+            },
+            "execution": {
+                "backend": format!("{:?}", self.execution.backend).to_lowercase(),
+                "fee_bps": self.execution.fee_bps,
+                "max_slippage_bps": self.execution.max_slippage_bps,
+            },
+            "tracking": {
+                "window_hours": self.tracking.window_hours,
+                "initial_interval_secs": self.tracking.initial_interval_secs,
+                "backoff": self.tracking.backoff,
+                "max_interval_secs": self.tracking.max_interval_secs,
+                "rug_ratio": self.tracking.rug_ratio,
+            },
+        })
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct CoinToken {
     name: Option<String>,
     symbol: Option<String>,
+    description: Option<String>,
+    #[serde(rename = "imageUri", alias = "image_uri", alias = "image")]
+    image_uri: Option<String>,
+    website: Option<String>,
+    twitter: Option<String>,
+    telegram: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct RawCoinData {
     #[serde(rename = "contractAddress")]
     contract_address: Option<String>,
@@ -108,222 +414,449 @@ struct RawCoinData {
 
 #[derive(Debug)]
 struct CoinData {
-    contract_address: String,
+    contract_address: Address,
     name: String,
     symbol: String,
-    creator_wallet: String,
+    creator_wallet: Address,
     migration_time: SystemTime,
     initial_liquidity: f64,
     creator_fee: f64,
     holders: i64,
+    description: Option<String>,
+    image_uri: Option<String>,
+    website: Option<String>,
+    /// Linked accounts, as normalised handles.
+    socials: Vec<(Platform, String)>,
 }
 
 struct PumpFunBot {
-    config: Config,
-    db: Connection,
-    current_contract: String,
+    config: Arc<Config>,
+    db: DbHandle,
+    state: LoopState,
     api_base: String,
+    tracker: Arc<Tracker>,
+    executor: Arc<dyn Executor>,
+    social: Option<Arc<dyn SocialProvider>>,
+    alerts: broadcast::Sender<AlertEvent>,
 }
 
 impl PumpFunBot {
-    fn new(config: Config) -> Result<Self, Box<dyn std::error::Error>> {
-        let db = Connection::open("pumpfun.db")?;
-        let bot = PumpFunBot {
-            config,
-            db,
-            current_contract: String::new(),
-            api_base: "https://api.pump.fun".to_string(),
-        };
-        bot.create_tables()?;
-        Ok(bot)
-    }
-
-    fn create_tables(&self) -> Result<(), Box<dyn std::error::Error>> {
-        self.db.execute(
-            "CREATE TABLE IF NOT EXISTS coins (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                contract_address TEXT UNIQUE,
-                name TEXT,
-                symbol TEXT,
-                creator_wallet TEXT,
-                migration_time DATETIME,
-                initial_liquidity REAL,
-                creator_fee REAL,
-                holders INTEGER,
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
-            )",
-            [],
-        )?;
-        Ok(())
+    fn new(config: Config, db_path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut conn = cli::open_db(db_path)?;
+        seed_config_blacklist(&mut conn, &config)?;
+        let api_base = "https://api.pump.fun".to_string();
+        let tracker = Tracker::new(config.tracking.clone(), &api_base, config.pumpfun_key.expose());
+        let executor = executor::build(&config.execution);
+        let social = social::build(&config.social)?;
+        Ok(PumpFunBot {
+            config: Arc::new(config),
+            db: DbHandle::spawn(conn)?,
+            state: LoopState::default(),
+            api_base,
+            tracker: Arc::new(tracker),
+            executor,
+            social,
+            alerts: broadcast::channel(64).0,
+        })
     }
 
-    async fn fetch_migrated_coins(&self, limit: u64) -> Result<Vec<RawCoinData>, Box<dyn std::error::Error>> {
-        let url = format!("{}/migrations?limit={}&sort=desc", self.api_base, limit);
+    async fn fetch_migrated_coins(&self, limit: u64, offset: u64) -> Result<Vec<RawCoinData>, Box<dyn std::error::Error>> {
+        let url = format!("{}/migrations?limit={}&offset={}&sort=desc", self.api_base, limit, offset);
         let client = reqwest::Client::new();
         let res = client
             .get(&url)
-            .header("Authorization", format!("Bearer {}", self.config.pumpfun_key))
+            .header("Authorization", format!("Bearer {}", self.config.pumpfun_key.expose()))
             .header("Content-Type", "application/json")
             .timeout(Duration::from_secs(10))
             .send()
-            .await?;
+            .await?
+            .error_for_status()?;
         let json: Value = res.json().await?;
         let data = json.get("data").ok_or("Missing data field")?;
         let coins: Vec<RawCoinData> = serde_json::from_value(data.clone())?;
         Ok(coins)
     }
 
-    fn parse_coin_data(&self, raw: &RawCoinData) -> Option<CoinData> {
-        let contract_address = raw.contract_address.as_ref()?.to_string();
-        // 此处可以使用 web3 crate 将地址转换为 checksum 格式
-        let token = raw.token.as_ref()?;
-        let name = token.name.clone().unwrap_or("Unknown".to_string());
-        let symbol = token.symbol.clone().unwrap_or("UNK".to_string());
-        let creator = raw.creator.as_ref().unwrap_or(&"0x0000000000000000000000000000000000000000".to_string()).to_string();
-        let migration_time_str = raw.migration_time.as_ref().unwrap_or(&"".to_string());
-        let migration_time = chrono::DateTime::parse_from_rfc3339(migration_time_str)
-            .map(|dt| SystemTime::from(dt))
-            .unwrap_or(SystemTime::now());
-        let initial_liquidity = raw.initial_liquidity.unwrap_or(0.0);
-        let creator_fee = raw.creator_fee.unwrap_or(0.0);
-        let holders = raw.holder_count.unwrap_or(0);
-        Some(CoinData {
-            contract_address,
-            name,
-            symbol,
-            creator_wallet: creator, // 可扩展为 checksum 地址
-            migration_time,
-            initial_liquidity,
-            creator_fee,
-            holders,
-        })
-    }
-
-    fn is_blacklisted(&self, coin: &CoinData) -> bool {
-        for addr in &self.config.coin_addresses {
-            if addr.trim() == coin.contract_address {
-                println!("[SECURITY] Coin {} is blacklisted.", coin.contract_address);
-                return true;
+    /// Pages back through `/migrations` until it reaches the cursor, so a
+    /// burst of migrations between two polls is never cut off at one page.
+    /// Returns how many pages were needed; more than one means the feed is
+    /// busy. Without a cursor (first run) only the newest page is taken.
+    async fn backfill(&mut self) -> Result<u64, Box<dyn std::error::Error>> {
+        let page_size = self.config.ingest.page_size;
+        let max_pages = if self.state.cursor.is_some() {
+            self.config.ingest.max_backfill_pages.max(1)
+        } else {
+            1
+        };
+        let mut collected = Vec::new();
+        let mut pages = 0;
+        while pages < max_pages {
+            let page = self.fetch_migrated_coins(page_size, pages * page_size).await?;
+            pages += 1;
+            let full = page.len() as u64 == page_size;
+            let reached = page.iter().any(|raw| self.state.is_behind_cursor(raw));
+            collected.extend(page);
+            if reached || !full {
+                break;
             }
-        }
-        for addr in &self.config.dev_addresses {
-            if addr.trim() == coin.creator_wallet {
-                println!("[SECURITY] Dev {} is blacklisted.", coin.creator_wallet);
-                return true;
+            if pages == max_pages {
+                println!("[INGEST] Backfill stopped after {} pages; older migrations may be missed.", pages);
             }
         }
-        false
+        let queued = self.state.enqueue_new(collected);
+        if queued > 0 {
+            println!("[INGEST] Queued {} new migrations from {} page(s).", queued, pages);
+        }
+        self.persist_state().await;
+        Ok(pages)
     }
 
-    fn apply_filters(&self, coin: &CoinData) -> bool {
-        if coin.initial_liquidity < self.config.min_liquidity {
-            return false;
-        }
-        if coin.creator_fee > self.config.max_creator_fee {
-            return false;
+    /// Polls sooner while the feed is busy, relaxing back to `POLL_INTERVAL`.
+    fn next_poll_delay(&self, pages: u64) -> Duration {
+        let base = Duration::from_secs(self.config.poll_interval);
+        if pages > 1 {
+            (base / 4).max(Duration::from_secs(5))
+        } else {
+            base
         }
-        if coin.holders < self.config.min_holders {
-            return false;
+    }
+
+    /// The chat for a profile's alerts. Alerts from a profile that has since
+    /// been removed from the config go to the `[TELEGRAM]` channel.
+    fn alert_channel(&self, profile: &str) -> i64 {
+        self.config
+            .profiles
+            .iter()
+            .find(|p| p.name == profile)
+            .map_or(self.config.telegram_channel_id, |p| p.telegram_channel_id)
+    }
+
+    async fn send_telegram_alert(&self, alert: &Alert) -> Result<(), Box<dyn std::error::Error>> {
+        let channel_id = self.alert_channel(&alert.profile);
+        if self.config.telegram_bot_token.is_empty() || channel_id == 0 {
+            println!("[TELEGRAM] Telegram not configured.");
+            return Ok(());
         }
-        if let Ok(elapsed) = coin.migration_time.elapsed() {
-            if elapsed < Duration::from_secs(self.config.block_new_coins_minutes * 60) {
-                return false;
+        telegram::send_message(self.config.telegram_bot_token.expose(), channel_id, &alert.text).await?;
+        Ok(())
+    }
+
+    /// Delivers queued alerts in order, stopping at the first failure so the
+    /// rest stay queued for the next attempt.
+    async fn flush_alerts(&mut self) {
+        while let Some(alert) = self.state.pending_alerts.front() {
+            if let Err(e) = self.send_telegram_alert(alert).await {
+                println!("[TELEGRAM] Send failed, {} alerts queued: {}", self.state.pending_alerts.len(), e);
+                return;
             }
+            self.state.pending_alerts.pop_front();
         }
-        true
     }
 
-    fn perform_security_checks(&self, coin: &CoinData) {
-        println!("[SECURITY] Performing security checks for {}", coin.contract_address);
-        // 此处添加外部安全检查逻辑
+    async fn persist_state(&self) {
+        let snapshot = self.state.clone();
+        if let Err(e) = self.db.call(move |db| state::save(db, &snapshot)).await {
+            println!("[ERROR] Failed to persist loop state: {}", e);
+        }
     }
 
-    fn save_coin(&self, coin: &CoinData) {
-        let _ = self.db.execute(
-            "INSERT OR IGNORE INTO coins (contract_address, name, symbol, creator_wallet, migration_time, initial_liquidity, creator_fee, holders)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-            params![
-                coin.contract_address,
-                coin.name,
-                coin.symbol,
-                coin.creator_wallet,
-                coin.migration_time.duration_since(UNIX_EPOCH).unwrap().as_secs(),
-                coin.initial_liquidity,
-                coin.creator_fee,
-                coin.holders
-            ],
-        );
+    /// Hands pending coins to the workers while the job queue has room.
+    fn dispatch(&mut self, jobs: &mpsc::Sender<(state::Cursor, RawCoinData)>) {
+        while let Ok(permit) = jobs.try_reserve() {
+            match self.state.take_next() {
+                Some(job) => permit.send(job),
+                None => break,
+            }
+        }
     }
 
-    fn analyze_coin(&self, coin: &CoinData) {
-        println!("[ANALYSIS] Analyzing {} ({})...", coin.symbol, coin.contract_address);
-        // 这里可以添加对交易模式、情绪分析等的扩展逻辑
+    async fn on_completion(&mut self, done: Completion) {
+        self.state.pending_alerts.extend(done.alerts);
+        self.state.complete(done.cursor);
+        self.persist_state().await;
+        self.flush_alerts().await;
     }
 
-    async fn send_telegram_alert(&self, message: &str) {
-        if self.config.telegram_bot_token.is_empty() || self.config.telegram_channel_id == 0 {
-            println!("[TELEGRAM] Telegram not configured.");
-            return;
+    /// Runs until `shutdown` fires. The loop only fetches and dispatches;
+    /// coins are processed by a pool of workers. On shutdown no new coins
+    /// are handed out, coins already in a worker are allowed to finish,
+    /// alerts are flushed and the state is saved so the next start resumes
+    /// from the same cursor.
+    async fn monitor_coins_loop(&mut self, mut shutdown: watch::Receiver<bool>) {
+        match self.db.call(|db| state::load(db)).await {
+            Ok(state) => {
+                if !state.pending_coins.is_empty() || !state.pending_alerts.is_empty() {
+                    println!(
+                        "[STATE] Resuming with {} pending coins and {} queued alerts.",
+                        state.pending_coins.len(),
+                        state.pending_alerts.len()
+                    );
+                }
+                self.state = state;
+            }
+            Err(e) => println!("[ERROR] Failed to load loop state, starting fresh: {}", e),
         }
-        // 简单通过 HTTP GET 调用 Telegram Bot API 发送消息（也可使用 teloxide 库）
-        let url = format!(
-            "https://api.telegram.org/bot{}/sendMessage?chat_id={}&text={}",
-            self.config.telegram_bot_token,
-            self.config.telegram_channel_id,
-            urlencoding::encode(message)
+        let workers = self.config.workers.clone();
+        let (job_tx, job_rx) = mpsc::channel(workers.queue_size.max(1));
+        let (done_tx, mut done_rx) = mpsc::unbounded_channel();
+        let pipeline = Pipeline::new(
+            self.config.clone(),
+            self.db.clone(),
+            self.tracker.clone(),
+            self.executor.clone(),
+            self.social.clone(),
+            self.alerts.clone(),
         );
-        let _ = reqwest::get(&url).await;
-    }
+        let handles = pipeline::spawn_workers(pipeline, workers.count, job_rx, done_tx);
 
-    async fn monitor_coins_loop(&mut self) {
-        loop {
-            match self.fetch_migrated_coins(10).await {
-                Ok(raw_coins) => {
-                    for raw in raw_coins.iter() {
-                        if let Some(coin) = self.parse_coin_data(raw) {
-                            if self.is_blacklisted(&coin) {
-                                continue;
-                            }
-                            self.perform_security_checks(&coin);
-                            if !self.apply_filters(&coin) {
-                                continue;
-                            }
-                            self.save_coin(&coin);
-                            self.analyze_coin(&coin);
-                            let alert = format!(
-                                "New coin found:\nSymbol: {}\nContract: {}\nLiquidity: {:.2}\n",
-                                coin.symbol, coin.contract_address, coin.initial_liquidity
-                            );
-                            self.send_telegram_alert(&alert).await;
-                            self.current_contract = coin.contract_address.clone();
+        let mut stream = match self.config.ingest.mode {
+            IngestMode::Stream => Some(ingest::spawn_stream(
+                self.config.ingest.stream_url.clone(),
+                self.config.pumpfun_key.expose().to_string(),
+                shutdown.clone(),
+            )),
+            IngestMode::Poll => None,
+        };
+        // Tracking and the blacklist feed run on their own timers so a slow
+        // sample or feed download never holds up fetching and dispatch.
+        let mut housekeeping = vec![spawn_tracker(
+            self.tracker.clone(),
+            self.db.clone(),
+            Duration::from_secs(self.config.poll_interval),
+            shutdown.clone(),
+        )];
+        if !self.config.blacklist_feed_url.is_empty() {
+            housekeeping.push(spawn_blacklist_sync(
+                self.config.blacklist_feed_url.clone(),
+                self.db.clone(),
+                Duration::from_secs(self.config.blacklist_feed_refresh_minutes * 60),
+                shutdown.clone(),
+            ));
+        }
+        // Polling doubles as the fallback while the stream is down.
+        let mut connected = false;
+        let mut next_poll = Instant::now();
+        while !*shutdown.borrow() {
+            if Instant::now() >= next_poll {
+                let mut delay = Duration::from_secs(self.config.poll_interval);
+                if !connected {
+                    match self.backfill().await {
+                        Ok(pages) => delay = self.next_poll_delay(pages),
+                        Err(e) => println!("[ERROR] {}", e),
+                    }
+                }
+                next_poll = Instant::now() + delay;
+            }
+            self.dispatch(&job_tx);
+            tokio::select! {
+                Some(done) = done_rx.recv() => self.on_completion(done).await,
+                event = ingest::next_event(&mut stream) => match event {
+                    Some(StreamEvent::Coin(raw)) => {
+                        self.state.enqueue_new(vec![*raw]);
+                        self.persist_state().await;
+                    }
+                    Some(StreamEvent::Connected) => {
+                        connected = true;
+                        if let Err(e) = self.backfill().await {
+                            println!("[ERROR] Gap backfill failed: {}", e);
                         }
                     }
+                    Some(StreamEvent::Disconnected) => {
+                        connected = false;
+                        next_poll = Instant::now();
+                    }
+                    None => {
+                        println!("[STREAM] Stream task ended, falling back to polling.");
+                        stream = None;
+                        connected = false;
+                    }
+                },
+                _ = sleep_until(next_poll) => {}
+                _ = shutdown.changed() => {}
+            }
+        }
+
+        drop(job_tx);
+        let grace = workers.security_timeout + workers.analysis_timeout;
+        while self.state.in_flight() > 0 {
+            match timeout(grace, done_rx.recv()).await {
+                Ok(Some(done)) => self.on_completion(done).await,
+                _ => {
+                    println!("[STATE] {} coins did not finish in time; they stay pending.", self.state.in_flight());
+                    break;
                 }
-                Err(e) => {
-                    println!("[ERROR] {}", e);
+            }
+        }
+        for handle in handles.into_iter().chain(housekeeping) {
+            handle.abort();
+        }
+        self.flush_alerts().await;
+        self.persist_state().await;
+        println!(
+            "[STATE] Shut down cleanly with {} pending coins and {} queued alerts saved.",
+            self.state.pending_coins.len(),
+            self.state.pending_alerts.len()
+        );
+    }
+}
+
+/// Samples tracked coins every `period` until `shutdown` fires.
+fn spawn_tracker(
+    tracker: Arc<Tracker>,
+    db: DbHandle,
+    period: Duration,
+    mut shutdown: watch::Receiver<bool>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticks = interval(period.max(Duration::from_secs(1)));
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        while !*shutdown.borrow() {
+            tokio::select! {
+                _ = ticks.tick() => {}
+                _ = shutdown.changed() => break,
+            }
+            if let Err(e) = tracker.run_due(&db, unix_now()).await {
+                println!("[ERROR] Tracker: {}", e);
+            }
+        }
+    })
+}
+
+/// Merges the shared blacklist feed every `period` and purges expired
+/// entries, until `shutdown` fires.
+fn spawn_blacklist_sync(
+    url: String,
+    db: DbHandle,
+    period: Duration,
+    mut shutdown: watch::Receiver<bool>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticks = interval(period.max(Duration::from_secs(60)));
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        while !*shutdown.borrow() {
+            tokio::select! {
+                _ = ticks.tick() => {}
+                _ = shutdown.changed() => break,
+            }
+            // The error is not `Send`, so it is rendered before the next await.
+            let fetched = blacklist::fetch_feed(&url).await.map_err(|e| e.to_string());
+            match fetched {
+                Ok(entries) => {
+                    match db.call(move |db| blacklist::replace_source(db, Source::SharedFeed, &entries)).await {
+                        Ok(n) => println!("[SECURITY] Merged {} entries from blacklist feed.", n),
+                        Err(e) => println!("[ERROR] {}", e),
+                    }
+                }
+                Err(e) => println!("[ERROR] Blacklist feed sync failed: {}", e),
+            }
+            if let Err(e) = db.call(|db| blacklist::purge_expired(db)).await {
+                println!("[ERROR] {}", e);
+            }
+        }
+    })
+}
+
+fn seed_config_blacklist(db: &mut Connection, config: &Config) -> rusqlite::Result<()> {
+    let coins = config.coin_addresses.iter().map(|a| (EntryKind::Coin, a));
+    let devs = config.dev_addresses.iter().map(|a| (EntryKind::Dev, a));
+    let entries: Vec<BlacklistEntry> = coins
+        .chain(devs)
+        .map(|(kind, addr)| BlacklistEntry::new(kind, addr.clone(), Source::Config))
+        .collect();
+    blacklist::replace_source(db, Source::Config, &entries)?;
+    Ok(())
+}
+
+async fn wait_for_shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = sigterm.recv() => {}
                 }
             }
-            sleep(Duration::from_secs(self.config.poll_interval)).await;
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+            }
         }
     }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
 }
 
 #[tokio::main]
 async fn main() {
-    let config = match Config::load("config.ini") {
+    let cli = cli::Cli::parse();
+    match &cli.command {
+        None | Some(cli::Command::Run) => run(&cli).await,
+        Some(command) => {
+            if let Err(e) = cli::execute(&cli, command) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+async fn run(cli: &cli::Cli) {
+    let config = match Config::load(&cli.config) {
         Ok(cfg) => cfg,
         Err(e) => {
             eprintln!("{}", e);
             return;
         }
     };
-    let mut bot = match PumpFunBot::new(config) {
+    let mut bot = match PumpFunBot::new(config, &cli.db) {
         Ok(bot) => bot,
         Err(e) => {
             eprintln!("Failed to initialize bot: {}", e);
             return;
         }
     };
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    tokio::spawn(async move {
+        wait_for_shutdown_signal().await;
+        println!("Shutdown requested, finishing current coin...");
+        let _ = shutdown_tx.send(true);
+    });
+    if bot.config.server.enabled {
+        match Connection::open(&cli.db) {
+            Ok(db) => {
+                let state = api::AppState::new(db, bot.config.public_json(), bot.alerts.clone());
+                let server = bot.config.server.clone();
+                let shutdown = shutdown_rx.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = api::serve(server, state, shutdown).await {
+                        eprintln!("[API] Server stopped: {}", e);
+                    }
+                });
+            }
+            Err(e) => eprintln!("[API] Failed to open database: {}", e),
+        }
+    }
+    if !bot.config.telegram_bot_token.is_empty() && bot.config.telegram_admin_chat_id != 0 {
+        tokio::spawn(telegram::run_commands(
+            bot.config.telegram_bot_token.expose().to_string(),
+            bot.config.telegram_admin_chat_id,
+            bot.db.clone(),
+            bot.config.risk.clone(),
+            bot.executor.clone(),
+            bot.config.execution.max_slippage_bps,
+            shutdown_rx.clone(),
+        ));
+    }
     println!("PumpFunBot is running...");
-    bot.monitor_coins_loop().await;
+    bot.monitor_coins_loop(shutdown_rx).await;
 }
@@ -0,0 +1,93 @@
+use rusqlite::Connection;
+
+use crate::{blacklist, coins, decisions, paper, risk, social, state, tracker};
+
+type Migration = fn(&Connection) -> rusqlite::Result<()>;
+
+/// Schema migrations in order. The database's `user_version` pragma records
+/// how many have been applied, so append new steps; never edit old ones.
+const MIGRATIONS: &[Migration] = &[initial_schema, strategy_profiles, risk_log, paper_fills, social_accounts, coin_metadata, risk_sides];
+
+/// Every table up to the introduction of versioning. The statements are all
+/// `CREATE ... IF NOT EXISTS`, so databases created before then upgrade
+/// cleanly from version 0. The module `create_*` functions it calls must
+/// keep that original shape; later columns are added by later migrations.
+fn initial_schema(db: &Connection) -> rusqlite::Result<()> {
+    coins::create_table(db)?;
+    blacklist::create_table(db)?;
+    tracker::create_tables(db)?;
+    state::create_table(db)?;
+    decisions::create_tables(db)?;
+    Ok(())
+}
+
+/// Tags decisions with the profile that made them and adds the per-profile
+/// paper book. Rows from before profiles belong to `default`.
+fn strategy_profiles(db: &Connection) -> rusqlite::Result<()> {
+    db.execute(
+        "ALTER TABLE decisions ADD COLUMN profile TEXT NOT NULL DEFAULT 'default'",
+        [],
+    )?;
+    paper::create_table(db)
+}
+
+/// Audit log of every risk review.
+fn risk_log(db: &Connection) -> rusqlite::Result<()> {
+    risk::create_table(db)
+}
+
+/// Fill details for paper positions, now that orders go through an
+/// executor, and a close for positions sold by hand.
+fn paper_fills(db: &Connection) -> rusqlite::Result<()> {
+    for column in [
+        "tokens REAL",
+        "entry_price REAL",
+        "executor TEXT",
+        "exit_proceeds REAL",
+        "closed_at INTEGER",
+    ] {
+        db.execute(&format!("ALTER TABLE paper_positions ADD COLUMN {}", column), [])?;
+    }
+    Ok(())
+}
+
+/// Twitter and Telegram accounts linked from coins, and their recent posts.
+fn social_accounts(db: &Connection) -> rusqlite::Result<()> {
+    social::create_tables(db)
+}
+
+/// Token metadata beyond name and symbol, and the image hash copycat
+/// detection compares.
+fn coin_metadata(db: &Connection) -> rusqlite::Result<()> {
+    for column in ["description", "image_uri", "website", "twitter", "telegram", "image_hash"] {
+        db.execute(&format!("ALTER TABLE coins ADD COLUMN {} TEXT", column), [])?;
+    }
+    Ok(())
+}
+
+/// Records whether each risk decision was for a buy or a sell, now that
+/// manual sells are reviewed too. Earlier rows were all buys.
+fn risk_sides(db: &Connection) -> rusqlite::Result<()> {
+    db.execute(
+        "ALTER TABLE risk_decisions ADD COLUMN side TEXT NOT NULL DEFAULT 'buy'",
+        [],
+    )?;
+    Ok(())
+}
+
+pub fn current_version(db: &Connection) -> rusqlite::Result<u32> {
+    db.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+/// Applies pending migrations, each in its own transaction. Returns the
+/// version before and after.
+pub fn migrate(db: &mut Connection) -> rusqlite::Result<(u32, u32)> {
+    let from = current_version(db)?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(from as usize) {
+        let tx = db.transaction()?;
+        migration(&tx)?;
+        tx.pragma_update(None, "user_version", index as u32 + 1)?;
+        tx.commit()?;
+    }
+    Ok((from, current_version(db)?))
+}
@@ -0,0 +1,336 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rusqlite::Connection;
+
+use crate::address::Address;
+use crate::filters::Filters;
+use crate::tracker::{self, Snapshot};
+
+/// Horizon used for the "not rugged" label and the paper exit.
+const HORIZON_SECS: i64 = 3600;
+
+const LIQUIDITY_GRID: &[f64] = &[0.0, 1.0, 2.0, 5.0, 10.0, 20.0, 50.0];
+const CREATOR_FEE_GRID: &[f64] = &[1.0, 2.0, 5.0, 10.0, 20.0];
+const HOLDERS_GRID: &[i64] = &[0, 10, 25, 50, 100, 200];
+const BLOCK_MINUTES_GRID: &[u64] = &[0, 5, 10, 30, 60];
+
+/// A historical coin together with what happened to it in the first hour.
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub liquidity: f64,
+    pub creator_fee: f64,
+    pub holders: i64,
+    /// Seconds between migration and the bot first seeing the coin.
+    pub age_secs: Option<i64>,
+    pub rugged: bool,
+    /// Return of buying at the first snapshot and selling at the horizon or
+    /// at the first rugged sample, whichever comes first.
+    pub pnl: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Score {
+    pub filters: Filters,
+    pub selected: usize,
+    pub precision: f64,
+    pub mean_pnl: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Search {
+    Grid,
+    Random { samples: usize, seed: u64 },
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub search: Search,
+    pub min_coins: usize,
+}
+
+/// Loads every saved coin whose tracking has covered the horizon (or which
+/// rugged before it). Only coins that passed the live filters are tracked,
+/// so the search can tighten the current filters but not loosen them.
+pub fn load_samples(db: &Connection, rug_ratio: f64) -> rusqlite::Result<Vec<Sample>> {
+    let mut stmt = db.prepare(
+        "SELECT contract_address, initial_liquidity, creator_fee, holders,
+                CAST(strftime('%s', created_at) AS INTEGER) - migration_time
+         FROM coins",
+    )?;
+    let coins = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, Address>(0)?,
+                row.get::<_, f64>(1)?,
+                row.get::<_, f64>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, Option<i64>>(4)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut samples = Vec::new();
+    for (address, liquidity, creator_fee, holders, age_secs) in coins {
+        let snapshots = tracker::load_snapshots(db, &address)?;
+        if let Some((rugged, pnl)) = label(&snapshots, rug_ratio) {
+            samples.push(Sample {
+                liquidity,
+                creator_fee,
+                holders,
+                age_secs: age_secs.filter(|a| *a >= 0),
+                rugged,
+                pnl,
+            });
+        }
+    }
+    Ok(samples)
+}
+
+fn label(snapshots: &[Snapshot], rug_ratio: f64) -> Option<(bool, f64)> {
+    let start = snapshots.iter().find(|s| s.price > 0.0)?.sampled_at;
+    let within: Vec<Snapshot> = snapshots
+        .iter()
+        .copied()
+        .filter(|s| s.sampled_at - start <= HORIZON_SECS)
+        .collect();
+    let outcome = tracker::compute_outcome(&within, rug_ratio)?;
+    if let Some(after) = outcome.rugged_after_secs {
+        let exit = within.iter().find(|s| s.sampled_at - start >= after)?;
+        return Some((true, exit.price / outcome.entry_price - 1.0));
+    }
+    let covered = snapshots.last()?.sampled_at - start >= HORIZON_SECS;
+    covered.then_some((false, outcome.final_multiple - 1.0))
+}
+
+pub fn score(filters: Filters, samples: &[Sample]) -> Score {
+    let selected: Vec<&Sample> = samples
+        .iter()
+        .filter(|s| {
+            let age = s.age_secs.map(|a| std::time::Duration::from_secs(a as u64));
+            filters.passes(s.liquidity, s.creator_fee, s.holders, age)
+        })
+        .collect();
+    let n = selected.len();
+    let (precision, mean_pnl) = if n == 0 {
+        (0.0, 0.0)
+    } else {
+        let survivors = selected.iter().filter(|s| !s.rugged).count();
+        let pnl: f64 = selected.iter().map(|s| s.pnl).sum();
+        (survivors as f64 / n as f64, pnl / n as f64)
+    };
+    Score {
+        filters,
+        selected: n,
+        precision,
+        mean_pnl,
+    }
+}
+
+pub fn candidates(search: Search) -> Vec<Filters> {
+    match search {
+        Search::Grid => {
+            let mut out = Vec::new();
+            for &min_liquidity in LIQUIDITY_GRID {
+                for &max_creator_fee in CREATOR_FEE_GRID {
+                    for &min_holders in HOLDERS_GRID {
+                        for &block_new_coins_minutes in BLOCK_MINUTES_GRID {
+                            out.push(Filters {
+                                min_liquidity,
+                                max_creator_fee,
+                                min_holders,
+                                block_new_coins_minutes,
+                            });
+                        }
+                    }
+                }
+            }
+            out
+        }
+        Search::Random { samples, seed } => {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let max = |grid: &[f64]| grid[grid.len() - 1];
+            (0..samples)
+                .map(|_| Filters {
+                    min_liquidity: round2(rng.gen_range(0.0..=max(LIQUIDITY_GRID))),
+                    max_creator_fee: round2(rng.gen_range(0.0..=max(CREATOR_FEE_GRID))),
+                    min_holders: rng.gen_range(0..=HOLDERS_GRID[HOLDERS_GRID.len() - 1]),
+                    block_new_coins_minutes: rng.gen_range(0..=BLOCK_MINUTES_GRID[BLOCK_MINUTES_GRID.len() - 1]),
+                })
+                .collect()
+        }
+    }
+}
+
+fn round2(x: f64) -> f64 {
+    (x * 100.0).round() / 100.0
+}
+
+/// Returns the scores not dominated on (precision, mean PnL), best
+/// precision first. Ties keep the loosest filters, i.e. the most coins.
+pub fn pareto_front(scores: &[Score], min_coins: usize) -> Vec<Score> {
+    let eligible: Vec<&Score> = scores.iter().filter(|s| s.selected >= min_coins).collect();
+    let dominates = |a: &Score, b: &Score| {
+        a.precision >= b.precision
+            && a.mean_pnl >= b.mean_pnl
+            && (a.precision > b.precision || a.mean_pnl > b.mean_pnl || a.selected > b.selected)
+    };
+    let mut front: Vec<Score> = eligible
+        .iter()
+        .filter(|s| !eligible.iter().any(|o| dominates(o, s)))
+        .map(|s| (*s).clone())
+        .collect();
+    front.sort_by(|a, b| b.precision.total_cmp(&a.precision).then(a.mean_pnl.total_cmp(&b.mean_pnl)));
+    front.dedup_by(|a, b| a.precision == b.precision && a.mean_pnl == b.mean_pnl);
+    front
+}
+
+pub fn run(db: &Connection, current: Filters, rug_ratio: f64, options: &Options) -> Result<(), Box<dyn std::error::Error>> {
+    let samples = load_samples(db, rug_ratio)?;
+    if samples.is_empty() {
+        return Err("no coins with a completed first hour of tracking yet".into());
+    }
+    let rugged = samples.iter().filter(|s| s.rugged).count();
+    println!("[OPTIMIZE] {} labelled coins, {} rugged within 1h", samples.len(), rugged);
+
+    let baseline = score(current, &samples);
+    print_score("current", &baseline);
+
+    let scores: Vec<Score> = candidates(options.search)
+        .into_iter()
+        .map(|f| score(f, &samples))
+        .collect();
+    let front = pareto_front(&scores, options.min_coins);
+    if front.is_empty() {
+        return Err(format!("no configuration selects at least {} coins", options.min_coins).into());
+    }
+    println!("[OPTIMIZE] Pareto front ({} of {} configurations):", front.len(), scores.len());
+    for s in &front {
+        print_score("front", s);
+    }
+
+    let best = front
+        .iter()
+        .max_by(|a, b| a.mean_pnl.total_cmp(&b.mean_pnl))
+        .expect("front is not empty");
+    println!("\n{}", best.filters);
+    Ok(())
+}
+
+fn print_score(label: &str, s: &Score) {
+    println!(
+        "  {:<8} precision {:>5.1}%  pnl {:>+7.1}%  coins {:>4}  | liq >= {} fee <= {} holders >= {} age >= {}m",
+        label,
+        s.precision * 100.0,
+        s.mean_pnl * 100.0,
+        s.selected,
+        s.filters.min_liquidity,
+        s.filters.max_creator_fee,
+        s.filters.min_holders,
+        s.filters.block_new_coins_minutes
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scored(precision: f64, mean_pnl: f64, selected: usize) -> Score {
+        Score {
+            filters: Filters::default(),
+            selected,
+            precision,
+            mean_pnl,
+        }
+    }
+
+    fn points(front: &[Score]) -> Vec<(f64, f64, usize)> {
+        front.iter().map(|s| (s.precision, s.mean_pnl, s.selected)).collect()
+    }
+
+    /// Snapshots ten minutes apart from prices, with steady liquidity.
+    fn snapshots(prices: &[f64]) -> Vec<Snapshot> {
+        prices
+            .iter()
+            .enumerate()
+            .map(|(i, &price)| Snapshot {
+                sampled_at: 1_700_000_000 + 600 * i as i64,
+                price,
+                liquidity: 10.0,
+                holders: 100,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pareto_front_drops_dominated_scores() {
+        let scores = [
+            scored(0.9, 0.1, 20),
+            scored(0.6, 0.5, 20),
+            // Worse than the first on both
+            scored(0.8, 0.05, 20),
+            // Too few coins, however good
+            scored(1.0, 2.0, 2),
+        ];
+        assert_eq!(points(&pareto_front(&scores, 10)), [(0.9, 0.1, 20), (0.6, 0.5, 20)]);
+        assert!(pareto_front(&[], 10).is_empty());
+        assert!(pareto_front(&scores, 100).is_empty());
+    }
+
+    #[test]
+    fn pareto_front_ties_keep_the_most_coins_once() {
+        let scores = [scored(0.8, 0.2, 15), scored(0.8, 0.2, 40), scored(0.8, 0.2, 40)];
+        assert_eq!(points(&pareto_front(&scores, 10)), [(0.8, 0.2, 40)]);
+    }
+
+    #[test]
+    fn labels_follow_tracker_outcomes() {
+        // Seven samples cover the hour
+        let pump = snapshots(&[1.0, 1.2, 1.5, 2.0, 1.8, 1.6, 1.5]);
+        let outcome = tracker::compute_outcome(&pump, 0.2).unwrap();
+        assert!(!outcome.rugged);
+        assert_eq!(label(&pump, 0.2), Some((false, outcome.final_multiple - 1.0)));
+
+        // Sold at the first rugged sample, not at the end of the hour
+        let rug = snapshots(&[1.0, 2.0, 0.1, 0.05, 0.05, 0.05, 0.05]);
+        let outcome = tracker::compute_outcome(&rug, 0.2).unwrap();
+        assert_eq!(outcome.rugged_after_secs, Some(1200));
+        let (rugged, pnl) = label(&rug, 0.2).unwrap();
+        assert!(rugged);
+        assert!((pnl - (0.1 - 1.0)).abs() < 1e-9);
+
+        // Samples after the horizon do not change the label
+        let late_rug = snapshots(&[1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.01]);
+        assert_eq!(label(&late_rug, 0.2), Some((false, 0.0)));
+
+        // Not tracked for the full hour, and not rugged yet: no label
+        assert_eq!(label(&snapshots(&[1.0, 1.5, 2.0]), 0.2), None);
+        assert_eq!(label(&snapshots(&[0.0, 0.0]), 0.2), None);
+    }
+
+    #[test]
+    fn scores_count_survivors_among_selected_coins() {
+        let sample = |liquidity, rugged, pnl| Sample {
+            liquidity,
+            creator_fee: 1.0,
+            holders: 100,
+            age_secs: Some(3600),
+            rugged,
+            pnl,
+        };
+        let samples = [
+            sample(10.0, false, 0.5),
+            sample(10.0, true, -0.9),
+            sample(10.0, false, 0.1),
+            // Filtered out by its liquidity
+            sample(1.0, true, -1.0),
+        ];
+
+        let s = score(Filters::default(), &samples);
+        assert_eq!(s.selected, 3);
+        assert!((s.precision - 2.0 / 3.0).abs() < 1e-9);
+        assert!((s.mean_pnl - (0.5 - 0.9 + 0.1) / 3.0).abs() < 1e-9);
+
+        let none = score(Filters { min_liquidity: 100.0, ..Filters::default() }, &samples);
+        assert_eq!((none.selected, none.precision, none.mean_pnl), (0, 0.0, 0.0));
+    }
+}
@@ -0,0 +1,200 @@
+use rusqlite::{params, Connection};
+use serde::Serialize;
+
+use crate::address::Address;
+use crate::db::DbHandle;
+use crate::executor::{ExecError, Executor, Fill, TradeRequest};
+use crate::unix_now;
+
+/// A notional position a profile opened when it accepted a coin. Positions
+/// are marked to market from tracker snapshots and close when the tracking
+/// window does, or when sold by hand.
+#[derive(Debug, Clone, Serialize)]
+pub struct Position {
+    pub profile: String,
+    pub contract_address: String,
+    pub creator_wallet: String,
+    /// SOL spent, or reserved while the buy is still executing.
+    pub size: f64,
+    pub opened_at: i64,
+    /// Tokens received; `None` until the buy has filled.
+    pub tokens: Option<f64>,
+    pub entry_price: Option<f64>,
+    pub executor: Option<String>,
+    /// Value relative to `size`: sale proceeds once sold, the final multiple
+    /// once tracking closed, otherwise the latest tracked price relative to
+    /// the first. `None` until the tracker has a priced sample.
+    pub multiple: Option<f64>,
+    pub closed: bool,
+}
+
+impl Position {
+    pub fn pnl(&self) -> Option<f64> {
+        self.multiple.map(|m| self.size * (m - 1.0))
+    }
+}
+
+pub fn create_table(db: &Connection) -> rusqlite::Result<()> {
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS paper_positions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            profile TEXT NOT NULL,
+            contract_address TEXT NOT NULL,
+            creator_wallet TEXT NOT NULL,
+            size REAL NOT NULL,
+            opened_at INTEGER NOT NULL,
+            UNIQUE (profile, contract_address)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Opens a position unless the profile already holds this coin, which
+/// happens when a coin is reprocessed after a restart.
+pub fn open(
+    db: &Connection,
+    profile: &str,
+    address: &Address,
+    creator: &Address,
+    size: f64,
+    now: i64,
+) -> rusqlite::Result<()> {
+    db.execute(
+        "INSERT OR IGNORE INTO paper_positions (profile, contract_address, creator_wallet, size, opened_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![profile, address, creator, size, now],
+    )?;
+    Ok(())
+}
+
+/// Records the fill of a reserved position. The size becomes what the
+/// executor actually spent.
+pub fn record_fill(db: &Connection, profile: &str, address: &Address, fill: &Fill) -> rusqlite::Result<()> {
+    db.execute(
+        "UPDATE paper_positions SET size = ?3, tokens = ?4, entry_price = ?5, executor = ?6
+         WHERE profile = ?1 AND contract_address = ?2",
+        params![profile, address, fill.amount_in, fill.amount_out, fill.price, fill.executor],
+    )?;
+    Ok(())
+}
+
+/// Drops a reserved position whose buy did not go through.
+pub fn cancel(db: &Connection, profile: &str, address: &Address) -> rusqlite::Result<()> {
+    db.execute(
+        "DELETE FROM paper_positions WHERE profile = ?1 AND contract_address = ?2 AND tokens IS NULL",
+        params![profile, address],
+    )?;
+    Ok(())
+}
+
+pub fn close(db: &Connection, profile: &str, address: &Address, fill: &Fill, now: i64) -> rusqlite::Result<()> {
+    db.execute(
+        "UPDATE paper_positions SET exit_proceeds = ?3, closed_at = ?4
+         WHERE profile = ?1 AND contract_address = ?2",
+        params![profile, address, fill.amount_out, now],
+    )?;
+    Ok(())
+}
+
+pub fn position(db: &Connection, profile: &str, address: &Address) -> rusqlite::Result<Option<Position>> {
+    let address = address.to_string();
+    Ok(book(db, Some(profile))?.into_iter().find(|p| p.contract_address == address))
+}
+
+/// Executes the buy for a position reserved by risk review and records the
+/// fill. A failed buy releases the reservation.
+pub async fn execute_open(
+    db: &DbHandle,
+    executor: &dyn Executor,
+    profile: &str,
+    request: TradeRequest,
+) -> Result<Fill, ExecError> {
+    let result = executor.execute(&request).await;
+    log_fill(profile, &request, &result);
+    let (profile, address, outcome) = (profile.to_string(), request.contract_address.clone(), result.clone());
+    let recorded = db
+        .call(move |db| match &outcome {
+            Ok(fill) => record_fill(db, &profile, &address, fill),
+            Err(_) => cancel(db, &profile, &address),
+        })
+        .await;
+    if let Err(e) = recorded {
+        println!("[ERROR] {}", e);
+    }
+    result
+}
+
+/// Sells a filled position and closes it.
+pub async fn execute_close(
+    db: &DbHandle,
+    executor: &dyn Executor,
+    profile: &str,
+    request: TradeRequest,
+) -> Result<Fill, ExecError> {
+    let result = executor.execute(&request).await;
+    log_fill(profile, &request, &result);
+    if let Ok(fill) = &result {
+        let (profile, address, fill) = (profile.to_string(), request.contract_address.clone(), fill.clone());
+        if let Err(e) = db.call(move |db| close(db, &profile, &address, &fill, unix_now())).await {
+            println!("[ERROR] {}", e);
+        }
+    }
+    result
+}
+
+fn log_fill(profile: &str, request: &TradeRequest, result: &Result<Fill, ExecError>) {
+    match result {
+        Ok(fill) => println!(
+            "[EXEC] {} {} {} via {}: {:.4} in, {:.4} out, price {:.10}, slippage {:.0}bps",
+            profile,
+            fill.side,
+            request.contract_address,
+            fill.executor,
+            fill.amount_in,
+            fill.amount_out,
+            fill.price,
+            fill.slippage_bps
+        ),
+        Err(e) => println!("[EXEC] {} {} {} failed: {}", profile, request.side, request.contract_address, e),
+    }
+}
+
+/// Every position, optionally for one profile, oldest first.
+pub fn book(db: &Connection, profile: Option<&str>) -> rusqlite::Result<Vec<Position>> {
+    let mut stmt = db.prepare(
+        "SELECT p.profile, p.contract_address, p.creator_wallet, p.size, p.opened_at, o.final_multiple,
+                p.tokens, p.entry_price, p.executor, p.exit_proceeds,
+                (SELECT price FROM coin_snapshots s
+                 WHERE s.contract_address = p.contract_address AND s.price > 0
+                 ORDER BY s.sampled_at LIMIT 1),
+                (SELECT price FROM coin_snapshots s
+                 WHERE s.contract_address = p.contract_address
+                 ORDER BY s.sampled_at DESC LIMIT 1)
+         FROM paper_positions p
+         LEFT JOIN coin_outcomes o ON o.contract_address = p.contract_address
+         WHERE ?1 IS NULL OR p.profile = ?1
+         ORDER BY p.id",
+    )?;
+    let rows = stmt.query_map(params![profile], |row| {
+        let size: f64 = row.get(3)?;
+        let final_multiple: Option<f64> = row.get(5)?;
+        let sold = row.get::<_, Option<f64>>(9)?.map(|proceeds| proceeds / size);
+        let entry: Option<f64> = row.get(10)?;
+        let last: Option<f64> = row.get(11)?;
+        let marked = entry.zip(last).map(|(entry, last)| last / entry);
+        Ok(Position {
+            profile: row.get(0)?,
+            contract_address: row.get(1)?,
+            creator_wallet: row.get(2)?,
+            size,
+            opened_at: row.get(4)?,
+            tokens: row.get(6)?,
+            entry_price: row.get(7)?,
+            executor: row.get(8)?,
+            multiple: sold.or(final_multiple).or(marked),
+            closed: sold.is_some() || final_multiple.is_some(),
+        })
+    })?;
+    rows.collect()
+}
@@ -0,0 +1,560 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rusqlite::params;
+use tokio::sync::{broadcast, mpsc, Mutex};
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+
+use crate::address::Address;
+use crate::api::AlertEvent;
+use crate::blacklist::{self, BlacklistEntry, EntryKind, Source};
+use crate::copycat::{self, Candidate, ImageHash, Match};
+use crate::db::DbHandle;
+use crate::decisions;
+use crate::executor::{Executor, Pool, Side, TradeRequest};
+use crate::paper;
+use crate::profiles::Profile;
+use crate::risk::{self, Order};
+use crate::social::{self, Platform, SocialMetrics, SocialProvider};
+use crate::state::{Alert, Cursor};
+use crate::tracker::Tracker;
+use crate::{unix_now, CoinData, Config, RawCoinData};
+
+#[derive(Debug, Clone)]
+pub struct WorkerConfig {
+    pub count: usize,
+    pub queue_size: usize,
+    pub security_timeout: Duration,
+    pub analysis_timeout: Duration,
+}
+
+impl Default for WorkerConfig {
+    fn default() -> Self {
+        WorkerConfig {
+            count: 4,
+            queue_size: 32,
+            security_timeout: Duration::from_secs(15),
+            analysis_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// A finished coin, reported back to the monitor loop.
+pub struct Completion {
+    pub cursor: Cursor,
+    /// Alerts from the profiles that accepted the coin, queued by the loop
+    /// for delivery.
+    pub alerts: Vec<Alert>,
+}
+
+const CREATOR_CHECK: &str = "creator_coin_count";
+const CREATOR_REASON: &str = "creator coin limit";
+const COPYCAT_CHECK: &str = "copycat";
+
+fn blacklist_check(kind: EntryKind) -> &'static str {
+    match kind {
+        EntryKind::Coin => "blacklist_coin",
+        EntryKind::Dev => "blacklist_dev",
+    }
+}
+
+/// The outcome of one security check, shared by every profile.
+struct Check {
+    name: &'static str,
+    passed: bool,
+    /// Decision reason for profiles that require the check.
+    reason: &'static str,
+}
+
+impl Check {
+    fn passed(name: &'static str) -> Self {
+        Check {
+            name,
+            passed: true,
+            reason: "",
+        }
+    }
+
+    fn failed(name: &'static str, reason: &'static str) -> Self {
+        Check {
+            name,
+            passed: false,
+            reason,
+        }
+    }
+}
+
+/// What the security stage found, shared by every profile.
+struct Screening {
+    checks: Vec<Check>,
+    /// Tokens the coin resembles, reported in alerts even to profiles that
+    /// do not require the copycat check.
+    copycats: Vec<Match>,
+    image_hash: Option<ImageHash>,
+}
+
+/// The per-coin stages, shared by all workers.
+#[derive(Clone)]
+pub struct Pipeline {
+    config: Arc<Config>,
+    db: DbHandle,
+    tracker: Arc<Tracker>,
+    executor: Arc<dyn Executor>,
+    social: Option<Arc<dyn SocialProvider>>,
+    alerts: broadcast::Sender<AlertEvent>,
+}
+
+impl Pipeline {
+    pub fn new(
+        config: Arc<Config>,
+        db: DbHandle,
+        tracker: Arc<Tracker>,
+        executor: Arc<dyn Executor>,
+        social: Option<Arc<dyn SocialProvider>>,
+        alerts: broadcast::Sender<AlertEvent>,
+    ) -> Self {
+        Pipeline {
+            config,
+            db,
+            tracker,
+            executor,
+            social,
+            alerts,
+        }
+    }
+
+    fn parse_coin_data(&self, raw: &RawCoinData) -> Option<CoinData> {
+        let contract_address = match raw.contract_address.as_ref()?.parse::<Address>() {
+            Ok(addr) => addr,
+            Err(e) => {
+                println!("[ERROR] Skipping coin: {}", e);
+                return None;
+            }
+        };
+        let token = raw.token.as_ref()?;
+        let name = token.name.clone().unwrap_or("Unknown".to_string());
+        let symbol = token.symbol.clone().unwrap_or("UNK".to_string());
+        let creator = raw
+            .creator
+            .as_deref()
+            .and_then(|s| s.parse::<Address>().ok())
+            .unwrap_or_else(Address::evm_zero);
+        let migration_time_str = raw.migration_time.as_deref().unwrap_or("");
+        let migration_time = chrono::DateTime::parse_from_rfc3339(migration_time_str)
+            .map(SystemTime::from)
+            .unwrap_or(SystemTime::now());
+        let initial_liquidity = raw.initial_liquidity.unwrap_or(0.0);
+        let creator_fee = raw.creator_fee.unwrap_or(0.0);
+        let holders = raw.holder_count.unwrap_or(0);
+        let text = |field: &Option<String>| field.as_deref().map(str::trim).filter(|s| !s.is_empty()).map(String::from);
+        let socials = [(Platform::Twitter, &token.twitter), (Platform::Telegram, &token.telegram)]
+            .into_iter()
+            .filter_map(|(platform, link)| Some((platform, social::parse_handle(platform, link.as_deref()?)?)))
+            .collect();
+        Some(CoinData {
+            contract_address,
+            name,
+            symbol,
+            creator_wallet: creator,
+            migration_time,
+            initial_liquidity,
+            creator_fee,
+            holders,
+            description: text(&token.description),
+            image_uri: text(&token.image_uri),
+            website: text(&token.website),
+            socials,
+        })
+    }
+
+    /// Blacklist lookups for the coin and its creator. A creator entry put
+    /// there by the coin-count rule is reported as that check rather than as
+    /// a blacklist hit, so profiles that skip the rule are not affected.
+    async fn blacklist_checks(&self, coin: &CoinData) -> Vec<Check> {
+        let lookups = [
+            (EntryKind::Coin, &coin.contract_address, "Coin"),
+            (EntryKind::Dev, &coin.creator_wallet, "Dev"),
+        ];
+        let mut checks = Vec::new();
+        for (kind, addr, label) in lookups {
+            let lookup_addr = addr.clone();
+            match self.db.call(move |db| blacklist::lookup(db, kind, &lookup_addr)).await {
+                Ok(Some(entry)) => {
+                    let check = if entry.source == Source::AutoRule {
+                        Check::failed(CREATOR_CHECK, CREATOR_REASON)
+                    } else {
+                        Check::failed(blacklist_check(kind), "blacklisted")
+                    };
+                    let detail = format!(
+                        "{} [{}]: {}",
+                        addr,
+                        entry.source,
+                        entry.reason.as_deref().unwrap_or("no reason given")
+                    );
+                    println!("[SECURITY] {} {} ({}) is blacklisted [{}]", label, addr, addr.chain(), entry.source);
+                    self.record_security(coin, check.name, false, Some(detail)).await;
+                    checks.push(check);
+                }
+                Ok(None) => {
+                    self.record_security(coin, blacklist_check(kind), true, None).await;
+                    checks.push(Check::passed(blacklist_check(kind)));
+                }
+                Err(e) => println!("[ERROR] Blacklist lookup failed: {}", e),
+            }
+        }
+        checks
+    }
+
+    /// Auto-blacklists creators that have launched more than
+    /// `MAX_COINS_PER_CREATOR` coins. The entry expires after a day so a
+    /// creator is re-evaluated rather than banned forever.
+    async fn check_creator_rule(&self, coin: &CoinData) -> Check {
+        let (creator, contract) = (coin.creator_wallet.clone(), coin.contract_address.clone());
+        let count: i64 = self
+            .db
+            .call(move |db| {
+                db.query_row(
+                    "SELECT COUNT(*) FROM coins WHERE creator_wallet = ?1 AND contract_address != ?2",
+                    params![creator, contract],
+                    |row| row.get(0),
+                )
+            })
+            .await
+            .unwrap_or(0);
+        if count < self.config.max_coins_per_creator {
+            self.record_security(coin, CREATOR_CHECK, true, None).await;
+            return Check::passed(CREATOR_CHECK);
+        }
+        let entry = BlacklistEntry::new(EntryKind::Dev, coin.creator_wallet.clone(), Source::AutoRule)
+            .with_reason(format!("created {} coins", count + 1))
+            .expires_in(Duration::from_secs(24 * 60 * 60));
+        if let Err(e) = self.db.call(move |db| blacklist::upsert(db, &entry)).await {
+            println!("[ERROR] {}", e);
+        }
+        println!("[SECURITY] Dev {} auto-blacklisted after {} coins.", coin.creator_wallet, count + 1);
+        let detail = format!("{} created {} coins", coin.creator_wallet, count + 1);
+        self.record_security(coin, CREATOR_CHECK, false, Some(detail)).await;
+        Check::failed(CREATOR_CHECK, CREATOR_REASON)
+    }
+
+    async fn record_security(&self, coin: &CoinData, check_name: &str, passed: bool, detail: Option<String>) {
+        let (address, check_name) = (coin.contract_address.clone(), check_name.to_string());
+        let result = self
+            .db
+            .call(move |db| decisions::record_security(db, &address, &check_name, passed, detail.as_deref()))
+            .await;
+        if let Err(e) = result {
+            println!("[ERROR] {}", e);
+        }
+    }
+
+    async fn record_decision(&self, profile: &Profile, coin: &CoinData, accepted: bool, reason: &str) {
+        let (profile, address, symbol, reason) = (
+            profile.name.clone(),
+            coin.contract_address.clone(),
+            coin.symbol.clone(),
+            reason.to_string(),
+        );
+        let result = self
+            .db
+            .call(move |db| decisions::record_decision(db, &profile, &address, &symbol, accepted, &reason))
+            .await;
+        if let Err(e) = result {
+            println!("[ERROR] {}", e);
+        }
+    }
+
+    /// The profile's rejection reason for the coin, if any: the first
+    /// required check that failed, then the profile's filters. Social
+    /// filters are applied separately, once accounts have been collected.
+    fn verdict(&self, profile: &Profile, coin: &CoinData, checks: &[Check]) -> Option<String> {
+        if let Some(check) = checks.iter().find(|c| !c.passed && profile.requires(c.name)) {
+            return Some(check.reason.to_string());
+        }
+        profile.filters.rejection(
+            coin.initial_liquidity,
+            coin.creator_fee,
+            coin.holders,
+            coin.migration_time.elapsed().ok(),
+        )
+    }
+
+    /// Compares the coin's name, symbol and image with well-known tokens
+    /// and saved coins. An image that cannot be fetched is left out of the
+    /// comparison rather than failing the check.
+    async fn check_copycat(&self, coin: &CoinData) -> (Check, Vec<Match>, Option<ImageHash>) {
+        let config = self.config.copycat.clone();
+        let image_hash = match &coin.image_uri {
+            Some(uri) if config.fetch_images => {
+                match copycat::fetch_image_hash(uri, self.config.workers.security_timeout).await {
+                    Ok(hash) => Some(hash),
+                    Err(e) => {
+                        println!("[SECURITY] Could not hash image of {}: {}", coin.contract_address, e);
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+        let (address, history) = (coin.contract_address.clone(), config.history);
+        let saved = match self.db.call(move |db| copycat::recent(db, &address, history)).await {
+            Ok(saved) => saved,
+            Err(e) => {
+                println!("[ERROR] Copycat check failed: {}", e);
+                Vec::new()
+            }
+        };
+        let candidate = Candidate {
+            name: &coin.name,
+            symbol: &coin.symbol,
+            image_hash,
+        };
+        let matches = copycat::detect(&config, &candidate, &saved);
+        if matches.is_empty() {
+            self.record_security(coin, COPYCAT_CHECK, true, None).await;
+            return (Check::passed(COPYCAT_CHECK), matches, image_hash);
+        }
+        let detail = matches.iter().map(Match::to_string).collect::<Vec<_>>().join("; ");
+        println!("[SECURITY] {} looks like a copycat: {}", coin.contract_address, detail);
+        self.record_security(coin, COPYCAT_CHECK, false, Some(detail)).await;
+        (Check::failed(COPYCAT_CHECK, "copycat"), matches, image_hash)
+    }
+
+    /// Runs every security check once for all profiles.
+    async fn security_stage(&self, coin: &CoinData) -> Screening {
+        let mut checks = self.blacklist_checks(coin).await;
+        if !checks.iter().any(|c| c.name == CREATOR_CHECK) {
+            checks.push(self.check_creator_rule(coin).await);
+        }
+        let (check, copycats, image_hash) = self.check_copycat(coin).await;
+        checks.push(check);
+        self.perform_security_checks(coin).await;
+        Screening {
+            checks,
+            copycats,
+            image_hash,
+        }
+    }
+
+    /// Collects the coin's linked accounts and stores what was found. Runs
+    /// only for coins some profile might still accept.
+    async fn social_stage(&self, coin: &CoinData) -> Vec<SocialMetrics> {
+        let Some(provider) = &self.social else {
+            return Vec::new();
+        };
+        if coin.socials.is_empty() {
+            return Vec::new();
+        }
+        let config = &self.config.social;
+        let now = unix_now();
+        let collect = social::collect(provider.as_ref(), &coin.socials, config.max_posts, now);
+        let collected = match timeout(config.timeout, collect).await {
+            Ok(collected) => collected,
+            Err(_) => {
+                println!("[SOCIAL] Collection for {} timed out.", coin.contract_address);
+                return Vec::new();
+            }
+        };
+        let metrics = collected.iter().map(|c| c.metrics.clone()).collect();
+        let address = coin.contract_address.clone();
+        if let Err(e) = self.db.call(move |db| social::save(db, &address, &collected, now)).await {
+            println!("[ERROR] {}", e);
+        }
+        metrics
+    }
+
+    async fn perform_security_checks(&self, coin: &CoinData) {
+        println!("[SECURITY] Performing security checks for {}", coin.contract_address);
+        // 此处添加外部安全检查逻辑
+    }
+
+    /// Saves the coin, starts tracking it and places a paper order, subject
+    /// to risk review, for each profile that accepted it.
+    async fn save_coin(&self, coin: &CoinData, image_hash: Option<ImageHash>, accepted: &[&Profile]) {
+        let row = (
+            coin.contract_address.clone(),
+            coin.name.clone(),
+            coin.symbol.clone(),
+            coin.creator_wallet.clone(),
+            coin.migration_time.duration_since(UNIX_EPOCH).unwrap().as_secs(),
+            coin.initial_liquidity,
+            coin.creator_fee,
+            coin.holders,
+        );
+        let handle = |platform| coin.socials.iter().find(|(p, _)| *p == platform).map(|(_, h)| h.clone());
+        let metadata = (
+            coin.description.clone(),
+            coin.image_uri.clone(),
+            coin.website.clone(),
+            handle(Platform::Twitter),
+            handle(Platform::Telegram),
+            image_hash.map(|h| h.to_string()),
+        );
+        let positions: Vec<(String, f64)> = accepted.iter().map(|p| (p.name.clone(), p.paper_size)).collect();
+        let (tracker, risk) = (self.tracker.clone(), self.config.risk.clone());
+        let result = self
+            .db
+            .call(move |db| {
+                db.execute(
+                    "INSERT OR IGNORE INTO coins (contract_address, name, symbol, creator_wallet, migration_time, initial_liquidity, creator_fee, holders,
+                        description, image_uri, website, twitter, telegram, image_hash)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+                    params![
+                        row.0, row.1, row.2, row.3, row.4, row.5, row.6, row.7, metadata.0, metadata.1, metadata.2,
+                        metadata.3, metadata.4, metadata.5
+                    ],
+                )?;
+                let now = unix_now();
+                tracker.start(db, &row.0, now)?;
+                let mut approved = Vec::new();
+                for (profile, size) in positions {
+                    let order = Order {
+                        profile: &profile,
+                        side: Side::Buy,
+                        contract_address: &row.0,
+                        creator: &row.3,
+                        size,
+                    };
+                    let verdict = risk::reserve_position(db, &risk, &order, now)?;
+                    if verdict.approved {
+                        approved.push((profile, verdict.size));
+                    }
+                }
+                Ok(approved)
+            })
+            .await;
+        let approved = match result {
+            Ok(approved) => approved,
+            Err(e) => {
+                println!("[ERROR] {}", e);
+                return;
+            }
+        };
+        for (profile, size) in approved {
+            let request = TradeRequest {
+                contract_address: coin.contract_address.clone(),
+                side: Side::Buy,
+                amount_in: size,
+                quote: Pool::migrated(coin.initial_liquidity),
+                max_slippage_bps: self.config.execution.max_slippage_bps,
+            };
+            // Failures are logged and release the reservation.
+            let _ = paper::execute_open(&self.db, self.executor.as_ref(), &profile, request).await;
+        }
+    }
+
+    async fn analyze_coin(&self, coin: &CoinData, social: &[SocialMetrics]) {
+        println!("[ANALYSIS] Analyzing {} ({})...", coin.symbol, coin.contract_address);
+        for metrics in social {
+            println!("[ANALYSIS] {}", metrics.describe());
+        }
+        // 这里可以添加对交易模式、情绪分析等的扩展逻辑
+    }
+
+    /// Runs one coin through every stage and returns an alert for each
+    /// profile that accepted it. Each network-bound stage has its own
+    /// timeout so a single slow coin cannot hold a worker indefinitely.
+    pub async fn process(&self, raw: &RawCoinData) -> Vec<Alert> {
+        let Some(coin) = self.parse_coin_data(raw) else {
+            return Vec::new();
+        };
+        let workers = &self.config.workers;
+        let profiles = &self.config.profiles;
+        let screening = match timeout(workers.security_timeout, self.security_stage(&coin)).await {
+            Ok(screening) => screening,
+            Err(_) => {
+                println!("[SECURITY] Checks for {} timed out.", coin.contract_address);
+                for profile in profiles {
+                    self.record_decision(profile, &coin, false, "security checks timed out").await;
+                }
+                return Vec::new();
+            }
+        };
+        let mut candidates = Vec::new();
+        for profile in profiles {
+            match self.verdict(profile, &coin, &screening.checks) {
+                Some(reason) => self.record_decision(profile, &coin, false, &reason).await,
+                None => candidates.push(profile),
+            }
+        }
+        if candidates.is_empty() {
+            return Vec::new();
+        }
+        let social = self.social_stage(&coin).await;
+        let mut accepted = Vec::new();
+        for profile in candidates {
+            match profile.social.rejection(&social) {
+                Some(reason) => self.record_decision(profile, &coin, false, &reason).await,
+                None => {
+                    self.record_decision(profile, &coin, true, "passed filters").await;
+                    accepted.push(profile);
+                }
+            }
+        }
+        if accepted.is_empty() {
+            return Vec::new();
+        }
+        self.save_coin(&coin, screening.image_hash, &accepted).await;
+        if timeout(workers.analysis_timeout, self.analyze_coin(&coin, &social)).await.is_err() {
+            println!("[ANALYSIS] Analysis of {} timed out.", coin.contract_address);
+        }
+        let mut message = format!(
+            "New coin found:\nSymbol: {}\nContract: {}\nLiquidity: {:.2}\n",
+            coin.symbol, coin.contract_address, coin.initial_liquidity
+        );
+        for metrics in &social {
+            message.push_str(&metrics.describe());
+            message.push('\n');
+        }
+        for copy in &screening.copycats {
+            message.push_str(&format!("Possible copycat: {}\n", copy));
+        }
+        // Nobody listening on the stream is not an error.
+        let _ = self.alerts.send(AlertEvent {
+            contract_address: coin.contract_address.to_string(),
+            name: coin.name.clone(),
+            symbol: coin.symbol.clone(),
+            initial_liquidity: coin.initial_liquidity,
+            creator_fee: coin.creator_fee,
+            holders: coin.holders,
+            profiles: accepted.iter().map(|p| p.name.clone()).collect(),
+            message: message.clone(),
+        });
+        accepted
+            .iter()
+            .map(|p| Alert {
+                profile: p.name.clone(),
+                text: format!("{}Profile: {}\n", message, p.name),
+            })
+            .collect()
+    }
+}
+
+/// Starts `count` workers pulling from `jobs`. They exit once `jobs` is
+/// closed and drained, or when the loop stops listening for completions.
+pub fn spawn_workers(
+    pipeline: Pipeline,
+    count: usize,
+    jobs: mpsc::Receiver<(Cursor, RawCoinData)>,
+    done: mpsc::UnboundedSender<Completion>,
+) -> Vec<JoinHandle<()>> {
+    let jobs = Arc::new(Mutex::new(jobs));
+    (0..count.max(1))
+        .map(|_| {
+            let (pipeline, jobs, done) = (pipeline.clone(), jobs.clone(), done.clone());
+            tokio::spawn(async move {
+                loop {
+                    let job = jobs.lock().await.recv().await;
+                    let Some((cursor, raw)) = job else {
+                        break;
+                    };
+                    let alerts = pipeline.process(&raw).await;
+                    if done.send(Completion { cursor, alerts }).is_err() {
+                        break;
+                    }
+                }
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,131 @@
+use ini::Ini;
+
+use crate::filters::{Filters, SocialFilters};
+use crate::telegram::MANUAL_PROFILE;
+
+/// Every security check the pipeline runs. A profile lists the ones a coin
+/// must pass.
+pub const CHECKS: &[&str] = &["blacklist_coin", "blacklist_dev", "creator_coin_count", "copycat"];
+
+/// Checks required when a profile does not list its own. The copycat check
+/// only flags coins unless a profile opts in, since imitation is common
+/// among meme coins and not always a scam.
+pub const DEFAULT_CHECKS: &[&str] = &["blacklist_coin", "blacklist_dev", "creator_coin_count"];
+
+/// A named strategy evaluated against every fetched coin. Each profile makes
+/// its own accept/reject decision, alerts its own channel and keeps its own
+/// paper book, while fetching and security checks are shared.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub name: String,
+    pub filters: Filters,
+    pub social: SocialFilters,
+    pub required_checks: Vec<String>,
+    /// Telegram chat that receives this profile's alerts.
+    pub telegram_channel_id: i64,
+    /// Notional size, in SOL, of each paper position this profile opens.
+    pub paper_size: f64,
+}
+
+impl Profile {
+    pub fn requires(&self, check: &str) -> bool {
+        self.required_checks.iter().any(|c| c == check)
+    }
+}
+
+pub const DEFAULT_PROFILE: &str = "default";
+pub const DEFAULT_PAPER_SIZE: f64 = 0.1;
+
+/// Names the bot books trades under itself; a configured profile using one
+/// would share its paper book and risk limits.
+pub const RESERVED_PROFILES: &[&str] = &[MANUAL_PROFILE];
+
+/// Reads `[PROFILE.<name>]` sections. Keys left out inherit from `[FILTERS]`
+/// and `[TELEGRAM]`, so a profile only needs to list what it changes. With
+/// no profile sections the bot runs a single `default` profile.
+pub fn parse(
+    ini: &Ini,
+    base: Filters,
+    social: SocialFilters,
+    telegram_channel_id: i64,
+) -> Result<Vec<Profile>, Box<dyn std::error::Error>> {
+    let mut profiles: Vec<Profile> = Vec::new();
+    for (section, props) in ini.iter() {
+        let Some(name) = section.and_then(|s| s.strip_prefix("PROFILE.")) else {
+            continue;
+        };
+        let name = name.trim();
+        if name.is_empty() || profiles.iter().any(|p| p.name == name) {
+            return Err(format!("[PROFILE.{}]: profile names must be unique and non-empty", name).into());
+        }
+        if RESERVED_PROFILES.contains(&name) {
+            return Err(format!("[PROFILE.{}]: '{}' is reserved for trades placed by hand", name, name).into());
+        }
+        let field = |key: &str| props.get(key).map(str::trim);
+        let required_checks = match field("REQUIRED_CHECKS") {
+            Some(list) => list
+                .split(',')
+                .map(str::trim)
+                .filter(|c| !c.is_empty())
+                .map(|c| {
+                    if CHECKS.contains(&c) {
+                        Ok(c.to_string())
+                    } else {
+                        Err(format!("PROFILE.{}.REQUIRED_CHECKS: unknown check '{}'", name, c))
+                    }
+                })
+                .collect::<Result<_, _>>()?,
+            None => DEFAULT_CHECKS.iter().map(|c| c.to_string()).collect(),
+        };
+        profiles.push(Profile {
+            name: name.to_string(),
+            filters: Filters {
+                min_liquidity: field("MIN_LIQUIDITY").map_or(Ok(base.min_liquidity), str::parse)?,
+                max_creator_fee: field("MAX_CREATOR_FEE").map_or(Ok(base.max_creator_fee), str::parse)?,
+                min_holders: field("MIN_HOLDERS").map_or(Ok(base.min_holders), str::parse)?,
+                block_new_coins_minutes: field("BLOCK_NEW_COINS_MINUTES")
+                    .map_or(Ok(base.block_new_coins_minutes), str::parse)?,
+            },
+            social: SocialFilters {
+                min_followers: field("MIN_FOLLOWERS").map_or(Ok(social.min_followers), str::parse)?,
+                min_account_age_days: field("MIN_ACCOUNT_AGE_DAYS")
+                    .map_or(Ok(social.min_account_age_days), str::parse)?,
+                max_bot_follower_ratio: field("MAX_BOT_FOLLOWER_RATIO")
+                    .map_or(Ok(social.max_bot_follower_ratio), str::parse)?,
+                min_engagement_rate: field("MIN_ENGAGEMENT_RATE").map_or(Ok(social.min_engagement_rate), str::parse)?,
+            },
+            required_checks,
+            telegram_channel_id: field("TELEGRAM_CHANNEL_ID").map_or(Ok(telegram_channel_id), str::parse)?,
+            paper_size: field("PAPER_SIZE").map_or(Ok(DEFAULT_PAPER_SIZE), str::parse)?,
+        });
+    }
+    if profiles.is_empty() {
+        profiles.push(Profile {
+            name: DEFAULT_PROFILE.to_string(),
+            filters: base,
+            social,
+            required_checks: DEFAULT_CHECKS.iter().map(|c| c.to_string()).collect(),
+            telegram_channel_id,
+            paper_size: DEFAULT_PAPER_SIZE,
+        });
+    }
+    Ok(profiles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(ini: &str) -> Result<Vec<Profile>, Box<dyn std::error::Error>> {
+        parse(&Ini::load_from_str(ini).unwrap(), Filters::default(), SocialFilters::default(), 0)
+    }
+
+    #[test]
+    fn reserved_profile_names_are_rejected() {
+        let err = parse_str("[PROFILE.manual]\nMIN_HOLDERS = 10\n").unwrap_err();
+        assert!(err.to_string().contains("reserved"), "{}", err);
+        let profiles = parse_str("[PROFILE.strict]\nMIN_HOLDERS = 10\n").unwrap();
+        assert_eq!(profiles[0].name, "strict");
+        assert_eq!(profiles[0].filters.min_holders, 10);
+    }
+}
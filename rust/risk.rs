@@ -0,0 +1,417 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::address::Address;
+use crate::executor::Side;
+use crate::paper;
+
+/// `[RISK]` limits. They apply to each profile's book separately; the kill
+/// switch halts every profile at once.
+#[derive(Debug, Clone)]
+pub struct RiskConfig {
+    /// Largest size, in SOL, of a single position. Larger orders are cut down.
+    pub max_position_size: f64,
+    pub max_open_positions: usize,
+    /// Total open size allowed in coins from one creator.
+    pub max_creator_exposure: f64,
+    /// No new positions once today's P&L (UTC) is at or below minus this.
+    pub daily_loss_limit: f64,
+}
+
+impl Default for RiskConfig {
+    fn default() -> Self {
+        RiskConfig {
+            max_position_size: 0.5,
+            max_open_positions: 10,
+            max_creator_exposure: 0.5,
+            daily_loss_limit: 1.0,
+        }
+    }
+}
+
+/// A request to open or close a position, before risk review.
+#[derive(Debug)]
+pub struct Order<'a> {
+    pub profile: &'a str,
+    pub side: Side,
+    pub contract_address: &'a Address,
+    pub creator: &'a Address,
+    pub size: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Verdict {
+    pub approved: bool,
+    /// The size to execute, which may be below the requested size.
+    pub size: f64,
+    pub reason: String,
+}
+
+impl Verdict {
+    fn approve(size: f64, reason: impl Into<String>) -> Self {
+        Verdict {
+            approved: true,
+            size,
+            reason: reason.into(),
+        }
+    }
+
+    fn reject(reason: impl Into<String>) -> Self {
+        Verdict {
+            approved: false,
+            size: 0.0,
+            reason: reason.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KillSwitch {
+    pub reason: String,
+    pub set_by: String,
+    pub set_at: i64,
+}
+
+pub fn create_table(db: &Connection) -> rusqlite::Result<()> {
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS risk_decisions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            profile TEXT NOT NULL,
+            contract_address TEXT NOT NULL,
+            requested_size REAL NOT NULL,
+            approved_size REAL NOT NULL,
+            approved INTEGER NOT NULL,
+            reason TEXT NOT NULL,
+            decided_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// The engaged kill switch, if any. It lives in `bot_state` so it survives
+/// restarts and is shared by the bot, the CLI and Telegram.
+pub fn kill_switch(db: &Connection) -> rusqlite::Result<Option<KillSwitch>> {
+    let value: Option<String> = db
+        .query_row("SELECT value FROM bot_state WHERE key = 'kill_switch'", [], |row| row.get(0))
+        .optional()?;
+    Ok(value.and_then(|json| serde_json::from_str(&json).ok()))
+}
+
+/// Engages the kill switch, or releases it when `switch` is `None`.
+pub fn set_kill_switch(db: &Connection, switch: Option<&KillSwitch>) -> rusqlite::Result<()> {
+    match switch {
+        Some(switch) => {
+            let json =
+                serde_json::to_string(switch).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+            db.execute(
+                "INSERT OR REPLACE INTO bot_state (key, value, updated_at) VALUES ('kill_switch', ?1, CURRENT_TIMESTAMP)",
+                params![json],
+            )?;
+        }
+        None => {
+            db.execute("DELETE FROM bot_state WHERE key = 'kill_switch'", [])?;
+        }
+    }
+    Ok(())
+}
+
+/// P&L of the positions opened since midnight UTC.
+fn daily_pnl(book: &[paper::Position], now: i64) -> f64 {
+    let day_start = now - now.rem_euclid(24 * 60 * 60);
+    book.iter()
+        .filter(|p| p.opened_at >= day_start)
+        .filter_map(|p| p.pnl())
+        .fold(0.0, |total, pnl| total + pnl)
+}
+
+/// Checks an order against the kill switch and the profile's book. Limits
+/// are checked in order of severity, and the first one hit decides. A coin
+/// the profile has held before is not bought again. Sells
+/// only need a filled open position: exits reduce risk, so neither the kill
+/// switch nor the limits hold them back.
+pub fn review(db: &Connection, config: &RiskConfig, order: &Order, now: i64) -> rusqlite::Result<Verdict> {
+    if order.side == Side::Sell {
+        return Ok(match paper::position(db, order.profile, order.contract_address)? {
+            Some(position) if !position.closed && position.tokens.is_some() => {
+                Verdict::approve(position.size, "closing open position")
+            }
+            Some(position) if !position.closed => Verdict::reject("buy has not filled"),
+            _ => Verdict::reject("no open position"),
+        });
+    }
+    if let Some(switch) = kill_switch(db)? {
+        return Ok(Verdict::reject(format!("kill switch engaged: {}", switch.reason)));
+    }
+    if !(order.size.is_finite() && order.size > 0.0) {
+        return Ok(Verdict::reject("size must be positive"));
+    }
+    let book = paper::book(db, Some(order.profile))?;
+    let daily_pnl = daily_pnl(&book, now);
+    if daily_pnl <= -config.daily_loss_limit {
+        return Ok(Verdict::reject(format!(
+            "daily loss {:.3} hit limit {}",
+            -daily_pnl, config.daily_loss_limit
+        )));
+    }
+    // A book holds one position per coin, so a closed one cannot be reopened.
+    let address = order.contract_address.to_string();
+    if let Some(held) = book.iter().find(|p| p.contract_address == address) {
+        return Ok(Verdict::reject(if held.closed {
+            "position already closed"
+        } else {
+            "position already open"
+        }));
+    }
+    let open: Vec<&paper::Position> = book.iter().filter(|p| !p.closed).collect();
+    if open.len() >= config.max_open_positions {
+        return Ok(Verdict::reject(format!(
+            "open positions {} >= {}",
+            open.len(),
+            config.max_open_positions
+        )));
+    }
+    let creator = order.creator.to_string();
+    let exposure: f64 = open
+        .iter()
+        .filter(|p| p.creator_wallet == creator)
+        .map(|p| p.size)
+        .sum();
+    let headroom = config.max_creator_exposure - exposure;
+    if headroom <= 0.0 {
+        return Ok(Verdict::reject(format!(
+            "creator exposure {:.3} >= {}",
+            exposure, config.max_creator_exposure
+        )));
+    }
+    let size = order.size.min(config.max_position_size).min(headroom);
+    if size < order.size {
+        return Ok(Verdict::approve(size, format!("size cut from {} to {:.3}", order.size, size)));
+    }
+    Ok(Verdict::approve(size, "within limits"))
+}
+
+/// A short human-readable status: the kill switch, then each book's open
+/// positions and P&L today.
+pub fn summary(db: &Connection, now: i64) -> rusqlite::Result<String> {
+    let mut lines = vec![match kill_switch(db)? {
+        Some(switch) => format!("Kill switch ENGAGED by {}: {}", switch.set_by, switch.reason),
+        None => "Kill switch off.".to_string(),
+    }];
+    let book = paper::book(db, None)?;
+    let mut profiles: Vec<&str> = book.iter().map(|p| p.profile.as_str()).collect();
+    profiles.sort();
+    profiles.dedup();
+    for profile in profiles {
+        let positions: Vec<paper::Position> = book.iter().filter(|p| p.profile == profile).cloned().collect();
+        let open: Vec<_> = positions.iter().filter(|p| !p.closed).collect();
+        let exposure: f64 = open.iter().map(|p| p.size).sum();
+        let today = daily_pnl(&positions, now);
+        lines.push(format!(
+            "{}: {} open, exposure {:.3}, today {:+.3}",
+            profile,
+            open.len(),
+            exposure,
+            today
+        ));
+    }
+    Ok(lines.join("\n"))
+}
+
+/// Logs a verdict, both to stdout and to `risk_decisions`.
+pub fn record(db: &Connection, order: &Order, verdict: &Verdict) -> rusqlite::Result<()> {
+    println!(
+        "[RISK] {} {} {} {} for {}: {}",
+        order.profile,
+        if verdict.approved { "approved" } else { "rejected" },
+        order.side,
+        order.contract_address,
+        order.size,
+        verdict.reason
+    );
+    db.execute(
+        "INSERT INTO risk_decisions (profile, side, contract_address, requested_size, approved_size, approved, reason)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            order.profile,
+            order.side.to_string(),
+            order.contract_address,
+            order.size,
+            verdict.size,
+            verdict.approved,
+            verdict.reason
+        ],
+    )?;
+    Ok(())
+}
+
+/// Reviews and logs `order` and, if approved, reserves its paper position
+/// at the approved size. Review and reservation happen in one call on the
+/// database thread, so concurrent orders cannot both slip under a limit.
+/// The caller then fills the position with [`paper::execute_open`].
+pub fn reserve_position(db: &Connection, config: &RiskConfig, order: &Order, now: i64) -> rusqlite::Result<Verdict> {
+    let verdict = review(db, config, order, now)?;
+    record(db, order, &verdict)?;
+    if verdict.approved {
+        paper::open(db, order.profile, order.contract_address, order.creator, verdict.size, now)?;
+    }
+    Ok(verdict)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::Fill;
+    use crate::migrations;
+
+    const NOW: i64 = 1_700_000_000;
+    const COINS: [&str; 3] = [
+        "So11111111111111111111111111111111111111112",
+        "4k3Dyjzvzp8eMZWUXbBCjEvwSkkk59S5iCNLY3QrkX6R",
+        "JUPyiwrYJFskUPiHa7hkeR8VUtAeFoSYbKedZNsDvCN",
+    ];
+
+    fn db() -> Connection {
+        let mut db = Connection::open_in_memory().unwrap();
+        migrations::migrate(&mut db).unwrap();
+        db
+    }
+
+    fn coin(i: usize) -> Address {
+        COINS[i].parse().unwrap()
+    }
+
+    fn fill(side: Side, amount_in: f64, amount_out: f64) -> Fill {
+        Fill {
+            executor: "paper",
+            side,
+            amount_in,
+            amount_out,
+            fee: 0.0,
+            price: 0.001,
+            slippage_bps: 0.0,
+        }
+    }
+
+    fn buy<'a>(address: &'a Address, creator: &'a Address, size: f64) -> Order<'a> {
+        Order {
+            profile: "default",
+            side: Side::Buy,
+            contract_address: address,
+            creator,
+            size,
+        }
+    }
+
+    /// Reserves and fills a buy, as the pipeline does.
+    fn hold(db: &Connection, config: &RiskConfig, address: &Address, creator: &Address, size: f64) {
+        let verdict = reserve_position(db, config, &buy(address, creator, size), NOW).unwrap();
+        assert!(verdict.approved, "{}", verdict.reason);
+        paper::record_fill(db, "default", address, &fill(Side::Buy, verdict.size, 100.0)).unwrap();
+    }
+
+    fn rejected(verdict: Verdict, reason: &str) {
+        assert!(!verdict.approved);
+        assert!(verdict.reason.contains(reason), "{}", verdict.reason);
+    }
+
+    #[test]
+    fn orders_within_limits_are_approved_and_oversized_ones_cut() {
+        let (db, config, creator) = (db(), RiskConfig::default(), Address::evm_zero());
+        let verdict = review(&db, &config, &buy(&coin(0), &creator, 0.2), NOW).unwrap();
+        assert_eq!(verdict, Verdict::approve(0.2, "within limits"));
+        let verdict = review(&db, &config, &buy(&coin(0), &creator, 2.0), NOW).unwrap();
+        assert!(verdict.approved);
+        assert_eq!(verdict.size, config.max_position_size);
+    }
+
+    #[test]
+    fn sizes_must_be_finite_and_positive() {
+        let (db, config, creator) = (db(), RiskConfig::default(), Address::evm_zero());
+        for size in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            rejected(review(&db, &config, &buy(&coin(0), &creator, size), NOW).unwrap(), "positive");
+        }
+    }
+
+    #[test]
+    fn kill_switch_halts_buys_until_released() {
+        let (db, config, creator) = (db(), RiskConfig::default(), Address::evm_zero());
+        let switch = KillSwitch {
+            reason: "drill".to_string(),
+            set_by: "test".to_string(),
+            set_at: NOW,
+        };
+        set_kill_switch(&db, Some(&switch)).unwrap();
+        rejected(review(&db, &config, &buy(&coin(0), &creator, 0.1), NOW).unwrap(), "kill switch engaged: drill");
+        set_kill_switch(&db, None).unwrap();
+        assert!(review(&db, &config, &buy(&coin(0), &creator, 0.1), NOW).unwrap().approved);
+    }
+
+    #[test]
+    fn open_position_limit_rejects() {
+        let db = db();
+        let config = RiskConfig {
+            max_open_positions: 1,
+            ..RiskConfig::default()
+        };
+        hold(&db, &config, &coin(0), &Address::evm_zero(), 0.1);
+        rejected(review(&db, &config, &buy(&coin(1), &coin(2), 0.1), NOW).unwrap(), "open positions 1 >= 1");
+    }
+
+    #[test]
+    fn creator_exposure_limit_cuts_then_rejects() {
+        let (db, config, creator) = (db(), RiskConfig::default(), Address::evm_zero());
+        hold(&db, &config, &coin(0), &creator, 0.3);
+        let verdict = review(&db, &config, &buy(&coin(1), &creator, 0.3), NOW).unwrap();
+        assert!(verdict.approved);
+        assert!((verdict.size - 0.2).abs() < 1e-9);
+        hold(&db, &config, &coin(1), &creator, 0.3);
+        rejected(review(&db, &config, &buy(&coin(2), &creator, 0.1), NOW).unwrap(), "creator exposure");
+    }
+
+    #[test]
+    fn daily_loss_limit_rejects() {
+        let db = db();
+        let config = RiskConfig {
+            daily_loss_limit: 0.4,
+            ..RiskConfig::default()
+        };
+        let creator = Address::evm_zero();
+        hold(&db, &config, &coin(0), &creator, 0.5);
+        paper::close(&db, "default", &coin(0), &fill(Side::Sell, 100.0, 0.0), NOW).unwrap();
+        rejected(review(&db, &config, &buy(&coin(1), &creator, 0.1), NOW).unwrap(), "daily loss 0.500 hit limit");
+    }
+
+    #[test]
+    fn sells_need_a_filled_open_position() {
+        let (db, config, creator) = (db(), RiskConfig::default(), Address::evm_zero());
+        let address = coin(0);
+        let sell = |size| Order {
+            profile: "default",
+            side: Side::Sell,
+            contract_address: &address,
+            creator: &creator,
+            size,
+        };
+        rejected(review(&db, &config, &sell(0.1), NOW).unwrap(), "no open position");
+        paper::open(&db, "default", &address, &creator, 0.1, NOW).unwrap();
+        rejected(review(&db, &config, &sell(0.1), NOW).unwrap(), "buy has not filled");
+    }
+
+    #[test]
+    fn closed_positions_are_not_bought_again() {
+        let (db, config, creator) = (db(), RiskConfig::default(), Address::evm_zero());
+        hold(&db, &config, &coin(0), &creator, 0.25);
+        rejected(review(&db, &config, &buy(&coin(0), &creator, 0.25), NOW).unwrap(), "position already open");
+        paper::close(&db, "default", &coin(0), &fill(Side::Sell, 100.0, 0.5), NOW).unwrap();
+
+        rejected(
+            reserve_position(&db, &config, &buy(&coin(0), &creator, 0.4), NOW).unwrap(),
+            "position already closed",
+        );
+        let position = paper::position(&db, "default", &coin(0)).unwrap().unwrap();
+        assert!(position.closed);
+        assert_eq!(position.size, 0.25);
+        assert_eq!(position.multiple, Some(2.0));
+    }
+}
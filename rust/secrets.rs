@@ -0,0 +1,329 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+/// Environment variable checked for the keystore passphrase before
+/// prompting on the terminal.
+pub const PASSPHRASE_ENV: &str = "PUMPFUN_KEYSTORE_PASSPHRASE";
+pub const DEFAULT_KEYSTORE: &str = "keystore.json";
+/// Config values starting with this name a keystore entry instead of
+/// holding the secret itself, e.g. `PUMPFUN_KEY = secret:pumpfun`.
+pub const REFERENCE_PREFIX: &str = "secret:";
+
+const FORMAT_VERSION: u32 = 1;
+
+/// A key or token. `Debug` never prints the value, so configs holding
+/// secrets can be logged safely; use [`Secret::expose`] where the value is
+/// actually sent somewhere.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Secret(value.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            f.write_str("Secret(<unset>)")
+        } else {
+            f.write_str("Secret(<redacted>)")
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum KeystoreError {
+    Io(io::Error),
+    /// The file is not a keystore this version can read.
+    Format(String),
+    /// Decryption failed: a wrong passphrase or a tampered file.
+    WrongPassphrase,
+    AlreadyExists(PathBuf),
+}
+
+impl fmt::Display for KeystoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeystoreError::Io(e) => write!(f, "{}", e),
+            KeystoreError::Format(reason) => write!(f, "invalid keystore: {}", reason),
+            KeystoreError::WrongPassphrase => write!(f, "wrong passphrase or corrupted keystore"),
+            KeystoreError::AlreadyExists(path) => write!(f, "{} already exists", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for KeystoreError {}
+
+impl From<io::Error> for KeystoreError {
+    fn from(e: io::Error) -> Self {
+        KeystoreError::Io(e)
+    }
+}
+
+/// What is written to disk. The key is derived from the passphrase with
+/// Argon2id and the entries are sealed with XChaCha20-Poly1305, so both the
+/// values and the entry names stay hidden.
+#[derive(Serialize, Deserialize)]
+struct KeystoreFile {
+    version: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// An unlocked keystore. Changes stay in memory until [`Keystore::save`].
+pub struct Keystore {
+    path: PathBuf,
+    salt: [u8; 16],
+    key: [u8; 32],
+    entries: BTreeMap<String, String>,
+}
+
+impl fmt::Debug for Keystore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Keystore")
+            .field("path", &self.path)
+            .field("names", &self.entries.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+fn derive_key(passphrase: &Secret, salt: &[u8]) -> Result<[u8; 32], KeystoreError> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.expose().as_bytes(), salt, &mut key)
+        .map_err(|e| KeystoreError::Format(e.to_string()))?;
+    Ok(key)
+}
+
+fn decode(field: &str, value: &str) -> Result<Vec<u8>, KeystoreError> {
+    BASE64
+        .decode(value)
+        .map_err(|e| KeystoreError::Format(format!("{}: {}", field, e)))
+}
+
+impl Keystore {
+    /// Starts an empty keystore at `path`, which must not exist yet.
+    pub fn create(path: &Path, passphrase: &Secret) -> Result<Self, KeystoreError> {
+        if path.exists() {
+            return Err(KeystoreError::AlreadyExists(path.to_path_buf()));
+        }
+        let mut salt = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut salt);
+        Ok(Keystore {
+            path: path.to_path_buf(),
+            salt,
+            key: derive_key(passphrase, &salt)?,
+            entries: BTreeMap::new(),
+        })
+    }
+
+    pub fn open(path: &Path, passphrase: &Secret) -> Result<Self, KeystoreError> {
+        let file: KeystoreFile =
+            serde_json::from_str(&fs::read_to_string(path)?).map_err(|e| KeystoreError::Format(e.to_string()))?;
+        if file.version != FORMAT_VERSION {
+            return Err(KeystoreError::Format(format!("unsupported version {}", file.version)));
+        }
+        let salt: [u8; 16] = decode("salt", &file.salt)?
+            .try_into()
+            .map_err(|_| KeystoreError::Format("salt must be 16 bytes".to_string()))?;
+        let nonce = decode("nonce", &file.nonce)?;
+        if nonce.len() != 24 {
+            return Err(KeystoreError::Format("nonce must be 24 bytes".to_string()));
+        }
+        let key = derive_key(passphrase, &salt)?;
+        let plaintext = XChaCha20Poly1305::new(&key.into())
+            .decrypt(XNonce::from_slice(&nonce), decode("ciphertext", &file.ciphertext)?.as_slice())
+            .map_err(|_| KeystoreError::WrongPassphrase)?;
+        let entries = serde_json::from_slice(&plaintext).map_err(|e| KeystoreError::Format(e.to_string()))?;
+        Ok(Keystore {
+            path: path.to_path_buf(),
+            salt,
+            key,
+            entries,
+        })
+    }
+
+    /// Re-encrypts every entry under a fresh nonce and replaces the file.
+    pub fn save(&self) -> Result<(), KeystoreError> {
+        let mut nonce = [0u8; 24];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let plaintext = serde_json::to_vec(&self.entries).map_err(|e| KeystoreError::Format(e.to_string()))?;
+        let ciphertext = XChaCha20Poly1305::new(&self.key.into())
+            .encrypt(XNonce::from_slice(&nonce), plaintext.as_slice())
+            .map_err(|_| KeystoreError::Format("encryption failed".to_string()))?;
+        let file = KeystoreFile {
+            version: FORMAT_VERSION,
+            salt: BASE64.encode(self.salt),
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        };
+        let json = serde_json::to_string_pretty(&file).map_err(|e| KeystoreError::Format(e.to_string()))?;
+        // Write beside the target and rename, so a crash never leaves a
+        // half-written keystore behind.
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, json)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&tmp, fs::Permissions::from_mode(0o600))?;
+        }
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<Secret> {
+        self.entries.get(name).map(|value| Secret::new(value.as_str()))
+    }
+
+    pub fn set(&mut self, name: &str, value: &Secret) {
+        self.entries.insert(name.to_string(), value.expose().to_string());
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.entries.remove(name).is_some()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+}
+
+/// Reads the passphrase from [`PASSPHRASE_ENV`], or prompts for it. With
+/// `confirm` the prompt asks twice, for creating a keystore.
+pub fn passphrase(confirm: bool) -> Result<Secret, Box<dyn std::error::Error>> {
+    if let Ok(value) = std::env::var(PASSPHRASE_ENV) {
+        if value.is_empty() {
+            return Err(format!("{} is set but empty", PASSPHRASE_ENV).into());
+        }
+        return Ok(Secret::new(value));
+    }
+    let value = rpassword::prompt_password("Keystore passphrase: ")
+        .map_err(|e| format!("no passphrase: set {} or run from a terminal ({})", PASSPHRASE_ENV, e))?;
+    if value.is_empty() {
+        return Err("passphrase must not be empty".into());
+    }
+    if confirm && rpassword::prompt_password("Repeat passphrase: ")? != value {
+        return Err("passphrases do not match".into());
+    }
+    Ok(Secret::new(value))
+}
+
+/// Turns config values into secrets, unlocking the keystore the first time
+/// a `secret:` reference needs it. Configs without references never touch
+/// the keystore or ask for a passphrase.
+pub struct Resolver {
+    path: PathBuf,
+    keystore: Option<Keystore>,
+}
+
+impl Resolver {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Resolver {
+            path: path.into(),
+            keystore: None,
+        }
+    }
+
+    /// `key` names the setting in errors, e.g. `API.PUMPFUN_KEY`.
+    pub fn resolve(&mut self, key: &str, value: &str) -> Result<Secret, Box<dyn std::error::Error>> {
+        let value = value.trim();
+        let Some(name) = value.strip_prefix(REFERENCE_PREFIX) else {
+            return Ok(Secret::new(value));
+        };
+        let keystore = match &mut self.keystore {
+            Some(keystore) => keystore,
+            keystore => {
+                if !self.path.exists() {
+                    return Err(format!("{} refers to the keystore, but {} does not exist", key, self.path.display()).into());
+                }
+                let opened = Keystore::open(&self.path, &passphrase(false)?)
+                    .map_err(|e| format!("{}: {}", self.path.display(), e))?;
+                println!("[SECURITY] Unlocked keystore {}", self.path.display());
+                keystore.insert(opened)
+            }
+        };
+        keystore
+            .get(name.trim())
+            .ok_or_else(|| format!("{}: no secret named '{}' in {}", key, name.trim(), self.path.display()).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keystore_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("keystore_{}_{}.json", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn entries_survive_a_save_and_reopen() {
+        let path = keystore_path("roundtrip");
+        let passphrase = Secret::new("correct horse");
+        let mut keystore = Keystore::create(&path, &passphrase).unwrap();
+        keystore.set("pumpfun", &Secret::new("pf-token"));
+        keystore.save().unwrap();
+
+        let on_disk = fs::read_to_string(&path).unwrap();
+        assert!(!on_disk.contains("pf-token") && !on_disk.contains("pumpfun"));
+        let reopened = Keystore::open(&path, &passphrase).unwrap();
+        assert_eq!(reopened.get("pumpfun"), Some(Secret::new("pf-token")));
+        assert_eq!(reopened.names().collect::<Vec<_>>(), ["pumpfun"]);
+        assert!(matches!(Keystore::create(&path, &passphrase), Err(KeystoreError::AlreadyExists(_))));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn a_wrong_passphrase_is_an_error() {
+        let path = keystore_path("wrong");
+        let mut keystore = Keystore::create(&path, &Secret::new("correct horse")).unwrap();
+        keystore.set("pumpfun", &Secret::new("pf-token"));
+        keystore.save().unwrap();
+        let opened = Keystore::open(&path, &Secret::new("battery staple"));
+        assert!(matches!(opened, Err(KeystoreError::WrongPassphrase)), "{:?}", opened);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn debug_output_hides_values() {
+        assert_eq!(format!("{:?}", Secret::new("pf-token")), "Secret(<redacted>)");
+        assert_eq!(format!("{:?}", Secret::default()), "Secret(<unset>)");
+        let path = keystore_path("debug");
+        let mut keystore = Keystore::create(&path, &Secret::new("correct horse")).unwrap();
+        keystore.set("pumpfun", &Secret::new("pf-token"));
+        let debug = format!("{:?}", keystore);
+        assert!(debug.contains("pumpfun") && !debug.contains("pf-token"), "{}", debug);
+    }
+
+    #[test]
+    fn an_empty_passphrase_from_the_environment_is_rejected() {
+        std::env::set_var(PASSPHRASE_ENV, "");
+        let result = passphrase(false);
+        std::env::remove_var(PASSPHRASE_ENV);
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,626 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::{self, BoxFuture};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+use crate::address::Address;
+use crate::secrets::Secret;
+
+pub type FetchError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    Twitter,
+    Telegram,
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Platform::Twitter => "twitter",
+            Platform::Telegram => "telegram",
+        })
+    }
+}
+
+impl FromStr for Platform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "twitter" => Ok(Platform::Twitter),
+            "telegram" => Ok(Platform::Telegram),
+            other => Err(format!("unknown platform '{}'", other)),
+        }
+    }
+}
+
+/// Extracts the handle from what a coin lists for `platform`: a bare
+/// handle, `@handle` or a profile URL. Returns `None` for links that are
+/// not a profile, such as a single post.
+pub fn parse_handle(platform: Platform, raw: &str) -> Option<String> {
+    let raw = raw.trim();
+    let path = ["https://", "http://", "www."]
+        .iter()
+        .fold(raw, |s, prefix| s.strip_prefix(prefix).unwrap_or(s));
+    let hosts: &[&str] = match platform {
+        Platform::Twitter => &["twitter.com/", "x.com/", "mobile.twitter.com/"],
+        Platform::Telegram => &["t.me/", "telegram.me/"],
+    };
+    let path = hosts.iter().find_map(|host| path.strip_prefix(host)).unwrap_or(path);
+    let handle = path.trim_start_matches('@').split(['?', '#']).next()?.trim_end_matches('/');
+    let valid = !handle.is_empty() && handle.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    valid.then(|| handle.to_lowercase())
+}
+
+/// One of a sample of an account's followers, enough to judge whether it is
+/// a real user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Follower {
+    pub followers: i64,
+    pub following: i64,
+    pub posts: i64,
+    /// Unix seconds.
+    pub created_at: i64,
+    #[serde(default)]
+    pub default_avatar: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Post {
+    pub id: String,
+    pub text: String,
+    #[serde(default)]
+    pub likes: i64,
+    /// Retweets, or forwards on Telegram.
+    #[serde(default)]
+    pub reposts: i64,
+    #[serde(default)]
+    pub replies: i64,
+    /// Unix seconds.
+    pub posted_at: i64,
+}
+
+/// An account as a provider reports it. For a Telegram channel `followers`
+/// is the member count.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Account {
+    pub followers: i64,
+    #[serde(default)]
+    pub following: i64,
+    /// Unix seconds.
+    pub created_at: i64,
+    #[serde(default)]
+    pub verified: bool,
+    /// Most recent posts, newest first.
+    #[serde(default)]
+    pub posts: Vec<Post>,
+    #[serde(default)]
+    pub follower_sample: Vec<Follower>,
+}
+
+/// Looks up social accounts. Implementations return `Ok(None)` for an
+/// account that does not exist.
+pub trait SocialProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn fetch<'a>(&'a self, platform: Platform, handle: &'a str) -> BoxFuture<'a, Result<Option<Account>, FetchError>>;
+}
+
+/// Serves accounts from a JSON file keyed by `platform:handle`, for tests
+/// and for replaying collected data.
+pub struct FixtureProvider {
+    accounts: HashMap<String, Account>,
+}
+
+impl FixtureProvider {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let contents = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let accounts: HashMap<String, Account> =
+            serde_json::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(FixtureProvider {
+            accounts: accounts.into_iter().map(|(key, account)| (key.to_lowercase(), account)).collect(),
+        })
+    }
+}
+
+impl SocialProvider for FixtureProvider {
+    fn name(&self) -> &'static str {
+        "fixture"
+    }
+
+    fn fetch<'a>(&'a self, platform: Platform, handle: &'a str) -> BoxFuture<'a, Result<Option<Account>, FetchError>> {
+        let account = self.accounts.get(&format!("{}:{}", platform, handle)).cloned();
+        Box::pin(future::ready(Ok(account)))
+    }
+}
+
+/// Fetches `GET {base_url}/{platform}/{handle}`, expecting an [`Account`]
+/// as JSON and 404 for unknown handles. Scraping services and Twitter API
+/// wrappers sit behind a small adapter exposing this shape.
+pub struct HttpProvider {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: Secret,
+}
+
+impl HttpProvider {
+    pub fn new(base_url: &str, api_key: Secret, timeout: Duration) -> Self {
+        HttpProvider {
+            client: reqwest::Client::builder().timeout(timeout).build().unwrap_or_default(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+        }
+    }
+}
+
+impl SocialProvider for HttpProvider {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    fn fetch<'a>(&'a self, platform: Platform, handle: &'a str) -> BoxFuture<'a, Result<Option<Account>, FetchError>> {
+        Box::pin(async move {
+            let mut request = self.client.get(format!("{}/{}/{}", self.base_url, platform, handle));
+            if !self.api_key.is_empty() {
+                request = request.bearer_auth(self.api_key.expose());
+            }
+            let response = request.send().await?;
+            if response.status() == reqwest::StatusCode::NOT_FOUND {
+                return Ok(None);
+            }
+            Ok(Some(response.error_for_status()?.json().await?))
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProviderKind {
+    None,
+    Fixture,
+    Http,
+}
+
+impl FromStr for ProviderKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "none" | "" => Ok(ProviderKind::None),
+            "fixture" => Ok(ProviderKind::Fixture),
+            "http" => Ok(ProviderKind::Http),
+            other => Err(format!("unknown social provider '{}' (expected none, fixture or http)", other)),
+        }
+    }
+}
+
+/// `[SOCIAL]` settings.
+#[derive(Debug, Clone)]
+pub struct SocialConfig {
+    pub provider: ProviderKind,
+    pub fixture_path: String,
+    pub api_url: String,
+    pub api_key: Secret,
+    pub timeout: Duration,
+    /// Posts kept per account.
+    pub max_posts: usize,
+}
+
+impl Default for SocialConfig {
+    fn default() -> Self {
+        SocialConfig {
+            provider: ProviderKind::None,
+            fixture_path: "social_fixtures.json".to_string(),
+            api_url: String::new(),
+            api_key: Secret::default(),
+            timeout: Duration::from_secs(10),
+            max_posts: 20,
+        }
+    }
+}
+
+pub fn build(config: &SocialConfig) -> Result<Option<Arc<dyn SocialProvider>>, Box<dyn std::error::Error>> {
+    Ok(match config.provider {
+        ProviderKind::None => None,
+        ProviderKind::Fixture => Some(Arc::new(FixtureProvider::load(Path::new(&config.fixture_path))?)),
+        ProviderKind::Http => {
+            if config.api_url.is_empty() {
+                return Err("SOCIAL.API_URL is required for the http provider".into());
+            }
+            Some(Arc::new(HttpProvider::new(&config.api_url, config.api_key.clone(), config.timeout)))
+        }
+    })
+}
+
+const DAY_SECS: i64 = 24 * 60 * 60;
+
+/// Word lists for a rough sentiment score. Crude, but it only needs to tell
+/// hype from warnings in short crypto posts.
+const POSITIVE: &[&str] = &[
+    "bullish", "moon", "gem", "pump", "launch", "live", "community", "love", "great", "win", "up", "buy", "🚀",
+];
+const NEGATIVE: &[&str] = &[
+    "bearish", "rug", "rugged", "scam", "dump", "dead", "fake", "honeypot", "sell", "exit", "down", "hacked",
+];
+
+/// Sentiment of a post in [-1, 1]: the balance of positive and negative
+/// words, or 0 when there are neither.
+pub fn sentiment(text: &str) -> f64 {
+    let (mut positive, mut negative) = (0, 0);
+    for word in text.split_whitespace() {
+        let word = word
+            .trim_matches(|c: char| c.is_ascii_punctuation() && c != '#')
+            .trim_start_matches(['#', '$'])
+            .to_lowercase();
+        if POSITIVE.contains(&word.as_str()) {
+            positive += 1;
+        } else if NEGATIVE.contains(&word.as_str()) {
+            negative += 1;
+        }
+    }
+    if positive + negative == 0 {
+        0.0
+    } else {
+        (positive - negative) as f64 / (positive + negative) as f64
+    }
+}
+
+fn words_with_prefix(text: &str, prefixes: &[&str]) -> Vec<String> {
+    text.split_whitespace()
+        .filter(|w| prefixes.iter().any(|p| w.starts_with(p)))
+        .map(|w| w.trim_end_matches(|c: char| c.is_ascii_punctuation() && c != '/').to_string())
+        .collect()
+}
+
+/// Whether a follower looks automated: at least two of a young account, a
+/// default avatar, almost no posts, and following far more than follow it.
+pub fn looks_like_bot(follower: &Follower, now: i64) -> bool {
+    let signals = [
+        now - follower.created_at < 30 * DAY_SECS,
+        follower.default_avatar,
+        follower.posts < 5,
+        follower.following > 100 && follower.following > 10 * follower.followers.max(1),
+    ];
+    signals.iter().filter(|&&s| s).count() >= 2
+}
+
+/// What the pipeline keeps about one of a coin's accounts, and what the
+/// social filters and analysis see.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SocialMetrics {
+    pub platform: Platform,
+    pub handle: String,
+    pub followers: i64,
+    pub following: i64,
+    pub account_age_days: i64,
+    pub verified: bool,
+    /// Posts per day over the sampled posts.
+    pub post_frequency: f64,
+    /// Mean likes, reposts and replies per post, per follower. `None`
+    /// without posts.
+    pub engagement_rate: Option<f64>,
+    /// Share of sampled followers that look automated. `None` without a
+    /// sample.
+    pub bot_follower_ratio: Option<f64>,
+    /// Mean post sentiment in [-1, 1].
+    pub sentiment: f64,
+}
+
+impl SocialMetrics {
+    pub fn compute(platform: Platform, handle: &str, account: &Account, now: i64) -> Self {
+        let posts = &account.posts;
+        let post_frequency = match posts.iter().map(|p| p.posted_at).min() {
+            Some(oldest) => posts.len() as f64 / ((now - oldest) as f64 / DAY_SECS as f64).max(1.0),
+            None => 0.0,
+        };
+        let engagement_rate = (!posts.is_empty()).then(|| {
+            let interactions: i64 = posts.iter().map(|p| p.likes + p.reposts + p.replies).sum();
+            interactions as f64 / posts.len() as f64 / account.followers.max(1) as f64
+        });
+        let sample = &account.follower_sample;
+        let bot_follower_ratio = (!sample.is_empty())
+            .then(|| sample.iter().filter(|f| looks_like_bot(f, now)).count() as f64 / sample.len() as f64);
+        let sentiment = if posts.is_empty() {
+            0.0
+        } else {
+            posts.iter().map(|p| sentiment(&p.text)).sum::<f64>() / posts.len() as f64
+        };
+        SocialMetrics {
+            platform,
+            handle: handle.to_string(),
+            followers: account.followers,
+            following: account.following,
+            account_age_days: (now - account.created_at).max(0) / DAY_SECS,
+            verified: account.verified,
+            post_frequency,
+            engagement_rate,
+            bot_follower_ratio,
+            sentiment,
+        }
+    }
+
+    /// One line for alerts and analysis prompts.
+    pub fn describe(&self) -> String {
+        let mut line = format!(
+            "{} @{}: {} followers, {}d old, {:.1} posts/day",
+            self.platform, self.handle, self.followers, self.account_age_days, self.post_frequency
+        );
+        if self.verified {
+            line.push_str(", verified");
+        }
+        if let Some(rate) = self.engagement_rate {
+            line.push_str(&format!(", engagement {:.2}%", rate * 100.0));
+        }
+        if let Some(ratio) = self.bot_follower_ratio {
+            line.push_str(&format!(", {:.0}% bot-like followers", ratio * 100.0));
+        }
+        line.push_str(&format!(", sentiment {:+.2}", self.sentiment));
+        line
+    }
+}
+
+/// The account the social filters judge a coin by: Twitter when it has one,
+/// since that is where the community gathers, otherwise Telegram.
+pub fn primary(metrics: &[SocialMetrics]) -> Option<&SocialMetrics> {
+    metrics
+        .iter()
+        .find(|m| m.platform == Platform::Twitter)
+        .or_else(|| metrics.first())
+}
+
+/// An account collected for a coin, ready to store.
+#[derive(Debug, Clone)]
+pub struct Collected {
+    pub metrics: SocialMetrics,
+    pub posts: Vec<Post>,
+}
+
+/// Fetches each handle and computes its metrics. Handles the provider does
+/// not know, or that fail, are logged and left out.
+pub async fn collect(
+    provider: &dyn SocialProvider,
+    handles: &[(Platform, String)],
+    max_posts: usize,
+    now: i64,
+) -> Vec<Collected> {
+    let mut collected = Vec::new();
+    for (platform, handle) in handles {
+        match provider.fetch(*platform, handle).await {
+            Ok(Some(mut account)) => {
+                account.posts.truncate(max_posts);
+                let metrics = SocialMetrics::compute(*platform, handle, &account, now);
+                println!("[SOCIAL] {}", metrics.describe());
+                collected.push(Collected {
+                    metrics,
+                    posts: account.posts,
+                });
+            }
+            Ok(None) => println!("[SOCIAL] {} @{} not found via {}", platform, handle, provider.name()),
+            Err(e) => println!("[SOCIAL] Fetching {} @{} failed: {}", platform, handle, e),
+        }
+    }
+    collected
+}
+
+/// The tables first laid out by the Python bot, keyed by platform as well
+/// so a coin can keep both its Twitter and Telegram accounts.
+pub fn create_tables(db: &Connection) -> rusqlite::Result<()> {
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS twitter_metrics (
+            coin_address TEXT NOT NULL,
+            platform TEXT NOT NULL DEFAULT 'twitter',
+            twitter_handle TEXT NOT NULL,
+            follower_count INTEGER,
+            following_count INTEGER,
+            sentiment_score REAL,
+            post_frequency REAL,
+            verified BOOLEAN,
+            account_age_days INTEGER,
+            engagement_rate REAL,
+            bot_follower_ratio REAL,
+            collected_at INTEGER NOT NULL,
+            PRIMARY KEY (coin_address, platform)
+        )",
+        [],
+    )?;
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS twitter_posts (
+            post_id TEXT NOT NULL,
+            platform TEXT NOT NULL DEFAULT 'twitter',
+            coin_address TEXT NOT NULL,
+            content TEXT,
+            likes INTEGER,
+            retweets INTEGER,
+            replies INTEGER,
+            timestamp INTEGER,
+            sentiment REAL,
+            hashtags TEXT,
+            links TEXT,
+            PRIMARY KEY (platform, post_id)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Stores a coin's collected accounts, replacing earlier collections.
+pub fn save(db: &Connection, coin: &Address, collected: &[Collected], now: i64) -> rusqlite::Result<()> {
+    for Collected { metrics: m, posts } in collected {
+        db.execute(
+            "INSERT OR REPLACE INTO twitter_metrics (coin_address, platform, twitter_handle, follower_count,
+                following_count, sentiment_score, post_frequency, verified, account_age_days, engagement_rate,
+                bot_follower_ratio, collected_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                coin,
+                m.platform.to_string(),
+                m.handle,
+                m.followers,
+                m.following,
+                m.sentiment,
+                m.post_frequency,
+                m.verified,
+                m.account_age_days,
+                m.engagement_rate,
+                m.bot_follower_ratio,
+                now
+            ],
+        )?;
+        for post in posts {
+            db.execute(
+                "INSERT OR REPLACE INTO twitter_posts (post_id, platform, coin_address, content, likes, retweets,
+                    replies, timestamp, sentiment, hashtags, links)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                params![
+                    post.id,
+                    m.platform.to_string(),
+                    coin,
+                    post.text,
+                    post.likes,
+                    post.reposts,
+                    post.replies,
+                    post.posted_at,
+                    sentiment(&post.text),
+                    words_with_prefix(&post.text, &["#"]).join(" "),
+                    words_with_prefix(&post.text, &["http://", "https://"]).join(" ")
+                ],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// The stored accounts of a coin.
+pub fn load(db: &Connection, coin: &Address) -> rusqlite::Result<Vec<SocialMetrics>> {
+    let mut stmt = db.prepare(
+        "SELECT platform, twitter_handle, follower_count, following_count, account_age_days, verified,
+                post_frequency, engagement_rate, bot_follower_ratio, sentiment_score
+         FROM twitter_metrics WHERE coin_address = ?1 ORDER BY platform DESC",
+    )?;
+    let rows = stmt.query_map(params![coin], |row| {
+        let platform: String = row.get(0)?;
+        Ok(SocialMetrics {
+            platform: platform.parse().unwrap_or(Platform::Twitter),
+            handle: row.get(1)?,
+            followers: row.get(2)?,
+            following: row.get(3)?,
+            account_age_days: row.get(4)?,
+            verified: row.get(5)?,
+            post_frequency: row.get(6)?,
+            engagement_rate: row.get(7)?,
+            bot_follower_ratio: row.get(8)?,
+            sentiment: row.get(9)?,
+        })
+    })?;
+    rows.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filters::SocialFilters;
+    use serde_json::json;
+
+    const NOW: i64 = 1_700_000_000;
+
+    /// Writes the accounts to a fixture file, as `SOCIAL.FIXTURE_PATH` would
+    /// point at, and loads it.
+    fn fixture(name: &str) -> FixtureProvider {
+        let accounts = json!({
+            "twitter:Gem_Coin": {
+                "followers": 1000,
+                "following": 50,
+                "created_at": NOW - 100 * DAY_SECS,
+                "posts": [
+                    {"id": "2", "text": "Launch is live 🚀", "likes": 10, "reposts": 5, "replies": 5,
+                     "posted_at": NOW - DAY_SECS},
+                    {"id": "1", "text": "Looks like a rug.", "likes": 30, "replies": 10,
+                     "posted_at": NOW - 4 * DAY_SECS}
+                ],
+                "follower_sample": [
+                    {"followers": 0, "following": 800, "posts": 0, "created_at": NOW - DAY_SECS,
+                     "default_avatar": true},
+                    {"followers": 100, "following": 50, "posts": 500, "created_at": NOW - 1000 * DAY_SECS}
+                ]
+            },
+            "telegram:fresh_chan": {"followers": 20, "created_at": NOW - 2 * DAY_SECS}
+        });
+        let path = std::env::temp_dir().join(format!("social_{}_{}.json", name, std::process::id()));
+        std::fs::write(&path, accounts.to_string()).unwrap();
+        let provider = FixtureProvider::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        provider
+    }
+
+    #[test]
+    fn handles_are_parsed_from_profile_links() {
+        for raw in ["https://twitter.com/Gem_Coin", "https://x.com/gem_coin/", "@Gem_Coin", "www.x.com/gem_coin?s=21"] {
+            assert_eq!(parse_handle(Platform::Twitter, raw).as_deref(), Some("gem_coin"), "{}", raw);
+        }
+        assert_eq!(parse_handle(Platform::Twitter, "https://x.com/gem_coin/status/123"), None);
+        for raw in ["https://t.me/fresh_chan", "telegram.me/Fresh_Chan", "@fresh_chan"] {
+            assert_eq!(parse_handle(Platform::Telegram, raw).as_deref(), Some("fresh_chan"), "{}", raw);
+        }
+        // Invite links do not name a channel.
+        assert_eq!(parse_handle(Platform::Telegram, "https://t.me/+AbCdEf123"), None);
+    }
+
+    #[tokio::test]
+    async fn metrics_are_computed_from_fixture_accounts() {
+        let provider = fixture("metrics");
+        let handles = [
+            (Platform::Twitter, "gem_coin".to_string()),
+            (Platform::Telegram, "fresh_chan".to_string()),
+            (Platform::Twitter, "unknown".to_string()),
+        ];
+        let collected = collect(&provider, &handles, 20, NOW).await;
+        assert_eq!(collected.len(), 2);
+
+        let twitter = &collected[0].metrics;
+        assert_eq!((twitter.followers, twitter.following, twitter.account_age_days), (1000, 50, 100));
+        assert_eq!(twitter.post_frequency, 0.5);
+        assert_eq!(twitter.engagement_rate, Some(0.03));
+        assert_eq!(twitter.bot_follower_ratio, Some(0.5));
+        assert_eq!(twitter.sentiment, 0.0);
+
+        let telegram = &collected[1].metrics;
+        assert_eq!((telegram.followers, telegram.account_age_days), (20, 2));
+        assert_eq!(telegram.post_frequency, 0.0);
+        assert_eq!((telegram.engagement_rate, telegram.bot_follower_ratio), (None, None));
+    }
+
+    #[tokio::test]
+    async fn social_filters_judge_the_primary_account() {
+        let provider = fixture("filters");
+        let handles = [
+            (Platform::Telegram, "fresh_chan".to_string()),
+            (Platform::Twitter, "gem_coin".to_string()),
+        ];
+        let collected = collect(&provider, &handles, 20, NOW).await;
+        let metrics: Vec<SocialMetrics> = collected.into_iter().map(|c| c.metrics).collect();
+        let filters = |min_followers, min_account_age_days| SocialFilters {
+            min_followers,
+            min_account_age_days,
+            ..SocialFilters::default()
+        };
+
+        // Twitter is judged even though Telegram is listed first.
+        assert_eq!(filters(500, 30).rejection(&metrics), None);
+        assert_eq!(filters(5000, 0).rejection(&metrics).as_deref(), Some("followers 1000 < 5000"));
+        assert_eq!(filters(0, 365).rejection(&metrics).as_deref(), Some("social account 100d old < 365d"));
+        assert_eq!(filters(500, 0).rejection(&metrics[..1]).as_deref(), Some("followers 20 < 500"));
+        assert_eq!(filters(500, 0).rejection(&[]).as_deref(), Some("no social accounts"));
+        let strict_bots = SocialFilters {
+            max_bot_follower_ratio: 0.25,
+            ..SocialFilters::default()
+        };
+        assert_eq!(strict_bots.rejection(&metrics).as_deref(), Some("bot-like followers 0.50 > 0.25"));
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::{HashSet, VecDeque};
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::profiles::DEFAULT_PROFILE;
+use crate::RawCoinData;
+
+/// Position of the newest migration the loop has fully processed. Migrations
+/// are ordered by time, then contract address, so equal timestamps are safe.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Cursor {
+    pub migration_time: i64,
+    pub contract_address: String,
+}
+
+impl Cursor {
+    pub fn of(raw: &RawCoinData) -> Option<Cursor> {
+        let time = chrono::DateTime::parse_from_rfc3339(raw.migration_time.as_deref()?).ok()?;
+        Some(Cursor {
+            migration_time: time.timestamp(),
+            contract_address: raw.contract_address.clone()?,
+        })
+    }
+}
+
+/// How many undated coins [`LoopState`] remembers, see `LoopState::undated`.
+const UNDATED_MEMORY: usize = 1000;
+
+/// A Telegram message waiting for delivery, routed by the profile that
+/// raised it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "StoredAlert")]
+pub struct Alert {
+    pub profile: String,
+    pub text: String,
+}
+
+/// Queues saved before profiles existed hold bare strings; those belong to
+/// the default profile.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredAlert {
+    Routed { profile: String, text: String },
+    Legacy(String),
+}
+
+impl From<StoredAlert> for Alert {
+    fn from(stored: StoredAlert) -> Self {
+        match stored {
+            StoredAlert::Routed { profile, text } => Alert { profile, text },
+            StoredAlert::Legacy(text) => Alert {
+                profile: DEFAULT_PROFILE.to_string(),
+                text,
+            },
+        }
+    }
+}
+
+/// Everything the monitor loop needs to resume where it stopped: the cursor,
+/// coins fetched but not yet processed, and alerts not yet delivered.
+///
+/// Coins stay in `pending_coins` until a worker has finished them, so a coin
+/// that was in flight when the process died is processed again on restart.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct LoopState {
+    pub cursor: Option<Cursor>,
+    pub pending_coins: VecDeque<RawCoinData>,
+    pub pending_alerts: VecDeque<Alert>,
+    /// Coins queued without a usable migration time. They are stamped with
+    /// the time they were first seen, and remembered so a coin the feed
+    /// keeps returning undated is only processed once. The stamp is not a
+    /// real migration time, so these coins never move the cursor.
+    #[serde(default)]
+    pub undated: VecDeque<String>,
+    #[serde(skip)]
+    in_flight: HashSet<Cursor>,
+    #[serde(skip)]
+    completed: HashSet<Cursor>,
+}
+
+impl LoopState {
+    /// Queues the coins in `raw` that lie past the cursor and are not already
+    /// pending, oldest first so the cursor only ever moves forward. A coin
+    /// with a missing or malformed migration time is queued as migrated now,
+    /// unless it has been queued undated before.
+    pub fn enqueue_new(&mut self, raw: Vec<RawCoinData>) -> usize {
+        let now = chrono::Utc::now().to_rfc3339();
+        let mut fresh: Vec<(Cursor, RawCoinData)> = raw
+            .into_iter()
+            .filter_map(|mut coin| {
+                if let Some(c) = Cursor::of(&coin) {
+                    return (self.cursor.as_ref() < Some(&c)).then_some((c, coin));
+                }
+                let Some(address) = coin.contract_address.clone() else {
+                    println!("[INGEST] Skipping migration without a contract address.");
+                    return None;
+                };
+                if self.undated.contains(&address) {
+                    return None;
+                }
+                println!(
+                    "[INGEST] {} has no valid migration time ({:?}); using the current time.",
+                    address, coin.migration_time
+                );
+                coin.migration_time = Some(now.clone());
+                self.undated.push_back(address);
+                if self.undated.len() > UNDATED_MEMORY {
+                    self.undated.pop_front();
+                }
+                Cursor::of(&coin).map(|c| (c, coin))
+            })
+            .filter(|(c, _)| {
+                !self
+                    .pending_coins
+                    .iter()
+                    .any(|p| Cursor::of(p).as_ref() == Some(c))
+            })
+            .collect();
+        fresh.sort_by(|a, b| a.0.cmp(&b.0));
+        let n = fresh.len();
+        self.pending_coins.extend(fresh.into_iter().map(|(_, coin)| coin));
+        n
+    }
+
+    /// True if `raw` is at or before the cursor, i.e. already processed.
+    pub fn is_behind_cursor(&self, raw: &RawCoinData) -> bool {
+        match (Cursor::of(raw), &self.cursor) {
+            (Some(c), Some(cur)) => &c <= cur,
+            _ => false,
+        }
+    }
+
+    /// Hands out the oldest pending coin no worker has picked up yet.
+    pub fn take_next(&mut self) -> Option<(Cursor, RawCoinData)> {
+        let next = self.pending_coins.iter().find_map(|raw| {
+            let c = Cursor::of(raw)?;
+            (!self.in_flight.contains(&c) && !self.completed.contains(&c)).then(|| (c, raw.clone()))
+        })?;
+        self.in_flight.insert(next.0.clone());
+        Some(next)
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Marks a coin finished. Workers complete out of order, so the cursor
+    /// only advances over the leading run of finished coins, and only to real
+    /// migration times.
+    pub fn complete(&mut self, cursor: Cursor) {
+        self.in_flight.remove(&cursor);
+        self.completed.insert(cursor);
+        while let Some(c) = self.pending_coins.front().and_then(Cursor::of) {
+            if !self.completed.remove(&c) {
+                break;
+            }
+            self.pending_coins.pop_front();
+            if self.cursor.as_ref() < Some(&c) && !self.undated.contains(&c.contract_address) {
+                self.cursor = Some(c);
+            }
+        }
+    }
+}
+
+pub fn create_table(db: &Connection) -> rusqlite::Result<()> {
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS bot_state (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL,
+            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+pub fn load(db: &Connection) -> rusqlite::Result<LoopState> {
+    let value: Option<String> = db
+        .query_row("SELECT value FROM bot_state WHERE key = 'loop'", [], |row| row.get(0))
+        .optional()?;
+    match value {
+        Some(json) => serde_json::from_str(&json).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e))
+        }),
+        None => Ok(LoopState::default()),
+    }
+}
+
+pub fn save(db: &Connection, state: &LoopState) -> rusqlite::Result<()> {
+    let json = serde_json::to_string(state).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    db.execute(
+        "INSERT OR REPLACE INTO bot_state (key, value, updated_at) VALUES ('loop', ?1, CURRENT_TIMESTAMP)",
+        params![json],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coin(address: &str, migration_time: Option<&str>) -> RawCoinData {
+        serde_json::from_value(serde_json::json!({
+            "contractAddress": address,
+            "migrationTime": migration_time,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn enqueues_coins_oldest_first() {
+        let mut state = LoopState::default();
+        let queued = state.enqueue_new(vec![
+            coin("B", Some("2024-05-01T10:00:05Z")),
+            coin("A", Some("2024-05-01T10:00:00Z")),
+        ]);
+        assert_eq!(queued, 2);
+        let order: Vec<_> = state.pending_coins.iter().map(|c| c.contract_address.clone().unwrap()).collect();
+        assert_eq!(order, ["A", "B"]);
+        assert_eq!(state.enqueue_new(vec![coin("A", Some("2024-05-01T10:00:00Z"))]), 0);
+    }
+
+    #[test]
+    fn undated_coins_are_queued_once_as_migrated_now() {
+        let mut state = LoopState::default();
+        let before = chrono::Utc::now().timestamp();
+        let queued = state.enqueue_new(vec![coin("A", None), coin("B", Some("yesterday"))]);
+        assert_eq!(queued, 2);
+        for raw in &state.pending_coins {
+            let cursor = Cursor::of(raw).expect("stamped with a valid time");
+            assert!(cursor.migration_time >= before);
+        }
+
+        // Processed and returned undated again by the next poll
+        while let Some((cursor, _)) = state.take_next() {
+            state.complete(cursor);
+        }
+        assert!(state.pending_coins.is_empty());
+        assert_eq!(state.enqueue_new(vec![coin("A", None), coin("B", Some("yesterday"))]), 0);
+    }
+
+    #[test]
+    fn undated_coins_do_not_move_the_cursor() {
+        let mut state = LoopState::default();
+        state.enqueue_new(vec![
+            coin("A", Some("2024-05-01T10:00:00Z")),
+            coin("U", None),
+            coin("B", Some("2024-05-01T10:00:05Z")),
+        ]);
+        while let Some((cursor, _)) = state.take_next() {
+            state.complete(cursor);
+        }
+        assert!(state.pending_coins.is_empty());
+        let newest = Cursor::of(&coin("B", Some("2024-05-01T10:00:05Z")));
+        assert_eq!(state.cursor, newest);
+
+        // A late coin past the last real migration is still picked up
+        assert_eq!(state.enqueue_new(vec![coin("C", Some("2024-05-01T10:00:07Z")), coin("U", None)]), 1);
+        assert_eq!(state.enqueue_new(vec![coin("A", Some("2024-05-01T10:00:00Z"))]), 0);
+    }
+
+    #[test]
+    fn coins_without_address_are_skipped() {
+        let mut state = LoopState::default();
+        let raw: RawCoinData = serde_json::from_value(serde_json::json!({ "migrationTime": null })).unwrap();
+        assert_eq!(state.enqueue_new(vec![raw]), 0);
+    }
+}
@@ -0,0 +1,269 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::Response;
+use serde::Deserialize;
+use tokio::sync::watch;
+
+use crate::address::Address;
+use crate::coins;
+use crate::db::DbHandle;
+use crate::executor::{Executor, Pool, Side, TradeRequest};
+use crate::paper;
+use crate::risk::{self, KillSwitch, Order, RiskConfig};
+use crate::tracker;
+use crate::unix_now;
+
+/// Profile name for orders placed by hand through `/buy` and `/sell`.
+pub const MANUAL_PROFILE: &str = "manual";
+
+/// Errors never include the request URL: it carries the bot token, and
+/// callers log them.
+pub async fn send_message(token: &str, chat_id: i64, text: &str) -> reqwest::Result<()> {
+    // 简单通过 HTTP GET 调用 Telegram Bot API 发送消息（也可使用 teloxide 库）
+    let url = format!(
+        "https://api.telegram.org/bot{}/sendMessage?chat_id={}&text={}",
+        token,
+        chat_id,
+        urlencoding::encode(text)
+    );
+    reqwest::Client::new()
+        .get(&url)
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await
+        .and_then(Response::error_for_status)
+        .map_err(reqwest::Error::without_url)?;
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct Updates {
+    result: Vec<Update>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Update {
+    update_id: i64,
+    message: Option<Message>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Message {
+    chat: Chat,
+    text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Chat {
+    id: i64,
+}
+
+async fn get_updates(token: &str, offset: i64) -> reqwest::Result<Vec<Update>> {
+    let url = format!(
+        "https://api.telegram.org/bot{}/getUpdates?offset={}&timeout=30&allowed_updates=%5B%22message%22%5D",
+        token, offset
+    );
+    // As in `send_message`, the token is stripped from errors.
+    let response = reqwest::Client::new()
+        .get(&url)
+        .timeout(Duration::from_secs(40))
+        .send()
+        .await
+        .and_then(Response::error_for_status)
+        .map_err(reqwest::Error::without_url)?;
+    let updates: Updates = response.json().await.map_err(reqwest::Error::without_url)?;
+    Ok(updates.result)
+}
+
+/// Long-polls for commands until `shutdown` fires. Only messages from
+/// `admin_chat` are acted on; everything else is ignored.
+pub async fn run_commands(
+    token: String,
+    admin_chat: i64,
+    db: DbHandle,
+    risk: RiskConfig,
+    executor: Arc<dyn Executor>,
+    max_slippage_bps: u32,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let mut offset = 0;
+    while !*shutdown.borrow() {
+        let updates = tokio::select! {
+            updates = get_updates(&token, offset) => updates,
+            _ = shutdown.changed() => break,
+        };
+        let updates = match updates {
+            Ok(updates) => updates,
+            Err(e) => {
+                println!("[TELEGRAM] Polling for commands failed: {}", e);
+                tokio::select! {
+                    _ = tokio::time::sleep(Duration::from_secs(5)) => {}
+                    _ = shutdown.changed() => {}
+                }
+                continue;
+            }
+        };
+        for update in updates {
+            offset = offset.max(update.update_id + 1);
+            let Some(message) = update.message else {
+                continue;
+            };
+            let (Some(text), true) = (message.text, message.chat.id == admin_chat) else {
+                continue;
+            };
+            let reply = handle_command(&text, &db, &risk, executor.as_ref(), max_slippage_bps).await;
+            if let Err(e) = send_message(&token, admin_chat, &reply).await {
+                println!("[TELEGRAM] Reply failed: {}", e);
+            }
+        }
+    }
+}
+
+async fn handle_command(
+    text: &str,
+    db: &DbHandle,
+    risk: &RiskConfig,
+    executor: &dyn Executor,
+    max_slippage_bps: u32,
+) -> String {
+    let mut words = text.split_whitespace();
+    // Commands may arrive as `/kill@SomeBot` in group chats.
+    let command = words.next().unwrap_or("").split('@').next().unwrap_or("");
+    let rest: Vec<&str> = words.collect();
+    let result = match command {
+        "/kill" => {
+            let reason = if rest.is_empty() {
+                "engaged from Telegram".to_string()
+            } else {
+                rest.join(" ")
+            };
+            println!("[RISK] Kill switch engaged from Telegram: {}", reason);
+            let switch = KillSwitch {
+                reason: reason.clone(),
+                set_by: "telegram".to_string(),
+                set_at: unix_now(),
+            };
+            db.call(move |db| risk::set_kill_switch(db, Some(&switch)))
+                .await
+                .map(|_| format!("Kill switch engaged: {}", reason))
+        }
+        "/resume" => {
+            println!("[RISK] Kill switch released from Telegram.");
+            db.call(|db| risk::set_kill_switch(db, None))
+                .await
+                .map(|_| "Kill switch released.".to_string())
+        }
+        "/risk" => db.call(|db| risk::summary(db, unix_now())).await,
+        "/buy" => {
+            let Some(address) = rest.first().and_then(|a| a.parse::<Address>().ok()) else {
+                return "Usage: /buy <contract address> [size]".to_string();
+            };
+            let size = rest.get(1).map_or(Ok(crate::profiles::DEFAULT_PAPER_SIZE), |s| s.parse::<f64>());
+            let Some(size) = size.ok().filter(|size| size.is_finite() && *size > 0.0) else {
+                return "Size must be a positive number.".to_string();
+            };
+            let (risk, lookup) = (risk.clone(), address.clone());
+            let reserved = db
+                .call(move |db| {
+                    let Some(coin) = coins::find(db, &lookup)? else {
+                        return Ok(None);
+                    };
+                    let creator = coin.creator_wallet.parse::<Address>().unwrap_or_else(|_| Address::evm_zero());
+                    let order = Order {
+                        profile: MANUAL_PROFILE,
+                        side: Side::Buy,
+                        contract_address: &lookup,
+                        creator: &creator,
+                        size,
+                    };
+                    let verdict = risk::reserve_position(db, &risk, &order, unix_now())?;
+                    Ok(Some((coin, verdict)))
+                })
+                .await;
+            match reserved {
+                Ok(None) => Ok(format!("{} is not a saved coin.", address)),
+                Ok(Some((_, verdict))) if !verdict.approved => Ok(format!("Order rejected: {}", verdict.reason)),
+                Ok(Some((coin, verdict))) => {
+                    let request = TradeRequest {
+                        contract_address: address,
+                        side: Side::Buy,
+                        amount_in: verdict.size,
+                        quote: Pool::migrated(coin.initial_liquidity),
+                        max_slippage_bps,
+                    };
+                    Ok(match paper::execute_open(db, executor, MANUAL_PROFILE, request).await {
+                        Ok(fill) => format!(
+                            "Bought {:.0} {} for {:.3} SOL via {} ({}).",
+                            fill.amount_out, coin.symbol, fill.amount_in, fill.executor, verdict.reason
+                        ),
+                        Err(e) => format!("Buy failed: {}", e),
+                    })
+                }
+                Err(e) => Err(e),
+            }
+        }
+        "/sell" => {
+            let Some(address) = rest.first().and_then(|a| a.parse::<Address>().ok()) else {
+                return "Usage: /sell <contract address>".to_string();
+            };
+            let (risk, lookup) = (risk.clone(), address.clone());
+            let held = db
+                .call(move |db| {
+                    let Some(position) = paper::position(db, MANUAL_PROFILE, &lookup)? else {
+                        return Ok(None);
+                    };
+                    let creator = position.creator_wallet.parse::<Address>().unwrap_or_else(|_| Address::evm_zero());
+                    let order = Order {
+                        profile: MANUAL_PROFILE,
+                        side: Side::Sell,
+                        contract_address: &lookup,
+                        creator: &creator,
+                        size: position.size,
+                    };
+                    let verdict = risk::review(db, &risk, &order, unix_now())?;
+                    risk::record(db, &order, &verdict)?;
+                    // Review only approves sells of filled positions.
+                    let Some(tokens) = position.tokens.filter(|_| verdict.approved) else {
+                        return Ok(Some(Err(verdict)));
+                    };
+                    // Quote from the latest tracked sample, falling back to
+                    // the pool at migration.
+                    let quote = match tracker::load_snapshots(db, &lookup)?.last() {
+                        Some(snapshot) if snapshot.price.is_finite() && snapshot.price > 0.0 => Pool {
+                            sol_reserve: snapshot.liquidity,
+                            token_reserve: snapshot.liquidity / snapshot.price,
+                        },
+                        _ => Pool::migrated(coins::find(db, &lookup)?.map_or(0.0, |coin| coin.initial_liquidity)),
+                    };
+                    Ok(Some(Ok((tokens, position.size, quote))))
+                })
+                .await;
+            match held {
+                Ok(Some(Err(verdict))) => Ok(format!("Order rejected: {}", verdict.reason)),
+                Ok(Some(Ok((tokens, size, quote)))) => {
+                    let request = TradeRequest {
+                        contract_address: address,
+                        side: Side::Sell,
+                        amount_in: tokens,
+                        quote,
+                        max_slippage_bps,
+                    };
+                    Ok(match paper::execute_close(db, executor, MANUAL_PROFILE, request).await {
+                        Ok(fill) => format!(
+                            "Sold for {:.3} SOL via {} ({:+.3}).",
+                            fill.amount_out,
+                            fill.executor,
+                            fill.amount_out - size
+                        ),
+                        Err(e) => format!("Sell failed: {}", e),
+                    })
+                }
+                Ok(None) => Ok(format!("No manual position in {}.", address)),
+                Err(e) => Err(e),
+            }
+        }
+        _ => Ok("Commands: /buy <address> [size], /sell <address>, /risk, /kill [reason], /resume".to_string()),
+    };
+    result.unwrap_or_else(|e| format!("Error: {}", e))
+}
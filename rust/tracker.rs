@@ -0,0 +1,414 @@
+use std::time::Duration;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::address::Address;
+use crate::db::DbHandle;
+
+#[derive(Debug, Clone)]
+pub struct TrackerConfig {
+    pub window_hours: u64,
+    pub initial_interval_secs: u64,
+    pub backoff: f64,
+    pub max_interval_secs: u64,
+    /// A coin counts as rugged once liquidity falls below this fraction of
+    /// its first sample, or price falls below it relative to entry.
+    pub rug_ratio: f64,
+}
+
+impl Default for TrackerConfig {
+    fn default() -> Self {
+        TrackerConfig {
+            window_hours: 24,
+            initial_interval_secs: 60,
+            backoff: 1.5,
+            max_interval_secs: 3600,
+            rug_ratio: 0.2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Snapshot {
+    pub sampled_at: i64,
+    pub price: f64,
+    pub liquidity: f64,
+    pub holders: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawMarketData {
+    price: Option<f64>,
+    liquidity: Option<f64>,
+    #[serde(rename = "holderCount")]
+    holder_count: Option<i64>,
+}
+
+/// Outcome labels for a coin whose tracking window has closed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub entry_price: f64,
+    pub peak_multiple: f64,
+    pub final_multiple: f64,
+    pub max_drawdown: f64,
+    pub rugged: bool,
+    /// Seconds from the first sample until the coin was first seen rugged.
+    pub rugged_after_secs: Option<i64>,
+}
+
+pub fn create_tables(db: &Connection) -> rusqlite::Result<()> {
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS coin_snapshots (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            contract_address TEXT NOT NULL,
+            sampled_at INTEGER NOT NULL,
+            price REAL,
+            liquidity REAL,
+            holders INTEGER
+        )",
+        [],
+    )?;
+    db.execute(
+        "CREATE INDEX IF NOT EXISTS idx_coin_snapshots_address
+         ON coin_snapshots (contract_address, sampled_at)",
+        [],
+    )?;
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS tracked_coins (
+            contract_address TEXT PRIMARY KEY,
+            started_at INTEGER NOT NULL,
+            next_sample_at INTEGER NOT NULL,
+            interval_secs INTEGER NOT NULL,
+            finished INTEGER NOT NULL DEFAULT 0
+        )",
+        [],
+    )?;
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS coin_outcomes (
+            contract_address TEXT PRIMARY KEY,
+            entry_price REAL,
+            peak_multiple REAL,
+            final_multiple REAL,
+            max_drawdown REAL,
+            rugged INTEGER,
+            rugged_after_secs INTEGER,
+            computed_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+pub struct Tracker {
+    config: TrackerConfig,
+    api_base: String,
+    api_key: String,
+}
+
+impl Tracker {
+    pub fn new(config: TrackerConfig, api_base: &str, api_key: &str) -> Self {
+        Tracker {
+            config,
+            api_base: api_base.to_string(),
+            api_key: api_key.to_string(),
+        }
+    }
+
+    /// Starts tracking an accepted coin. The first sample is taken on the
+    /// next call to [`Tracker::run_due`].
+    pub fn start(&self, db: &Connection, address: &Address, now: i64) -> rusqlite::Result<()> {
+        db.execute(
+            "INSERT OR IGNORE INTO tracked_coins (contract_address, started_at, next_sample_at, interval_secs)
+             VALUES (?1, ?2, ?2, ?3)",
+            params![address, now, self.config.initial_interval_secs as i64],
+        )?;
+        Ok(())
+    }
+
+    /// Samples every coin whose next sample is due and closes out coins whose
+    /// window has elapsed. Returns the number of coins sampled.
+    pub async fn run_due(&self, db: &DbHandle, now: i64) -> Result<usize, Box<dyn std::error::Error>> {
+        let due: Vec<(Address, i64, i64)> = db
+            .call(move |db| {
+                let mut stmt = db.prepare(
+                    "SELECT contract_address, started_at, interval_secs FROM tracked_coins
+                     WHERE finished = 0 AND next_sample_at <= ?1",
+                )?;
+                let rows = stmt.query_map(params![now], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+                rows.collect()
+            })
+            .await?;
+        let window = (self.config.window_hours * 3600) as i64;
+        let rug_ratio = self.config.rug_ratio;
+        let backoff = self.config.backoff;
+        let max_interval = self.config.max_interval_secs as i64;
+        let mut sampled = 0;
+        for (address, started_at, interval) in due {
+            let snapshot = match self.fetch_snapshot(&address, now).await {
+                Ok(snapshot) => {
+                    sampled += 1;
+                    Some(snapshot)
+                }
+                Err(e) => {
+                    println!("[TRACKER] Sample failed for {}: {}", address, e);
+                    None
+                }
+            };
+            db.call(move |db| {
+                if let Some(snapshot) = snapshot {
+                    record_snapshot(db, &address, &snapshot)?;
+                }
+                if now - started_at >= window {
+                    return finish(db, &address, rug_ratio);
+                }
+                let next_interval = ((interval as f64 * backoff) as i64).clamp(1, max_interval);
+                db.execute(
+                    "UPDATE tracked_coins SET next_sample_at = ?2, interval_secs = ?3 WHERE contract_address = ?1",
+                    params![address, now + interval, next_interval],
+                )?;
+                Ok(())
+            })
+            .await?;
+        }
+        Ok(sampled)
+    }
+
+    async fn fetch_snapshot(&self, address: &Address, now: i64) -> Result<Snapshot, Box<dyn std::error::Error>> {
+        let url = format!("{}/coins/{}", self.api_base, address);
+        let res = reqwest::Client::new()
+            .get(&url)
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await?
+            .error_for_status()?;
+        let json: Value = res.json().await?;
+        let data = json.get("data").ok_or("Missing data field")?;
+        let raw: RawMarketData = serde_json::from_value(data.clone())?;
+        // A missing price or liquidity is not a zero: recording one would
+        // label the coin as rugged, so the sample is skipped instead.
+        Ok(Snapshot {
+            sampled_at: now,
+            price: raw.price.ok_or("Missing price")?,
+            liquidity: raw.liquidity.ok_or("Missing liquidity")?,
+            holders: raw.holder_count.unwrap_or(0),
+        })
+    }
+}
+
+fn finish(db: &Connection, address: &Address, rug_ratio: f64) -> rusqlite::Result<()> {
+    let snapshots = load_snapshots(db, address)?;
+    if let Some(outcome) = compute_outcome(&snapshots, rug_ratio) {
+        save_outcome(db, address, &outcome)?;
+        println!(
+            "[TRACKER] {} closed: peak {:.2}x, drawdown {:.0}%, rugged {}",
+            address,
+            outcome.peak_multiple,
+            outcome.max_drawdown * 100.0,
+            outcome.rugged
+        );
+    }
+    db.execute(
+        "UPDATE tracked_coins SET finished = 1 WHERE contract_address = ?1",
+        params![address],
+    )?;
+    Ok(())
+}
+
+pub fn record_snapshot(db: &Connection, address: &Address, snapshot: &Snapshot) -> rusqlite::Result<()> {
+    db.execute(
+        "INSERT INTO coin_snapshots (contract_address, sampled_at, price, liquidity, holders)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![address, snapshot.sampled_at, snapshot.price, snapshot.liquidity, snapshot.holders],
+    )?;
+    Ok(())
+}
+
+pub fn load_snapshots(db: &Connection, address: &Address) -> rusqlite::Result<Vec<Snapshot>> {
+    let mut stmt = db.prepare(
+        "SELECT sampled_at, price, liquidity, holders FROM coin_snapshots
+         WHERE contract_address = ?1 ORDER BY sampled_at",
+    )?;
+    let rows = stmt.query_map(params![address], |row| {
+        Ok(Snapshot {
+            sampled_at: row.get(0)?,
+            price: row.get(1)?,
+            liquidity: row.get(2)?,
+            holders: row.get(3)?,
+        })
+    })?;
+    rows.collect()
+}
+
+pub fn save_outcome(db: &Connection, address: &Address, outcome: &Outcome) -> rusqlite::Result<()> {
+    db.execute(
+        "INSERT OR REPLACE INTO coin_outcomes
+         (contract_address, entry_price, peak_multiple, final_multiple, max_drawdown, rugged, rugged_after_secs)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            address,
+            outcome.entry_price,
+            outcome.peak_multiple,
+            outcome.final_multiple,
+            outcome.max_drawdown,
+            outcome.rugged,
+            outcome.rugged_after_secs
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn load_outcome(db: &Connection, address: &Address) -> rusqlite::Result<Option<Outcome>> {
+    db.query_row(
+        "SELECT entry_price, peak_multiple, final_multiple, max_drawdown, rugged, rugged_after_secs
+         FROM coin_outcomes WHERE contract_address = ?1",
+        params![address],
+        |row| {
+            Ok(Outcome {
+                entry_price: row.get(0)?,
+                peak_multiple: row.get(1)?,
+                final_multiple: row.get(2)?,
+                max_drawdown: row.get(3)?,
+                rugged: row.get(4)?,
+                rugged_after_secs: row.get(5)?,
+            })
+        },
+    )
+    .optional()
+}
+
+/// Derives outcome labels from a time-ordered series of snapshots. The first
+/// sample with a positive price is the entry; returns `None` if there is none.
+pub fn compute_outcome(snapshots: &[Snapshot], rug_ratio: f64) -> Option<Outcome> {
+    let first = snapshots.iter().position(|s| s.price > 0.0)?;
+    let series = &snapshots[first..];
+    let entry = series[0];
+    let mut peak = entry.price;
+    let mut max_drawdown: f64 = 0.0;
+    let mut rugged_after_secs = None;
+    for s in series {
+        peak = peak.max(s.price);
+        if peak > 0.0 {
+            max_drawdown = max_drawdown.max((peak - s.price) / peak);
+        }
+        let liquidity_pulled = entry.liquidity > 0.0 && s.liquidity < entry.liquidity * rug_ratio;
+        let price_collapsed = s.price < entry.price * rug_ratio;
+        if rugged_after_secs.is_none() && (liquidity_pulled || price_collapsed) {
+            rugged_after_secs = Some(s.sampled_at - entry.sampled_at);
+        }
+    }
+    let last = series[series.len() - 1];
+    Some(Outcome {
+        entry_price: entry.price,
+        peak_multiple: peak / entry.price,
+        final_multiple: last.price / entry.price,
+        max_drawdown,
+        rugged: rugged_after_secs.is_some(),
+        rugged_after_secs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Samples a minute apart, from (price, liquidity) pairs.
+    fn series(points: &[(f64, f64)]) -> Vec<Snapshot> {
+        points
+            .iter()
+            .enumerate()
+            .map(|(i, &(price, liquidity))| Snapshot {
+                sampled_at: 1_700_000_000 + 60 * i as i64,
+                price,
+                liquidity,
+                holders: 100,
+            })
+            .collect()
+    }
+
+    fn assert_outcome(name: &str, points: &[(f64, f64)], expected: Outcome) {
+        let outcome = compute_outcome(&series(points), 0.2).unwrap_or_else(|| panic!("{name}: no outcome"));
+        assert_eq!(outcome.rugged, expected.rugged, "{name}");
+        assert_eq!(outcome.rugged_after_secs, expected.rugged_after_secs, "{name}");
+        for (field, got, want) in [
+            ("entry_price", outcome.entry_price, expected.entry_price),
+            ("peak_multiple", outcome.peak_multiple, expected.peak_multiple),
+            ("final_multiple", outcome.final_multiple, expected.final_multiple),
+            ("max_drawdown", outcome.max_drawdown, expected.max_drawdown),
+        ] {
+            assert!((got - want).abs() < 1e-9, "{name}: {field} is {got}, expected {want}");
+        }
+    }
+
+    #[test]
+    fn outcomes_are_labelled_from_snapshots() {
+        assert_outcome(
+            "pump",
+            &[(1.0, 10.0), (2.0, 20.0), (4.0, 40.0), (3.0, 30.0)],
+            Outcome {
+                entry_price: 1.0,
+                peak_multiple: 4.0,
+                final_multiple: 3.0,
+                max_drawdown: 0.25,
+                rugged: false,
+                rugged_after_secs: None,
+            },
+        );
+        assert_outcome(
+            "flat",
+            &[(1.0, 10.0), (1.0, 10.0), (1.0, 10.0)],
+            Outcome {
+                entry_price: 1.0,
+                peak_multiple: 1.0,
+                final_multiple: 1.0,
+                max_drawdown: 0.0,
+                rugged: false,
+                rugged_after_secs: None,
+            },
+        );
+        assert_outcome(
+            "rug by pulled liquidity",
+            &[(1.0, 10.0), (1.5, 10.0), (1.0, 1.0), (0.5, 1.0)],
+            Outcome {
+                entry_price: 1.0,
+                peak_multiple: 1.5,
+                final_multiple: 0.5,
+                max_drawdown: 1.0 / 1.5,
+                rugged: true,
+                rugged_after_secs: Some(120),
+            },
+        );
+        assert_outcome(
+            "rug by collapsed price",
+            &[(1.0, 10.0), (0.5, 10.0), (0.1, 10.0)],
+            Outcome {
+                entry_price: 1.0,
+                peak_multiple: 1.0,
+                final_multiple: 0.1,
+                max_drawdown: 0.9,
+                rugged: true,
+                rugged_after_secs: Some(120),
+            },
+        );
+        assert_outcome(
+            "entry after samples without a price",
+            &[(0.0, 0.0), (0.0, 5.0), (2.0, 10.0), (4.0, 10.0)],
+            Outcome {
+                entry_price: 2.0,
+                peak_multiple: 2.0,
+                final_multiple: 2.0,
+                max_drawdown: 0.0,
+                rugged: false,
+                rugged_after_secs: None,
+            },
+        );
+    }
+
+    #[test]
+    fn no_outcome_without_a_priced_sample() {
+        assert_eq!(compute_outcome(&[], 0.2), None);
+        assert_eq!(compute_outcome(&series(&[(0.0, 10.0), (0.0, 0.0)]), 0.2), None);
+    }
+}